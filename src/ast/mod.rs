@@ -1,7 +1,10 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fmt;
 
 use crate::interp::Func;
 use crate::interp::Value;
+use crate::source::Span;
 
 pub type Prog = Vec<Stmt>;
 
@@ -17,9 +20,16 @@ pub enum BinaryOp {
     Sub,
     Mul,
     Div,
+    IntDiv,
     Mod,
     Pow,
 
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+
     Great,
     Less,
 
@@ -40,6 +50,8 @@ pub enum Expr {
         l: Box<Expr>,
         r: Box<Expr>,
         inclusive: bool,
+        step: Option<Box<Expr>>,
+        span: Span,
     },
 
     Interp {
@@ -47,25 +59,31 @@ pub enum Expr {
         exprs: Vec<Expr>,
     },
 
-    Unary(UnaryOp, Box<Expr>),
-    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    // The span of unary and binary expressions is the one of the operator
+    Unary(UnaryOp, Box<Expr>, Span),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>, Span),
 
-    Get(String),
-    Set(String, Box<Expr>),
+    Get(String, Span),
+    Set(String, Box<Expr>, Span),
 
     GetField {
         base: Box<Expr>,
         index: Box<Expr>,
+        span: Span,
     },
     SetField {
         base: Box<Expr>,
         index: Box<Expr>,
         expr: Box<Expr>,
+        span: Span,
     },
 
     Call {
         func: Box<Expr>,
         args: Vec<Expr>,
+        // Arguments given by parameter name, as in `deploy(env: 'prod')`
+        named: Vec<(String, Expr)>,
+        span: Span,
     },
 
     Cmd(Cmd),
@@ -77,11 +95,12 @@ pub enum Expr {
 pub enum Stmt {
     Expr(Expr),
     Cmd(Cmd),
-    Import(String),
+    Import(String, Span),
     Let {
         is_exp: bool,
-        name: String,
+        pattern: Pattern,
         init: Option<Expr>,
+        span: Span,
     },
     Block(Vec<Stmt>),
     If {
@@ -90,19 +109,139 @@ pub enum Stmt {
         else_do: Option<Box<Stmt>>,
     },
     For {
-        lvar: String,
-        rvar: Option<String>,
+        lvar: Pattern,
+        rvar: Option<Pattern>,
         iterated: Expr,
         each_do: Box<Stmt>,
+        span: Span,
     },
     While {
         cond: Expr,
         then_do: Box<Stmt>,
     },
     Func(Func),
-    Continue,
-    Break,
-    Return(Option<Expr>),
+    Continue(Span),
+    Break(Span),
+    Return(Option<Expr>, Span),
+    Try {
+        try_do: Box<Stmt>,
+        // Name the error is bound to inside `catch_do`, if any
+        err_var: Option<String>,
+        catch_do: Option<Box<Stmt>>,
+        finally_do: Option<Box<Stmt>>,
+    },
+    Throw(Expr, Span),
+    Match {
+        subject: Expr,
+        arms: Vec<MatchArm>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Stmt,
+}
+
+/// What a value is matched against in a `match` arm, or destructured with in `let`, `for` and parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    // `_`, matches anything
+    Any,
+    // Matches anything and binds it to the name
+    Bind(String, Span),
+    Value(Expr),
+    // Matches the numbers in the range, the bounds are ints or floats
+    Range {
+        l: Box<Value>,
+        r: Box<Value>,
+        inclusive: bool,
+    },
+    // `re '...'`, matches strings and binds their named groups
+    Regex(String),
+    // `glob '...'`, matches strings like file names
+    Glob(String),
+    // `[a, ...rest, b]`, the rest is the position it's at and the name it's bound to, if any
+    Vec {
+        items: Vec<Pattern>,
+        rest: Option<(usize, Option<String>)>,
+    },
+    // `{name, version: v}`, matches dicts that have the keys
+    Dict(Vec<(String, Pattern)>),
+    Or(Vec<Pattern>),
+    // `pattern = default`, for items of vecs and dicts that may be missing. The span is the one of the default.
+    Default {
+        pattern: Box<Pattern>,
+        default: Expr,
+        span: Span,
+    },
+}
+
+impl Pattern {
+    /// The name a parameter with this pattern can be given by, which it only has if it binds the whole value.
+    pub fn param_name(&self) -> Option<&str> {
+        match self {
+            Pattern::Bind(name, _) => Some(name),
+            Pattern::Default { pattern, .. } => pattern.param_name(),
+            _ => None,
+        }
+    }
+
+    /// The variables the pattern binds, with where they are declared if they're named in the pattern itself.
+    pub fn names(&self) -> Vec<(String, Option<Span>)> {
+        match self {
+            Pattern::Any | Pattern::Value(_) | Pattern::Range { .. } | Pattern::Glob(_) => Vec::new(),
+            Pattern::Bind(name, span) => vec![(name.clone(), Some(span.clone()))],
+            Pattern::Regex(pattern) => regex::Regex::new(pattern)
+                .map(|re| re.capture_names().flatten().map(|name| (name.to_string(), None)).collect())
+                .unwrap_or_default(),
+            Pattern::Vec { items, rest } => {
+                let mut names = items.iter().flat_map(Pattern::names).collect::<Vec<(String, Option<Span>)>>();
+                if let Some((_, Some(name))) = rest {
+                    names.push((name.clone(), None));
+                }
+                names
+            }
+            Pattern::Dict(entries) => entries.iter().flat_map(|(_, pattern)| pattern.names()).collect(),
+            Pattern::Or(alternatives) => alternatives.iter().flat_map(Pattern::names).collect(),
+            Pattern::Default { pattern, .. } => pattern.names(),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Any => write!(f, "_"),
+            Pattern::Bind(name, _) => write!(f, "{}", name),
+            Pattern::Value(Expr::Literal(val)) => write!(f, "{}", val.to_string_quoted()),
+            Pattern::Value(_) => write!(f, "\"...\""),
+            Pattern::Range { l, r, inclusive } => write!(f, "{}..{}{}", l, if *inclusive { "=" } else { "" }, r),
+            Pattern::Regex(pattern) => write!(f, "re {}", Value::String(pattern.clone()).to_string_quoted()),
+            Pattern::Glob(pattern) => write!(f, "glob {}", Value::String(pattern.clone()).to_string_quoted()),
+            Pattern::Vec { items, rest } => {
+                let mut items = items.iter().map(Pattern::to_string).collect::<Vec<String>>();
+                if let Some((at, name)) = rest {
+                    items.insert(*at, format!("...{}", name.as_deref().unwrap_or("")));
+                }
+                write!(f, "[{}]", items.join(", "))
+            }
+            Pattern::Dict(entries) => {
+                let entries = entries.iter().map(|(key, pattern)| match pattern {
+                    Pattern::Bind(name, _) if name == key => key.clone(),
+                    Pattern::Default { pattern, span, .. } if matches!(&**pattern, Pattern::Bind(name, _) if name == key) => {
+                        format!("{} = {}", key, span.text())
+                    }
+                    _ => format!("{}: {}", key, pattern),
+                }).collect::<Vec<String>>();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Pattern::Or(alternatives) => {
+                write!(f, "{}", alternatives.iter().map(Pattern::to_string).collect::<Vec<String>>().join(" | "))
+            }
+            Pattern::Default { pattern, span, .. } => write!(f, "{} = {}", pattern, span.text()),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -128,6 +267,16 @@ pub enum CmdOp {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Cmd {
-    Atom(Vec<Vec<Expr>>),
+    Atom(Vec<Vec<Expr>>, Span),
     Op(Box<Cmd>, CmdOp, Box<Cmd>),
 }
+
+impl Cmd {
+    /// Returns a span covering the whole command, from its first to its last atom.
+    pub fn span(&self) -> Span {
+        match self {
+            Cmd::Atom(_, span) => span.clone(),
+            Cmd::Op(lhs, _, rhs) => lhs.span().to(&rhs.span()),
+        }
+    }
+}
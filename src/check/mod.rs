@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{Cmd, Expr, Stmt};
+use crate::interp::{Func, import_path};
+use crate::lexer::new as new_lexer;
+use crate::parser::{ParseError, Parser};
+use crate::source::{Source, Span};
+
+#[cfg(test)]
+mod test;
+
+/// Parses the file at `path` and, recursively, every file it imports without running any of them. Returns the
+/// errors found in all of them.
+pub fn check(path: &Path) -> io::Result<Vec<ParseError>> {
+    let text = fs::read_to_string(path)?;
+
+    let mut checker = Checker {
+        visited: HashSet::new(),
+        errors: Vec::new(),
+    };
+    checker.check_file(path, text);
+
+    Ok(checker.errors)
+}
+
+struct Checker {
+    // Canonical paths of the files already checked, so that each file is reported once and import cycles end
+    visited: HashSet<PathBuf>,
+    errors: Vec<ParseError>,
+}
+
+impl Checker {
+    fn check_file(&mut self, path: &Path, text: String) {
+        if let Ok(canonical_path) = fs::canonicalize(path) {
+            if !self.visited.insert(canonical_path) {
+                return;
+            }
+        }
+
+        let source = Source::new(path.display().to_string(), text);
+        let (prog, mut errors) = Parser::new(new_lexer(source)).parse();
+        self.errors.append(&mut errors);
+
+        // Imports are relative to the directory of the file that contains them
+        let import_root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        let mut imports = Vec::new();
+        for stmt in &prog {
+            collect_stmt_imports(stmt, &mut imports);
+        }
+
+        for (base_path, span) in imports {
+            let path = import_path(&import_root, &base_path);
+
+            match fs::read_to_string(&path) {
+                Ok(text) => self.check_file(&path, text),
+                Err(err) => self.errors.push(ParseError {
+                    message: format!("couldn't read imported file {}: {}", path.display(), err),
+                    span,
+                }),
+            }
+        }
+    }
+}
+
+fn collect_stmt_imports(stmt: &Stmt, imports: &mut Vec<(String, Span)>) {
+    match stmt {
+        Stmt::Import(base_path, span) => imports.push((base_path.clone(), span.clone())),
+        Stmt::Expr(expr) => collect_expr_imports(expr, imports),
+        Stmt::Cmd(cmd) => collect_cmd_imports(cmd, imports),
+        Stmt::Let { init: Some(init), .. } => collect_expr_imports(init, imports),
+        Stmt::Block(stmts) => {
+            for stmt in stmts {
+                collect_stmt_imports(stmt, imports);
+            }
+        }
+        Stmt::If { cond, then_do, else_do } => {
+            collect_expr_imports(cond, imports);
+            collect_stmt_imports(then_do, imports);
+            if let Some(else_do) = else_do {
+                collect_stmt_imports(else_do, imports);
+            }
+        }
+        Stmt::For { iterated, each_do, .. } => {
+            collect_expr_imports(iterated, imports);
+            collect_stmt_imports(each_do, imports);
+        }
+        Stmt::While { cond, then_do } => {
+            collect_expr_imports(cond, imports);
+            collect_stmt_imports(then_do, imports);
+        }
+        Stmt::Func(Func::User { body, .. }) => collect_stmt_imports(body, imports),
+        Stmt::Return(Some(expr), _) | Stmt::Throw(expr, _) => collect_expr_imports(expr, imports),
+        Stmt::Try { try_do, catch_do, finally_do, .. } => {
+            collect_stmt_imports(try_do, imports);
+            for stmt in catch_do.iter().chain(finally_do) {
+                collect_stmt_imports(stmt, imports);
+            }
+        }
+        Stmt::Match { subject, arms } => {
+            collect_expr_imports(subject, imports);
+            for arm in arms {
+                collect_stmt_imports(&arm.body, imports);
+            }
+        }
+        _ => (),
+    }
+}
+
+// Expressions can only contain imports in the body of lambdas
+fn collect_expr_imports(expr: &Expr, imports: &mut Vec<(String, Span)>) {
+    match expr {
+        Expr::Lambda(Func::User { body, .. }) => collect_stmt_imports(body, imports),
+        Expr::Vec(exprs) | Expr::Interp { exprs, .. } => {
+            for expr in exprs {
+                collect_expr_imports(expr, imports);
+            }
+        }
+        Expr::Dict(dict) => {
+            for expr in dict.values() {
+                collect_expr_imports(expr, imports);
+            }
+        }
+        Expr::Range { l, r, step, .. } => {
+            collect_expr_imports(l, imports);
+            collect_expr_imports(r, imports);
+            if let Some(step) = step {
+                collect_expr_imports(step, imports);
+            }
+        }
+        Expr::Binary(l, _, r, _) | Expr::GetField { base: l, index: r, .. } => {
+            collect_expr_imports(l, imports);
+            collect_expr_imports(r, imports);
+        }
+        Expr::Unary(_, expr, _) | Expr::Set(_, expr, _) => collect_expr_imports(expr, imports),
+        Expr::SetField { base, index, expr, .. } => {
+            collect_expr_imports(base, imports);
+            collect_expr_imports(index, imports);
+            collect_expr_imports(expr, imports);
+        }
+        Expr::Call { func, args, named, .. } => {
+            collect_expr_imports(func, imports);
+            for arg in args.iter().chain(named.iter().map(|(_, arg)| arg)) {
+                collect_expr_imports(arg, imports);
+            }
+        }
+        Expr::Cmd(cmd) => collect_cmd_imports(cmd, imports),
+        _ => (),
+    }
+}
+
+fn collect_cmd_imports(cmd: &Cmd, imports: &mut Vec<(String, Span)>) {
+    match cmd {
+        Cmd::Atom(segments, _) => {
+            for expr in segments.iter().flatten() {
+                collect_expr_imports(expr, imports);
+            }
+        }
+        Cmd::Op(lhs, _, rhs) => {
+            collect_cmd_imports(lhs, imports);
+            collect_cmd_imports(rhs, imports);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::PathBuf;
+
+use super::*;
+
+fn write_files(dir: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("koi-check-{}-{}", dir, std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+
+    for (name, content) in files {
+        fs::write(dir.join(name), content).unwrap();
+    }
+
+    dir
+}
+
+#[test]
+fn checks_imports_recursively() {
+    let dir = write_files("recursive", &[
+        ("main.koi", "import 'lib/a'\nlet = 1"),
+        ("lib/a.koi", "import 'b.koi'\nfn f() {\n    import 'missing'\n}"),
+        ("lib/b.koi", "print(1 +)"),
+    ]);
+
+    let errors = check(&dir.join("main.koi")).unwrap();
+
+    assert_eq!(
+        errors.iter().map(|err| (err.span.source.name.rsplit('/').next().unwrap(), err.span.line)).collect::<Vec<(&str, usize)>>(),
+        vec![("main.koi", 2), ("b.koi", 1), ("a.koi", 3)],
+    );
+    assert!(errors[2].message.starts_with("couldn't read imported file"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stops_at_import_cycles() {
+    let dir = write_files("cycle", &[
+        ("main.koi", "import 'other'"),
+        ("other.koi", "import 'main'\nlet = 1"),
+    ]);
+
+    assert_eq!(check(&dir.join("main.koi")).unwrap().len(), 1);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn checks_without_running() {
+    let dir = write_files("no_run", &[
+        ("main.koi", "touch should_not_exist\nexit(1)"),
+    ]);
+
+    assert!(check(&dir.join("main.koi")).unwrap().is_empty());
+    assert!(!PathBuf::from("should_not_exist").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,194 @@
+use std::fs;
+
+use crate::interp::{executables, method_names, Interpreter, Value};
+use crate::lexer::new as new_lexer;
+use crate::parser::Parser;
+use crate::source::Source;
+
+#[cfg(test)]
+mod test;
+
+/// Ways to complete the word that ends at the cursor. Each candidate replaces the text from `start` to the cursor.
+#[derive(Debug, PartialEq)]
+pub struct Completions {
+    pub start: usize,
+    pub candidates: Vec<String>,
+}
+
+/// Completes `text` at the byte offset `cursor` using what's defined in `interpreter`. In expressions the candidates
+/// are variable names or, after a dot, the methods and keys of the value before it. In commands they are executables
+/// in PATH for the program and files for the arguments.
+pub fn complete(interpreter: &Interpreter, text: &str, cursor: usize) -> Completions {
+    let line_start = text[..cursor].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[line_start..cursor];
+
+    let (start, candidates) = match cmd_start(line) {
+        Some((cmd_start, is_stmt)) => {
+            let (start, candidates) = complete_cmd(interpreter, &line[cmd_start..], is_stmt);
+            (cmd_start + start, candidates)
+        }
+        None => complete_expr(interpreter, line),
+    };
+
+    Completions {
+        start: line_start + start,
+        candidates,
+    }
+}
+
+/// Finds where the command that the end of `line` belongs to starts, together with whether the command is a whole
+/// statement. Returns `None` if the end of `line` is in an expression.
+fn cmd_start(line: &str) -> Option<(usize, bool)> {
+    // For every open parenthesis, where the command starts if it's a `$(`
+    let mut parens = Vec::new();
+    let mut quote = None;
+
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(delimiter), _) if c == delimiter => quote = None,
+            (Some(_), _) => (),
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, '(') => parens.push(if line[..i].ends_with('$') { Some(i + 1) } else { None }),
+            (None, ')') => {
+                parens.pop();
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(Some(start)) = parens.last() {
+        return Some((*start, false));
+    }
+
+    let stmt = line.trim_start();
+    let stmt_start = line.len() - stmt.len();
+
+    let mut parser = Parser::new(new_lexer(Source::new(String::new(), stmt.to_string())));
+    if !parser.is_cmd_next() {
+        return None;
+    }
+
+    Some((stmt_start + if stmt.starts_with('$') { 1 } else { 0 }, true))
+}
+
+fn complete_cmd(interpreter: &Interpreter, cmd: &str, is_stmt: bool) -> (usize, Vec<String>) {
+    // Only the last command of pipes and sequences matters
+    let atom_start = cmd.rfind(['|', '&', ';', '>', '<', '(']).map_or(0, |i| i + 1);
+    let is_redirection = matches!(cmd[..atom_start].chars().last(), Some('>') | Some('<'));
+
+    let atom = &cmd[atom_start..];
+    let word_start = atom.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let word = &atom[word_start..];
+
+    let is_program = atom[..word_start].trim().is_empty() && !is_redirection;
+
+    if !is_program || word.contains('/') {
+        return (atom_start + word_start, complete_path(word));
+    }
+
+    // Until the first word is over a statement could still turn out to be an expression like `f()` or `x.y = z`
+    if is_stmt && word.contains('.') {
+        let (start, candidates) = complete_expr(interpreter, atom);
+        return (atom_start + start, candidates);
+    }
+
+    let mut candidates = executables();
+    if is_stmt {
+        candidates.extend(interpreter.global_names());
+    }
+
+    (atom_start + word_start, filter(candidates, word))
+}
+
+fn complete_expr(interpreter: &Interpreter, expr: &str) -> (usize, Vec<String>) {
+    let word_start = ident_start(expr);
+    let word = &expr[word_start..];
+
+    let candidates = match expr[..word_start].strip_suffix('.') {
+        Some(base) => match base_value(interpreter, base) {
+            Some(Value::Dict(dict)) => {
+                let mut candidates = dict.borrow().keys().cloned().collect::<Vec<String>>();
+                candidates.extend(method_names(&Value::Dict(dict.clone())).into_iter().map(String::from));
+                candidates
+            }
+            Some(val) => method_names(&val).into_iter().map(String::from).collect(),
+            None => Vec::new(),
+        },
+        None => interpreter.global_names(),
+    };
+
+    (word_start, filter(candidates, word))
+}
+
+/// Value of the expression at the end of `text` if it's simple enough to know without running anything: a string
+/// literal or a chain of variables and dict keys like `a.b.c`.
+fn base_value(interpreter: &Interpreter, text: &str) -> Option<Value> {
+    if text.ends_with('\'') || text.ends_with('"') {
+        return Some(Value::String(String::new()));
+    }
+
+    let chain_start = text.char_indices().rev()
+        .take_while(|(_, c)| is_ident_char(*c) || *c == '.')
+        .last()
+        .map_or(text.len(), |(i, _)| i);
+
+    let mut names = text[chain_start..].split('.');
+
+    let mut val = match interpreter.get(names.next()?) {
+        Value::Nil => return None,
+        val => val,
+    };
+
+    for name in names {
+        val = match val {
+            Value::Dict(dict) => dict.borrow().get(name).cloned()?,
+            _ => return None,
+        };
+    }
+
+    Some(val)
+}
+
+fn complete_path(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+
+    let entries = match fs::read_dir(if dir.is_empty() { "." } else { dir }) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let candidates = entries.flatten().filter_map(|entry| {
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        // Hidden files only show up when asked for
+        if name.starts_with('.') && !prefix.starts_with('.') {
+            return None;
+        }
+
+        let suffix = if entry.path().is_dir() { "/" } else { "" };
+        Some(format!("{}{}{}", dir, name, suffix))
+    }).collect();
+
+    filter(candidates, word)
+}
+
+fn filter(mut candidates: Vec<String>, prefix: &str) -> Vec<String> {
+    candidates.retain(|candidate| candidate.starts_with(prefix));
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+fn ident_start(text: &str) -> usize {
+    text.char_indices().rev()
+        .take_while(|(_, c)| is_ident_char(*c))
+        .last()
+        .map_or(text.len(), |(i, _)| i)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,61 @@
+use super::*;
+
+fn interpreter(source: &str) -> Interpreter {
+    let (prog, errors) = Parser::new(new_lexer(Source::new(String::new(), source.to_owned()))).parse();
+    assert!(errors.is_empty());
+
+    let mut interpreter = Interpreter::new();
+    interpreter.do_collect();
+    interpreter.run(prog).unwrap();
+    interpreter
+}
+
+fn candidates(interpreter: &Interpreter, text: &str) -> Vec<String> {
+    complete(interpreter, text, text.len()).candidates
+}
+
+#[test]
+fn completes_names() {
+    let interpreter = interpreter("let answer = 42\nlet another = 1");
+
+    assert_eq!(candidates(&interpreter, "print(an"), vec!["another", "answer"]);
+    assert_eq!(complete(&interpreter, "let x = 1 + ans", 15), Completions {
+        start: 12,
+        candidates: vec!["answer".to_string()],
+    });
+}
+
+#[test]
+fn completes_methods_and_keys() {
+    let interpreter = interpreter("let s = 'a'\nlet d = {inner: {key: 1}}");
+
+    assert_eq!(candidates(&interpreter, "print(s.s"), vec!["split", "string", "strip"]);
+    assert_eq!(candidates(&interpreter, "print('x'.up"), vec!["upper"]);
+    assert_eq!(candidates(&interpreter, "print(d.in"), vec!["inner"]);
+    assert_eq!(candidates(&interpreter, "print(d.inner.k"), vec!["key"]);
+    assert_eq!(candidates(&interpreter, "d.inner.k"), vec!["key"]);
+    assert_eq!(candidates(&interpreter, "print(nope.s"), Vec::<String>::new());
+}
+
+#[test]
+fn completes_executables_in_command_position() {
+    let interpreter = interpreter("let shadow = 1");
+
+    assert!(candidates(&interpreter, "s").contains(&"sh".to_string()));
+    assert!(candidates(&interpreter, "s").contains(&"shadow".to_string()));
+    assert!(candidates(&interpreter, "echo 1 | s").contains(&"sh".to_string()));
+
+    let in_capture = candidates(&interpreter, "let x = $(s");
+    assert!(in_capture.contains(&"sh".to_string()));
+    assert!(!in_capture.contains(&"shadow".to_string()));
+}
+
+#[test]
+fn completes_paths_in_arguments() {
+    let interpreter = interpreter("");
+
+    assert_eq!(candidates(&interpreter, "ls src/interp/gol"), vec!["src/interp/golden/"]);
+    assert_eq!(candidates(&interpreter, "let x = $(cat src/interp/golden/try"), vec!["src/interp/golden/try.koi"]);
+    assert_eq!(candidates(&interpreter, "echo 1 > src/pars"), vec!["src/parser/"]);
+    assert_eq!(complete(&interpreter, "ls src/interp/gol", 17).start, 3);
+}
@@ -0,0 +1,373 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use itertools::Itertools;
+
+use super::{ErrorKind, RuntimeError, RuntimeResult, Value};
+
+/// Why the arguments couldn't be turned into values.
+#[derive(Debug, PartialEq)]
+pub enum ArgsError {
+    // The user asked for help, with the text to show
+    Help(String),
+    // With the message and the usage to show
+    Invalid(String, String),
+}
+
+/// The arguments a script accepts, built from the dict given to `parseArgs`:
+///
+/// ```text
+/// {
+///     name: 'deploy',
+///     help: 'Deploys the app',
+///     flags: {verbose: {short: 'v', help: 'Print more'}},
+///     options: {count: {type: 'num', default: 1, short: 'n', help: 'How many'}},
+///     positionals: ['env', {name: 'region', default: 'eu', help: 'Where'}],
+/// }
+/// ```
+///
+/// Every key is optional, flags and options can also be described by just their help text. Defaults must have the
+/// option's type. Short flags can be grouped, as in `-vq`.
+pub struct Spec {
+    name: String,
+    help: Option<String>,
+    flags: Vec<Flag>,
+    options: Vec<Opt>,
+    positionals: Vec<Positional>,
+}
+
+struct Flag {
+    name: String,
+    short: Option<char>,
+    help: Option<String>,
+}
+
+struct Opt {
+    name: String,
+    short: Option<char>,
+    help: Option<String>,
+    typ: String,
+    default: Value,
+}
+
+struct Positional {
+    name: String,
+    help: Option<String>,
+    // Positionals with a default can be left out
+    default: Option<Value>,
+}
+
+impl Spec {
+    pub fn from_value(spec: &Value) -> RuntimeResult<Spec> {
+        let spec = expect_dict(spec, "spec")?;
+
+        let name = match spec.get("name") {
+            Some(name) => expect_string(name, "name")?,
+            None => "script".to_string(),
+        };
+
+        let help = spec.get("help").map(|help| expect_string(help, "help")).transpose()?;
+
+        let mut flags = Vec::new();
+        if let Some(spec_flags) = spec.get("flags") {
+            for (name, flag) in sorted(&expect_dict(spec_flags, "flags")?) {
+                let (short, help) = describe(&flag, &name)?;
+                flags.push(Flag { name, short, help });
+            }
+        }
+
+        let mut options = Vec::new();
+        if let Some(spec_options) = spec.get("options") {
+            for (name, opt) in sorted(&expect_dict(spec_options, "options")?) {
+                let (short, help) = describe(&opt, &name)?;
+
+                let (typ, default) = match &opt {
+                    Value::Dict(opt) => {
+                        let opt = RefCell::borrow(opt);
+                        let typ = opt.get("type").map(|typ| expect_string(typ, "type")).transpose()?;
+                        (typ.unwrap_or_else(|| "string".to_string()), opt.get("default").cloned().unwrap_or(Value::Nil))
+                    }
+                    _ => ("string".to_string(), Value::Nil),
+                };
+
+                let is_default_ok = match typ.as_str() {
+                    "string" => matches!(default, Value::Nil | Value::String(_)),
+                    "num" => matches!(default, Value::Nil | Value::Int(_) | Value::Num(_)),
+                    "bool" => matches!(default, Value::Nil | Value::Bool(_)),
+                    _ => return Err(spec_error(format!("type of option {} must be string, num or bool, got {}", name, typ))),
+                };
+                if !is_default_ok {
+                    let expected = if typ == "num" { "int or float" } else { typ.as_str() };
+                    return Err(spec_error(format!(
+                        "default of option {} must be {}, got {}", name, expected, default.type_name(),
+                    )));
+                }
+
+                options.push(Opt { name, short, help, typ, default });
+            }
+        }
+
+        let mut positionals = Vec::new();
+        if let Some(spec_positionals) = spec.get("positionals") {
+            let spec_positionals = match spec_positionals {
+                Value::Vec(vec) => RefCell::borrow(vec).clone(),
+                val => return Err(spec_error(format!("positionals must be a vec, got {}", val.type_name()))),
+            };
+
+            for positional in spec_positionals {
+                positionals.push(match positional {
+                    Value::String(name) => Positional { name, help: None, default: None },
+                    Value::Dict(positional) => {
+                        let positional = RefCell::borrow(&positional);
+                        Positional {
+                            name: expect_string(positional.get("name").unwrap_or(&Value::Nil), "positional name")?,
+                            help: positional.get("help").map(|help| expect_string(help, "help")).transpose()?,
+                            default: positional.get("default").cloned(),
+                        }
+                    }
+                    val => return Err(spec_error(format!("positionals must be strings or dicts, got {}", val.type_name()))),
+                });
+            }
+        }
+
+        Ok(Spec { name, help, flags, options, positionals })
+    }
+
+    /// Parses `args` into a dict with a value for every flag, option and positional.
+    pub fn parse(&self, args: &[String]) -> Result<Value, ArgsError> {
+        let mut vals = HashMap::new();
+        for flag in &self.flags {
+            vals.insert(flag.name.clone(), Value::Bool(false));
+        }
+        for opt in &self.options {
+            vals.insert(opt.name.clone(), opt.default.clone());
+        }
+
+        let mut positionals = Vec::new();
+        let mut args = args.iter();
+        let mut are_options_over = false;
+
+        while let Some(arg) = args.next() {
+            if are_options_over || arg == "-" || !arg.starts_with('-') {
+                positionals.push(arg.clone());
+                continue;
+            }
+
+            if arg == "--" {
+                are_options_over = true;
+                continue;
+            }
+
+            if arg == "--help" || arg == "-h" {
+                return Err(ArgsError::Help(self.help()));
+            }
+
+            let (name, inline_val) = match arg.strip_prefix("--") {
+                Some(long) => match long.split_once('=') {
+                    Some((name, val)) => (name.to_string(), Some(val.to_string())),
+                    None => (long.to_string(), None),
+                },
+                None => {
+                    let mut chars = arg[1..].chars();
+
+                    loop {
+                        let short = chars.next().unwrap();
+                        let rest = chars.as_str();
+
+                        let name = self.flags.iter().filter_map(|flag| Some((flag.short?, &flag.name)))
+                            .chain(self.options.iter().filter_map(|opt| Some((opt.short?, &opt.name))))
+                            .find(|(flag_short, _)| *flag_short == short)
+                            .map(|(_, name)| name.clone())
+                            .ok_or_else(|| self.invalid(format!("unknown option -{}", short)))?;
+
+                        // Short flags can be grouped, as in `-abc`
+                        if !rest.is_empty() && self.flags.iter().any(|flag| flag.name == name) {
+                            vals.insert(name, Value::Bool(true));
+                            continue;
+                        }
+
+                        // Values can stick to short options, as in `-n3` or `-vn3`
+                        break (name, if rest.is_empty() { None } else { Some(rest.to_string()) });
+                    }
+                }
+            };
+
+            if self.flags.iter().any(|flag| flag.name == name) {
+                if inline_val.is_some() {
+                    return Err(self.invalid(format!("flag --{} doesn't take a value", name)));
+                }
+                vals.insert(name, Value::Bool(true));
+            } else if let Some(opt) = self.options.iter().find(|opt| opt.name == name) {
+                let val = match inline_val.or_else(|| args.next().cloned()) {
+                    Some(val) => val,
+                    None => return Err(self.invalid(format!("missing value for option --{}", name))),
+                };
+                vals.insert(name, self.convert(opt, val)?);
+            } else {
+                return Err(self.invalid(format!("unknown option --{}", name)));
+            }
+        }
+
+        if positionals.len() > self.positionals.len() {
+            return Err(self.invalid(format!("unexpected argument {}", positionals[self.positionals.len()])));
+        }
+
+        let mut positionals = positionals.into_iter();
+        for positional in &self.positionals {
+            let val = match (positionals.next(), &positional.default) {
+                (Some(val), _) => Value::String(val),
+                (None, Some(default)) => default.clone(),
+                (None, None) => return Err(self.invalid(format!("missing argument <{}>", positional.name))),
+            };
+            vals.insert(positional.name.clone(), val);
+        }
+
+        Ok(Value::Dict(Rc::new(RefCell::new(vals))))
+    }
+
+    fn convert(&self, opt: &Opt, val: String) -> Result<Value, ArgsError> {
+        let converted = match opt.typ.as_str() {
+            "num" => Value::parse_num(&val),
+            "bool" => match val.as_str() {
+                "true" => Some(Value::Bool(true)),
+                "false" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            _ => Some(Value::String(val.clone())),
+        };
+
+        // `num` options take either kind of number
+        let expected = if opt.typ == "num" { "int or float" } else { opt.typ.as_str() };
+        converted.ok_or_else(|| self.invalid(format!("invalid value '{}' for option --{}: expected {}", val, opt.name, expected)))
+    }
+
+    fn invalid(&self, message: String) -> ArgsError {
+        ArgsError::Invalid(message, self.usage())
+    }
+
+    fn usage(&self) -> String {
+        let mut usage = format!("USAGE:\n    {}", self.name);
+
+        if !self.flags.is_empty() {
+            usage.push_str(" [FLAGS]");
+        }
+        if !self.options.is_empty() {
+            usage.push_str(" [OPTIONS]");
+        }
+        for positional in &self.positionals {
+            usage.push_str(&format!(" {}", positional_label(positional)));
+        }
+
+        usage
+    }
+
+    fn help(&self) -> String {
+        let mut help = String::new();
+
+        if let Some(about) = &self.help {
+            help.push_str(&format!("{}\n\n", about));
+        }
+
+        help.push_str(&self.usage());
+
+        let mut flags = vec![("-h, --help".to_string(), "Prints help information".to_string())];
+        flags.extend(self.flags.iter().map(|flag| (
+            option_label(flag.short, &flag.name),
+            flag.help.clone().unwrap_or_default(),
+        )));
+        help.push_str(&section("FLAGS", &flags));
+
+        let options = self.options.iter().map(|opt| {
+            let mut text = opt.help.clone().unwrap_or_default();
+            if opt.default != Value::Nil {
+                text = format!("{} [default: {}]", text, opt.default).trim_start().to_string();
+            }
+            (format!("{} <{}>", option_label(opt.short, &opt.name), opt.typ), text)
+        }).collect::<Vec<(String, String)>>();
+        help.push_str(&section("OPTIONS", &options));
+
+        let positionals = self.positionals.iter()
+            .map(|positional| (positional_label(positional), positional.help.clone().unwrap_or_default()))
+            .collect::<Vec<(String, String)>>();
+        help.push_str(&section("ARGS", &positionals));
+
+        help
+    }
+}
+
+fn section(title: &str, rows: &[(String, String)]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+
+    format!("\n\n{}:\n{}", title, rows.iter()
+        .map(|(label, text)| format!("    {:width$}    {}", label, text, width = width).trim_end().to_string())
+        .join("\n"))
+}
+
+fn option_label(short: Option<char>, name: &str) -> String {
+    match short {
+        Some(short) => format!("-{}, --{}", short, name),
+        None => format!("    --{}", name),
+    }
+}
+
+fn positional_label(positional: &Positional) -> String {
+    if positional.default.is_some() {
+        format!("[{}]", positional.name)
+    } else {
+        format!("<{}>", positional.name)
+    }
+}
+
+/// Reads the short name and help text of a flag or option, described either by a dict or by its help text.
+fn describe(val: &Value, name: &str) -> RuntimeResult<(Option<char>, Option<String>)> {
+    match val {
+        Value::String(help) => Ok((None, Some(help.clone()))),
+        Value::Dict(dict) => {
+            let dict = RefCell::borrow(dict);
+
+            let short = match dict.get("short") {
+                Some(short) => {
+                    let short = expect_string(short, "short")?;
+                    let mut chars = short.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if c != '-' => Some(c),
+                        _ => return Err(spec_error(format!("short name of {} must be a single char, got '{}'", name, short))),
+                    }
+                }
+                None => None,
+            };
+
+            let help = dict.get("help").map(|help| expect_string(help, "help")).transpose()?;
+
+            Ok((short, help))
+        }
+        val => Err(spec_error(format!("{} must be described by a string or a dict, got {}", name, val.type_name()))),
+    }
+}
+
+fn sorted(dict: &HashMap<String, Value>) -> Vec<(String, Value)> {
+    dict.iter().map(|(k, v)| (k.clone(), v.clone())).sorted_by(|(a, _), (b, _)| a.cmp(b)).collect()
+}
+
+fn expect_dict(val: &Value, what: &str) -> RuntimeResult<HashMap<String, Value>> {
+    match val {
+        Value::Dict(dict) => Ok(RefCell::borrow(dict).clone()),
+        val => Err(spec_error(format!("{} must be a dict, got {}", what, val.type_name()))),
+    }
+}
+
+fn expect_string(val: &Value, what: &str) -> RuntimeResult<String> {
+    match val {
+        Value::String(str) => Ok(str.clone()),
+        val => Err(spec_error(format!("{} must be a string, got {}", what, val.type_name()))),
+    }
+}
+
+fn spec_error(message: String) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Value, format!("invalid parseArgs spec: {}", message))
+}
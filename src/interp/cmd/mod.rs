@@ -1,26 +1,39 @@
 use std::cell::RefCell;
 use std::fs::File;
+use std::io;
 use std::io::Read;
 use std::ops::DerefMut;
+use std::os::unix::process::ExitStatusExt;
 use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
 
 use either::Either;
+use itertools::Itertools;
 use os_pipe::{pipe, PipeReader, PipeWriter};
 
 use crate::ast::{Cmd, CmdOp, Expr};
+use crate::source::Span;
 
-use super::Interpreter;
+use super::{ErrorKind, Interpreter, RuntimeError, RuntimeResult};
 use super::value::Value;
 
+mod path;
+mod trace;
+
+pub use path::executables;
+
 #[cfg(test)]
 mod test;
 
 pub type OsEnv = Vec<(String, String)>;
 
 enum Process {
-    Std(Either<Command, Child>),
+    // The index identifies the atom the command was built from, to locate spawn failures
+    Std(Either<Command, Child>, usize),
+    // A command that couldn't be spawned, it behaves as if it exited like it would in a shell
+    Failed(ExitStatus),
     Pipe {
         lhs: Box<Process>,
         rhs: Box<Process>,
@@ -28,19 +41,31 @@ enum Process {
     Cond {
         op: CmdOp,
         procs: Option<Box<(Process, Process)>>,
-        handle: Option<JoinHandle<ExitStatus>>,
+        handle: Option<JoinHandle<(ExitStatus, bool)>>,
     },
 }
 
+struct SpawnFailure {
+    atom: usize,
+    program: String,
+    error: io::Error,
+}
+
+// Shared with the threads that run the right hand side of conditional commands
+type SpawnFailures = Arc<Mutex<Vec<SpawnFailure>>>;
+
 impl Process {
-    fn wait(&mut self) -> ExitStatus {
+    /// Waits for the process to exit. Also returns whether the exit status is the one of a command on the left of `&&`
+    /// or `||`, which errexit ignores like `set -e` does.
+    fn wait(&mut self) -> (ExitStatus, bool) {
         match self {
-            Process::Std(either) => {
+            Process::Std(either, _) => {
                 match either {
                     Either::Left(_) => panic!("process not spawned"),
-                    Either::Right(child) => child.wait().unwrap(),
+                    Either::Right(child) => (child.wait().unwrap(), false),
                 }
             }
+            Process::Failed(status) => (*status, false),
             Process::Pipe { lhs, rhs } => {
                 lhs.wait();
                 rhs.wait()
@@ -53,7 +78,7 @@ impl Process {
 
     fn set_env(&mut self, env: OsEnv) {
         match self {
-            Process::Std(either) => {
+            Process::Std(either, _) => {
                 match either {
                     Either::Left(cmd) => {
                         cmd.envs(env);
@@ -61,6 +86,7 @@ impl Process {
                     Either::Right(_) => panic!("process already spawned"),
                 }
             }
+            Process::Failed(_) => panic!("process already spawned"),
             Process::Pipe { lhs, rhs } => {
                 lhs.set_env(env.clone());
                 rhs.set_env(env);
@@ -73,29 +99,45 @@ impl Process {
         }
     }
 
-    fn spawn(&mut self) {
+    fn spawn(&mut self, failures: &SpawnFailures) {
         match self {
-            Process::Std(either) => {
-                match either {
-                    Either::Left(cmd) => {
-                        let child = cmd.spawn().unwrap();
-                        *either = Either::Right(child);
-                    }
+            Process::Std(either, atom) => {
+                let cmd = match either {
+                    Either::Left(cmd) => cmd,
                     Either::Right(_) => panic!("process already spawned"),
+                };
+
+                match cmd.spawn() {
+                    Ok(child) => *either = Either::Right(child),
+                    Err(error) => {
+                        // Like shells do, 127 if the program doesn't exist and 126 if it can't be executed
+                        let code = if error.kind() == io::ErrorKind::NotFound { 127 } else { 126 };
+
+                        failures.lock().unwrap().push(SpawnFailure {
+                            atom: *atom,
+                            program: cmd.get_program().to_string_lossy().into_owned(),
+                            error,
+                        });
+
+                        // Dropping the command closes its end of the pipes it is connected to
+                        *self = Process::Failed(ExitStatus::from_raw(code << 8));
+                    }
                 }
             }
+            Process::Failed(_) => panic!("process already spawned"),
             Process::Pipe { lhs, rhs } => {
-                lhs.spawn();
-                rhs.spawn();
+                lhs.spawn(failures);
+                rhs.spawn(failures);
             }
             Process::Cond { procs, handle, op } => {
                 let op = *op;
                 let (mut lhs, mut rhs) = *procs.take().unwrap();
 
-                lhs.spawn();
+                lhs.spawn(failures);
 
+                let failures = Arc::clone(failures);
                 *handle = Some(thread::spawn(move || {
-                    let lhs_exit = lhs.wait();
+                    let (lhs_exit, _) = lhs.wait();
 
                     let spawn_rhs = match op {
                         CmdOp::Seq => true,
@@ -105,10 +147,10 @@ impl Process {
                     };
 
                     if spawn_rhs {
-                        rhs.spawn();
+                        rhs.spawn(&failures);
                         rhs.wait()
                     } else {
-                        lhs_exit
+                        (lhs_exit, true)
                     }
                 }));
             }
@@ -136,9 +178,9 @@ impl Clone for Stream {
     }
 }
 
-impl Into<Stdio> for Stream {
-    fn into(self) -> Stdio {
-        match self {
+impl From<Stream> for Stdio {
+    fn from(stream: Stream) -> Self {
+        match stream {
             Stream::Inherit => Stdio::inherit(),
             Stream::Null => Stdio::null(),
             Stream::File(file) => Stdio::from(file),
@@ -149,39 +191,80 @@ impl Into<Stdio> for Stream {
 }
 
 impl Interpreter {
-    pub fn run_cmd_pipe(&mut self, cmd: Cmd, env: OsEnv) {
-        let mut cmd = self.build_cmd(cmd, Stream::Null, Stream::Inherit, Stream::Inherit);
-        cmd.set_env(env);
-        cmd.spawn();
-        let rc = cmd.wait();
-        self.set_rc(&rc);
+    pub fn run_cmd_pipe(&mut self, cmd: Cmd, env: OsEnv) -> RuntimeResult<()> {
+        self.run_process(cmd, env, Stream::Inherit, Stream::Inherit)
     }
 
-    pub fn run_cmd_capture(&mut self, cmd: Cmd, env: OsEnv, capture_err: bool) -> String {
-        let (mut r, w) = pipe().unwrap();
+    pub fn run_cmd_capture(&mut self, cmd: Cmd, env: OsEnv, capture_err: bool) -> RuntimeResult<String> {
+        let (mut r, w) = pipe().map_err(io_error)?;
 
         let err_stream = if capture_err {
-            Stream::PipeWriter(w.try_clone().unwrap())
+            Stream::PipeWriter(w.try_clone().map_err(io_error)?)
         } else {
             Stream::Inherit
         };
 
-        let mut cmd = self.build_cmd(cmd, Stream::Null, Stream::PipeWriter(w), err_stream);
-        cmd.set_env(env);
-        cmd.spawn();
-        let rc = cmd.wait();
-        self.set_rc(&rc);
+        self.run_process(cmd, env, Stream::PipeWriter(w), err_stream)?;
 
         let mut out = String::new();
-        r.read_to_string(&mut out).unwrap();
+        r.read_to_string(&mut out).map_err(io_error)?;
 
-        out
+        Ok(out)
     }
 
-    fn build_cmd(&mut self, cmd: Cmd, mut stdin: Stream, mut stdout: Stream, mut stderr: Stream) -> Process {
-        match cmd {
-            Cmd::Atom(segments) => {
-                let mut segments = self.raster_segments(segments);
+    fn run_process(&mut self, cmd: Cmd, env: OsEnv, stdout: Stream, stderr: Stream) -> RuntimeResult<()> {
+        let errexit_span = self.errexit_span(&cmd);
+        let is_guarded = is_guarded(&cmd) || self.condition_depth > 0;
+
+        let mut atoms = Vec::new();
+        let mut line = String::new();
+        let mut process = self.build_cmd(cmd, Stream::Null, stdout, stderr, &mut atoms, &mut line)?;
+
+        if self.trace {
+            eprintln!("+ {}{}", trace::env_prefix(&env), line);
+        }
+        process.set_env(env);
+
+        let failures = SpawnFailures::default();
+        process.spawn(&failures);
+        let (rc, is_exempt) = process.wait();
+        self.set_rc(&rc);
+
+        for failure in failures.lock().unwrap().drain(..) {
+            let err = spawn_error(failure, &atoms);
+
+            // Like in a shell it's enough to tell the user about it, unless the script asked for failures to be errors
+            // with errexit or a `try`. A guarded command is expected to fail either way.
+            if !is_guarded && (errexit_span.is_some() || self.try_depth > 0) {
+                return Err(err);
+            }
+            eprint!("{}", err);
+        }
+
+        check_exit(errexit_span.filter(|_| !is_exempt), &rc)
+    }
+
+    /// Returns the span of `cmd` if it has to raise an error when it fails because of errexit. Commands in conditions
+    /// are expected to fail sometimes and are exempt, like `set -e` does.
+    fn errexit_span(&self, cmd: &Cmd) -> Option<Span> {
+        if !self.errexit || self.condition_depth > 0 {
+            return None;
+        }
+
+        Some(cmd.span())
+    }
+
+    /// Builds the processes of `cmd` without spawning them, and writes the command to `line` as it will run, to trace it.
+    fn build_cmd(&mut self, cmd: Cmd, mut stdin: Stream, mut stdout: Stream, mut stderr: Stream, atoms: &mut Vec<Span>, line: &mut String) -> RuntimeResult<Process> {
+        let process = match cmd {
+            Cmd::Atom(segments, span) => {
+                let mut segments = self.raster_segments(segments)?;
+
+                if segments.is_empty() {
+                    return Err(RuntimeError::at(ErrorKind::Value, "command evaluated to no arguments", &span));
+                }
+
+                line.push_str(&segments.iter().map(|segment| trace::quote(segment)).join(" "));
 
                 let mut cmd = Command::new(segments.remove(0));
                 cmd.args(segments);
@@ -190,20 +273,22 @@ impl Interpreter {
                 cmd.stdout(stdout);
                 cmd.stderr(stderr);
 
-                Process::Std(Either::Left(cmd))
+                atoms.push(span);
+                Process::Std(Either::Left(cmd), atoms.len() - 1)
             }
             Cmd::Op(lhs, op, rhs) if [CmdOp::OutPipe, CmdOp::ErrPipe, CmdOp::AllPipe].contains(&op) => {
-                let (r, w) = pipe().unwrap();
+                let (r, w) = pipe().map_err(io_error)?;
 
                 let (out, err) = match op {
                     CmdOp::OutPipe => (Stream::PipeWriter(w), Stream::Null),
                     CmdOp::ErrPipe => (Stream::Null, Stream::PipeWriter(w)),
-                    CmdOp::AllPipe => (Stream::PipeWriter(w.try_clone().unwrap()), Stream::PipeWriter(w)),
+                    CmdOp::AllPipe => (Stream::PipeWriter(w.try_clone().map_err(io_error)?), Stream::PipeWriter(w)),
                     _ => unreachable!()
                 };
 
-                let lhs = self.build_cmd(*lhs, stdin, out, err);
-                let rhs = self.build_cmd(*rhs, Stream::PipeReader(r), stdout, stderr);
+                let lhs = self.build_cmd(*lhs, stdin, out, err, atoms, line)?;
+                line.push_str(&format!(" {} ", trace::symbol(op)));
+                let rhs = self.build_cmd(*rhs, Stream::PipeReader(r), stdout, stderr, atoms, line)?;
 
                 Process::Pipe {
                     lhs: Box::new(lhs),
@@ -215,8 +300,9 @@ impl Interpreter {
                 let (out_1, out_2) = (stdout.clone(), stdout);
                 let (err_1, err_2) = (stderr.clone(), stderr);
 
-                let lhs = self.build_cmd(*lhs, in_1, out_1, err_1);
-                let rhs = self.build_cmd(*rhs, in_2, out_2, err_2);
+                let lhs = self.build_cmd(*lhs, in_1, out_1, err_1, atoms, line)?;
+                line.push_str(&format!(" {} ", trace::symbol(op)));
+                let rhs = self.build_cmd(*rhs, in_2, out_2, err_2, atoms, line)?;
 
                 Process::Cond {
                     op,
@@ -225,7 +311,7 @@ impl Interpreter {
                 }
             }
             Cmd::Op(lhs, op, rhs) if [CmdOp::OutWrite, CmdOp::ErrWrite, CmdOp::AllWrite, CmdOp::OutAppend, CmdOp::ErrAppend, CmdOp::AllAppend, CmdOp::Read].contains(&op) => {
-                let path = self.cmd_to_path(*rhs);
+                let (path, span) = self.cmd_to_path(*rhs)?;
 
                 let mut file = File::options();
 
@@ -236,33 +322,42 @@ impl Interpreter {
                     _ => unreachable!(),
                 };
 
-                let file = file.open(&path).unwrap();
+                let file = file.open(&path).map_err(|err| RuntimeError::at(
+                    ErrorKind::Io,
+                    format!("couldn't open {}: {}", path, err),
+                    &span,
+                ))?;
 
                 match op {
                     CmdOp::Read => stdin = Stream::File(file),
                     CmdOp::OutWrite | CmdOp::OutAppend => stdout = Stream::File(file),
                     CmdOp::ErrWrite | CmdOp::ErrAppend => stderr = Stream::File(file),
                     CmdOp::AllWrite | CmdOp::AllAppend => {
-                        let file_cloned = file.try_clone().unwrap();
+                        let file_cloned = file.try_clone().map_err(io_error)?;
                         stdout = Stream::File(file);
                         stderr = Stream::File(file_cloned);
                     }
                     _ => unreachable!()
                 }
 
-                self.build_cmd(*lhs, stdin, stdout, stderr)
+                let process = self.build_cmd(*lhs, stdin, stdout, stderr, atoms, line)?;
+                line.push_str(&format!(" {} {}", trace::symbol(op), trace::quote(&path)));
+
+                return Ok(process);
             }
             _ => unreachable!()
-        }
+        };
+
+        Ok(process)
     }
 
-    fn raster_segments(&mut self, segments: Vec<Vec<Expr>>) -> Vec<String> {
+    fn raster_segments(&mut self, segments: Vec<Vec<Expr>>) -> RuntimeResult<Vec<String>> {
         let home_dir = dirs::home_dir().expect("bad home dir").display().to_string();
 
         let mut out = Vec::new();
 
         for segment in segments {
-            let mut vals: Vec<Value> = segment.into_iter().map(|expr| self.eval(expr)).collect();
+            let mut vals = segment.into_iter().map(|expr| self.eval(expr)).collect::<RuntimeResult<Vec<Value>>>()?;
 
             vals.iter_mut().for_each(|val| {
                 if let Value::String(str) = val {
@@ -273,34 +368,81 @@ impl Interpreter {
             out.append(&mut cross_product(vals));
         }
 
-        out
+        Ok(out)
     }
 
-    fn cmd_to_path(&mut self, cmd: Cmd) -> String {
-        let segments = if let Cmd::Atom(segments) = cmd {
-            segments
+    fn cmd_to_path(&mut self, cmd: Cmd) -> RuntimeResult<(String, Span)> {
+        let (segments, span) = if let Cmd::Atom(segments, span) = cmd {
+            (segments, span)
         } else {
-            panic!("expected atom command");
+            unreachable!("the parser only allows simple commands as redirection targets");
         };
 
-        let mut segments = self.raster_segments(segments);
+        let mut segments = self.raster_segments(segments)?;
 
         if segments.len() != 1 {
-            panic!("expected one segment");
+            return Err(RuntimeError::at(
+                ErrorKind::Value,
+                format!("expected redirection target to be one path, got {}", segments.len()),
+                &span,
+            ));
         }
 
-        segments.remove(0)
+        Ok((segments.remove(0), span))
     }
 
     fn set_rc(&mut self, rc: &ExitStatus) {
         self.get_env_mut().def("rc".to_string(), if let Some(rc) = rc.code() {
-            Value::Num(rc as f64)
+            Value::Int(rc as i64)
         } else {
             Value::Nil
         });
     }
 }
 
+fn spawn_error(failure: SpawnFailure, atoms: &[Span]) -> RuntimeError {
+    let message = match failure.error.kind() {
+        io::ErrorKind::NotFound => {
+            let suggestions = path::close_matches(&failure.program);
+            if suggestions.is_empty() {
+                format!("command not found: {}", failure.program)
+            } else {
+                format!("command not found: {}, did you mean {}?", failure.program, suggestions.join(" or "))
+            }
+        }
+        io::ErrorKind::PermissionDenied => format!("permission denied: {}", failure.program),
+        _ => format!("couldn't run {}: {}", failure.program, failure.error),
+    };
+
+    RuntimeError::at(ErrorKind::Spawn, message, &atoms[failure.atom])
+}
+
+fn is_guarded(cmd: &Cmd) -> bool {
+    match cmd {
+        Cmd::Atom(..) => false,
+        Cmd::Op(_, CmdOp::And | CmdOp::Or, _) => true,
+        // A redirection applies to the command on its left
+        Cmd::Op(lhs, CmdOp::OutWrite | CmdOp::ErrWrite | CmdOp::AllWrite | CmdOp::OutAppend | CmdOp::ErrAppend | CmdOp::AllAppend | CmdOp::Read, _) => is_guarded(lhs),
+        // Pipes and sequences exit with the status of their last command
+        Cmd::Op(_, _, rhs) => is_guarded(rhs),
+    }
+}
+
+fn check_exit(errexit_span: Option<Span>, rc: &ExitStatus) -> RuntimeResult<()> {
+    let span = match errexit_span {
+        Some(span) if !rc.success() => span,
+        _ => return Ok(()),
+    };
+
+    let cmd_line = span.source.text.get(span.start..span.end).unwrap_or("");
+    let status = match rc.code() {
+        Some(code) => format!("exited with status {}", code),
+        None => "was killed by a signal".to_string(),
+    };
+
+    Err(RuntimeError::at(ErrorKind::Command, format!("command `{}` {}", cmd_line, status), &span))
+}
+
 fn cross_product(mut vals: Vec<Value>) -> Vec<String> {
     let mut out = vec![String::from("")];
 
@@ -315,7 +457,7 @@ fn cross_product(mut vals: Vec<Value>) -> Vec<String> {
                     let prefix = prefix.to_string();
 
                     for s in &out {
-                        out_tmp.push(format!("{}{}", &prefix, s));
+                        out_tmp.push(format!("{}{}", prefix, s));
                     }
                 }
 
@@ -332,3 +474,7 @@ fn cross_product(mut vals: Vec<Value>) -> Vec<String> {
 
     out
 }
+
+fn io_error(err: io::Error) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Io, err.to_string())
+}
@@ -0,0 +1,84 @@
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+/// Returns the names of the executables found in the directories listed in `PATH`, sorted and without duplicates.
+pub fn executables() -> Vec<String> {
+    match env::var_os("PATH") {
+        Some(path) => executables_in(&path),
+        None => Vec::new(),
+    }
+}
+
+/// Returns the names of the executables found in the directories listed in `path`, formatted like `PATH`.
+pub fn executables_in(path: &OsStr) -> Vec<String> {
+    let mut names = Vec::new();
+
+    for dir in env::split_paths(path) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            // Follows symlinks, many executables are links to others
+            let is_executable = fs::metadata(entry.path())
+                .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false);
+
+            if is_executable {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Returns up to three executables in `PATH` whose name is close to `program`, closest first.
+pub fn close_matches(program: &str) -> Vec<String> {
+    close_matches_in(program, executables())
+}
+
+/// Returns up to three of `executables` whose name is close to `program`, closest first.
+pub fn close_matches_in(program: &str, executables: Vec<String>) -> Vec<String> {
+    // Paths are not looked up in PATH, so nothing there could be what the user meant
+    if program.contains('/') {
+        return Vec::new();
+    }
+
+    let max_distance = (program.chars().count() / 3).max(1);
+
+    let mut matches = executables.into_iter()
+        .map(|name| (edit_distance(program, &name), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<(usize, String)>>();
+
+    matches.sort();
+
+    matches.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// Levenshtein distance between `a` and `b`, counted in chars.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+
+    // Distances between the prefix of `a` seen so far and every prefix of `b`
+    let mut row = (0..=b.len()).collect::<Vec<usize>>();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + if a_char == *b_char { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
@@ -1,9 +1,15 @@
 use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::process;
 use std::rc::Rc;
 
 use crate::interp::value::Value;
 
 use super::cross_product;
+use super::path::{close_matches_in, edit_distance, executables_in};
+use super::trace::{env_prefix, quote};
 
 #[test]
 fn test_cross_product() {
@@ -59,3 +65,49 @@ fn test_cross_product() {
         String::from("x3y3z"),
     ]);
 }
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("docker", "docker"), 0);
+    assert_eq!(edit_distance("dokcer", "docker"), 2);
+    assert_eq!(edit_distance("gti", "git"), 2);
+    assert_eq!(edit_distance("pyton", "python"), 1);
+    assert_eq!(edit_distance("", "ls"), 2);
+}
+
+#[test]
+fn suggests_close_executables() {
+    let dir = env::temp_dir().join(format!("koi-close-executables-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let tool = dir.join("koitool");
+    fs::write(&tool, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(dir.join("koitoo"), "not executable").unwrap();
+
+    let executables = executables_in(dir.as_os_str());
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(executables, vec!["koitool".to_string()]);
+    assert_eq!(close_matches_in("kotool", executables.clone()), vec!["koitool".to_string()]);
+    assert!(close_matches_in("./kotool", executables).is_empty());
+}
+
+#[test]
+fn test_trace_quote() {
+    assert_eq!(quote("src/main.rs"), "src/main.rs");
+    assert_eq!(quote("--color=auto"), "--color=auto");
+    assert_eq!(quote("a b"), "'a b'");
+    assert_eq!(quote("it's"), r"'it'\''s'");
+    assert_eq!(quote(""), "''");
+}
+
+#[test]
+fn test_trace_env_prefix() {
+    let path = std::env::var("PATH").unwrap();
+
+    assert_eq!(env_prefix(&vec![
+        ("PATH".to_string(), path),
+        ("KOI_TRACE_TEST".to_string(), "a b".to_string()),
+    ]), "KOI_TRACE_TEST='a b' ");
+}
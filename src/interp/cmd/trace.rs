@@ -0,0 +1,42 @@
+use std::env;
+
+use crate::ast::CmdOp;
+
+use super::OsEnv;
+
+/// The exported variables whose value differs from the one koi was started with, as `NAME=value `.
+pub fn env_prefix(env: &OsEnv) -> String {
+    env.iter()
+        .filter(|(name, val)| env::var(name).ok().as_ref() != Some(val))
+        .map(|(name, val)| format!("{}={} ", name, quote(val)))
+        .collect()
+}
+
+/// Quotes `arg` like a shell would need it to be, if at all.
+pub fn quote(arg: &str) -> String {
+    let is_plain = !arg.is_empty() && arg.chars().all(|c| c.is_alphanumeric() || "_-./=:,+@%^".contains(c));
+
+    if is_plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+pub fn symbol(op: CmdOp) -> &'static str {
+    match op {
+        CmdOp::And => "&&",
+        CmdOp::Or => "||",
+        CmdOp::Seq => ";",
+        CmdOp::OutPipe => "|",
+        CmdOp::ErrPipe => "*|",
+        CmdOp::AllPipe => "&|",
+        CmdOp::OutWrite => ">",
+        CmdOp::ErrWrite => "*>",
+        CmdOp::AllWrite => "&>",
+        CmdOp::OutAppend => ">>",
+        CmdOp::ErrAppend => "*>>",
+        CmdOp::AllAppend => "&>>",
+        CmdOp::Read => "<",
+    }
+}
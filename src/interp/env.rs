@@ -64,15 +64,25 @@ impl Env {
         }
     }
 
-    pub fn put(&mut self, name: &str, new_val: Value) {
+    pub fn is_defined(&self, name: &str) -> bool {
+        if self.map.contains_key(name) {
+            true
+        } else if let Some(parent) = &self.parent {
+            RefCell::borrow(parent).is_defined(name)
+        } else {
+            false
+        }
+    }
+
+    /// Assigns to an existing variable, returns false if it's not defined in this env or any of its parents.
+    pub fn put(&mut self, name: &str, new_val: Value) -> bool {
         if let Some(val) = self.map.get_mut(name) {
-            val.val = new_val
+            val.val = new_val;
+            true
+        } else if let Some(parent) = &mut self.parent {
+            RefCell::borrow_mut(parent).put(name, new_val)
         } else {
-            if let Some(parent) = &mut self.parent {
-                RefCell::borrow_mut(parent).put(name, new_val)
-            } else {
-                panic!("undefined variable");
-            }
+            false
         }
     }
 
@@ -80,6 +90,19 @@ impl Env {
         self.map.insert(name, var.into());
     }
 
+    /// Names of the variables defined in this env and in its parents.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = if let Some(parent) = &self.parent {
+            RefCell::borrow(parent).names()
+        } else {
+            Vec::new()
+        };
+
+        names.extend(self.map.keys().cloned());
+
+        names
+    }
+
     pub fn os_env(&self) -> OsEnv {
         let mut os_env = if let Some(parent) = &self.parent {
             RefCell::borrow(parent).os_env()
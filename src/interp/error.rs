@@ -0,0 +1,223 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::source::Span;
+
+use super::Value;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ErrorKind {
+    // An operand or argument has the wrong type
+    Type,
+    // A variable that was never declared
+    Name,
+    // A vec index or a range out of bounds
+    Index,
+    // A key missing from a dict
+    Key,
+    // An argument has the right type but an invalid value, e.g. parsing a malformed number
+    Value,
+    // Wrong number of arguments
+    Arity,
+    // Reading or writing files
+    Io,
+    // An imported file doesn't parse
+    Syntax,
+    // `break`, `continue` or `return` used outside of a loop or a function
+    Control,
+    // Raised by a `throw` statement
+    User,
+    // A command failed while errexit is on
+    Command,
+    // A command couldn't be started, e.g. because the program doesn't exist
+    Spawn,
+    // `parseArgs` was given `-h` or `--help`, the message is the help
+    Help,
+    // `parseArgs` was given arguments that don't fit the spec, the message comes with the usage
+    Usage,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            ErrorKind::Type => "type",
+            ErrorKind::Name => "name",
+            ErrorKind::Index => "index",
+            ErrorKind::Key => "key",
+            ErrorKind::Value => "value",
+            ErrorKind::Arity => "arity",
+            ErrorKind::Io => "io",
+            ErrorKind::Syntax => "syntax",
+            ErrorKind::Control => "control",
+            ErrorKind::User => "user",
+            ErrorKind::Command => "command",
+            ErrorKind::Spawn => "spawn",
+            ErrorKind::Help => "help",
+            ErrorKind::Usage => "usage",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameKind {
+    Func(String),
+    Lambda,
+    // Running the code of an imported file, with the path as written in the import statement
+    Import(String),
+}
+
+/// An entry of the Koi call stack kept by the interpreter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub call_site: Span,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            FrameKind::Func(name) => write!(f, "{}()", name),
+            FrameKind::Lambda => write!(f, "<lambda>"),
+            FrameKind::Import(path) => write!(f, "import {:?}", path),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    // Natives don't know where they are called from so they leave this empty, the interpreter fills it as the error
+    // bubbles up through the call expression
+    pub span: Option<Span>,
+    // Call stack at the point the error was raised, outermost call first
+    pub stack: Vec<Frame>,
+    // The dict given to `throw`, handed back as is to `catch` so that rethrowing an error preserves it
+    pub thrown: Option<Box<Value>>,
+}
+
+pub type RuntimeResult<T> = Result<T, RuntimeError>;
+
+impl RuntimeError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> RuntimeError {
+        RuntimeError {
+            kind,
+            message: message.into(),
+            span: None,
+            stack: Vec::new(),
+            thrown: None,
+        }
+    }
+
+    /// Makes the error raised by `throw value`. Strings become the message of the error, dicts are completed with
+    /// the missing `kind` and `location` fields.
+    pub fn thrown(value: Value, span: &Span) -> RuntimeError {
+        let mut dict = match value {
+            Value::String(message) => return RuntimeError::at(ErrorKind::User, message, span),
+            Value::Dict(dict) => RefCell::borrow(&dict).clone(),
+            val => return RuntimeError::at(
+                ErrorKind::Type,
+                format!("can only throw strings and dicts, got {}", val.type_name()),
+                span,
+            ),
+        };
+
+        let message = match dict.get("message") {
+            Some(message) => message.to_string(),
+            None => return RuntimeError::at(ErrorKind::Value, "thrown dict must have a message", span),
+        };
+
+        let mut err = RuntimeError::at(ErrorKind::User, message, span);
+
+        dict.entry("kind".to_string()).or_insert_with(|| Value::String(ErrorKind::User.to_string()));
+        dict.entry("location".to_string()).or_insert_with(|| location_value(span));
+
+        err.thrown = Some(Box::new(Value::Dict(Rc::new(RefCell::new(dict)))));
+        err
+    }
+
+    /// The value a `catch` clause binds the error to.
+    pub fn to_value(&self) -> Value {
+        let stack = Value::Vec(Rc::new(RefCell::new(self.stack.iter().map(|frame| {
+            let mut dict = HashMap::new();
+            dict.insert("name".to_string(), Value::String(frame.to_string()));
+            dict.insert("location".to_string(), location_value(&frame.call_site));
+
+            Value::Dict(Rc::new(RefCell::new(dict)))
+        }).collect())));
+
+        if let Some(thrown) = &self.thrown {
+            if let Value::Dict(dict) = thrown.as_ref() {
+                dict.borrow_mut().entry("stack".to_string()).or_insert(stack);
+            }
+            return *thrown.clone();
+        }
+
+        let mut dict = HashMap::new();
+        dict.insert("message".to_string(), Value::String(self.message.clone()));
+        dict.insert("kind".to_string(), Value::String(self.kind.to_string()));
+        dict.insert("location".to_string(), self.span.as_ref().map(location_value).unwrap_or(Value::Nil));
+        dict.insert("stack".to_string(), stack);
+
+        Value::Dict(Rc::new(RefCell::new(dict)))
+    }
+
+    pub fn at(kind: ErrorKind, message: impl Into<String>, span: &Span) -> RuntimeError {
+        RuntimeError::new(kind, message).or_at(span)
+    }
+
+    /// The status koi exits with when the error ends the script: 0 after printing the help, 2 for invalid arguments
+    /// like other command line tools and 1 for everything else.
+    pub fn exit_status(&self) -> i32 {
+        match self.kind {
+            ErrorKind::Help => 0,
+            ErrorKind::Usage => 2,
+            _ => 1,
+        }
+    }
+
+    /// Sets the location of the error unless it already has one.
+    pub fn or_at(mut self, span: &Span) -> RuntimeError {
+        if self.span.is_none() {
+            self.span = Some(span.clone());
+        }
+        self
+    }
+}
+
+fn location_value(span: &Span) -> Value {
+    let mut dict = HashMap::new();
+    dict.insert("file".to_string(), Value::String(span.source.name.clone()));
+    dict.insert("line".to_string(), Value::Int(span.line as i64));
+    dict.insert("col".to_string(), Value::Int(span.col as i64));
+
+    Value::Dict(Rc::new(RefCell::new(dict)))
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Meant for the user of the script rather than its author, so without the location
+        match self.kind {
+            ErrorKind::Help => return writeln!(f, "{}", self.message),
+            ErrorKind::Usage => return writeln!(f, "error: {}\n\nFor more information try --help", self.message),
+            _ => (),
+        }
+
+        match &self.span {
+            Some(span) => write!(f, "{}", span.render(&self.message))?,
+            None => writeln!(f, "error: {}", self.message)?,
+        }
+
+        if !self.stack.is_empty() {
+            writeln!(f, "traceback (most recent call last):")?;
+            for frame in &self.stack {
+                writeln!(f, "  {} at {}", frame, frame.call_site)?;
+            }
+        }
+
+        Ok(())
+    }
+}
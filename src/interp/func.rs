@@ -3,22 +3,30 @@ use std::fmt::{Debug, Formatter};
 use std::fmt;
 use std::rc::Rc;
 
-use crate::ast::Stmt;
-use crate::interp::{Interpreter, Value};
+use crate::ast::{Pattern, Stmt};
+use crate::interp::{Interpreter, RuntimeResult, Value};
 use crate::interp::env::Env;
+use crate::interp::signature;
+use crate::interp::signature::Param;
 
 #[derive(Clone)]
 pub enum Func {
     User {
         name: Option<String>,
-        params: Vec<String>,
+        params: Vec<Pattern>,
+        // Gets the arguments left after the other parameters, as in `fn run(...cmd)`
+        rest: Option<Box<Pattern>>,
         body: Box<Stmt>,
+        // The comment block right above a `fn` statement
+        doc: Option<Box<str>>,
+        // Functions to run before this one when it's called from the command line
+        deps: Vec<String>,
         captured_env: Option<Rc<RefCell<Env>>>,
     },
     Native {
         name: String,
-        params: Option<usize>,
-        func: fn(&mut Interpreter, Vec<Value>) -> Value,
+        params: &'static [Param],
+        func: fn(&mut Interpreter, Vec<Value>) -> RuntimeResult<Value>,
         receiver: Option<Box<Value>>,
     },
 }
@@ -30,7 +38,7 @@ impl Debug for Func {
                 Some(name) => write!(f, "<func {}>", name),
                 None => write!(f, "<lambda func>"),
             },
-            Func::Native { name, .. } => write!(f, "<native func {}>", name),
+            Func::Native { name, params, .. } => write!(f, "<native func {}>", signature::describe(name, params)),
         }
     }
 }
@@ -1,193 +1,86 @@
 use super::func::Func;
-use super::Interpreter;
+use super::signature::{Param, Type};
+use super::{Interpreter, RuntimeResult};
 use super::native;
 use super::value::Value;
 
+const SUBSTRING: &[Param] = &[Param::required("substring", &[Type::String])];
+const FROM_TO: &[Param] = &[Param::required("from", &[Type::String]), Param::required("to", &[Type::String])];
+const SEP: &[Param] = &[Param::required("sep", &[Type::String])];
+const VEC: &[Param] = &[Param::required("vec", &[Type::Vec])];
+const PATTERN: &[Param] = &[Param::required("pattern", &[Type::String])];
+const FUNC: &[Param] = &[Param::required("f", &[Type::Func])];
+const VAL: &[Param] = &[Param::required("val", &[Type::Any])];
+const KEY: &[Param] = &[Param::required("key", &[Type::Num, Type::String])];
+const INDEX: &[Param] = &[Param::required("index", &[Type::Num])];
+const NUM: &[Param] = &[Param::required("num", &[Type::Num])];
+const STEP: &[Param] = &[Param::required("step", &[Type::Num])];
+
+struct Method {
+    receiver: Type,
+    name: &'static str,
+    params: &'static [Param],
+    func: fn(&mut Interpreter, Vec<Value>) -> RuntimeResult<Value>,
+}
+
+const METHODS: &[Method] = &[
+    Method { receiver: Type::Any, name: "string", params: &[], func: native::string },
+    Method { receiver: Type::Any, name: "type", params: &[], func: native::typ },
+    Method { receiver: Type::Any, name: "bool", params: &[], func: native::bool },
+    Method { receiver: Type::Any, name: "toJson", params: &[], func: native::to_json },
+    Method { receiver: Type::String, name: "parseJson", params: &[], func: native::parse_json },
+    Method { receiver: Type::String, name: "strip", params: &[], func: native::strip },
+    Method { receiver: Type::String, name: "contains", params: SUBSTRING, func: native::string_contains },
+    Method { receiver: Type::String, name: "lower", params: &[], func: native::lower },
+    Method { receiver: Type::String, name: "upper", params: &[], func: native::upper },
+    Method { receiver: Type::String, name: "parseBool", params: &[], func: native::parse_bool },
+    Method { receiver: Type::String, name: "parseNum", params: &[], func: native::parse_num },
+    Method { receiver: Type::String, name: "replace", params: FROM_TO, func: native::replace },
+    Method { receiver: Type::String, name: "split", params: SEP, func: native::split },
+    Method { receiver: Type::String, name: "join", params: VEC, func: native::join },
+    Method { receiver: Type::String, name: "matches", params: PATTERN, func: native::matches },
+    Method { receiver: Type::String, name: "find", params: PATTERN, func: native::find },
+    Method { receiver: Type::String, name: "len", params: &[], func: native::string_len },
+    Method { receiver: Type::Vec, name: "len", params: &[], func: native::vec_len },
+    Method { receiver: Type::Dict, name: "len", params: &[], func: native::dict_len },
+    Method { receiver: Type::Vec, name: "map", params: FUNC, func: native::map },
+    Method { receiver: Type::Vec, name: "filter", params: FUNC, func: native::filter },
+    Method { receiver: Type::Vec, name: "forEach", params: FUNC, func: native::for_each },
+    Method { receiver: Type::Vec, name: "clone", params: &[], func: native::clone_vec },
+    Method { receiver: Type::Dict, name: "clone", params: &[], func: native::clone_dict },
+    Method { receiver: Type::Vec, name: "toDict", params: &[], func: native::vec_2_dict },
+    Method { receiver: Type::Dict, name: "toVec", params: &[], func: native::dict_2_vec },
+    Method { receiver: Type::Vec, name: "contains", params: VAL, func: native::vec_contains },
+    Method { receiver: Type::Dict, name: "contains", params: KEY, func: native::dict_contains },
+    Method { receiver: Type::Vec, name: "remove", params: INDEX, func: native::vec_remove },
+    Method { receiver: Type::Dict, name: "remove", params: KEY, func: native::dict_remove },
+    Method { receiver: Type::Range, name: "len", params: &[], func: native::range_len },
+    Method { receiver: Type::Range, name: "contains", params: NUM, func: native::range_contains },
+    Method { receiver: Type::Range, name: "rev", params: &[], func: native::rev },
+    Method { receiver: Type::Range, name: "step", params: STEP, func: native::step },
+    Method { receiver: Type::Range, name: "toVec", params: &[], func: native::range_2_vec },
+];
+
 impl Interpreter {
-    pub fn build_native_method(&self, base: Value, method_name: String) -> Value {
-        let func = match (base.clone(), &method_name[..]) {
-            (_, "string") => Func::Native {
-                func: native::string,
-                params: Some(1),
-                name: "string".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (_, "type") => Func::Native {
-                func: native::typ,
-                params: Some(1),
-                name: "type".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (_, "bool") => Func::Native {
-                func: native::bool,
-                params: Some(1),
-                name: "bool".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (_, "toJson") => Func::Native {
-                func: native::to_json,
-                params: Some(1),
-                name: "toJson".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "parseJson") => Func::Native {
-                func: native::parse_json,
-                params: Some(1),
-                name: "parseJson".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "strip") => Func::Native {
-                func: native::strip,
-                params: Some(1),
-                name: "strip".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "contains") => Func::Native {
-                func: native::string_contains,
-                params: Some(2),
-                name: "contains".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "lower") => Func::Native {
-                func: native::lower,
-                params: Some(1),
-                name: "lower".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "upper") => Func::Native {
-                func: native::upper,
-                params: Some(1),
-                name: "upper".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "parseBool") => Func::Native {
-                func: native::parse_bool,
-                params: Some(1),
-                name: "parseBool".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "parseNum") => Func::Native {
-                func: native::parse_num,
-                params: Some(1),
-                name: "parseNum".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "replace") => Func::Native {
-                func: native::replace,
-                params: Some(3),
-                name: "replace".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "split") => Func::Native {
-                func: native::split,
-                params: Some(2),
-                name: "split".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "join") => Func::Native {
-                func: native::join,
-                params: Some(2),
-                name: "join".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "matches") => Func::Native {
-                func: native::matches,
-                params: Some(2),
-                name: "matches".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "find") => Func::Native {
-                func: native::find,
-                params: Some(2),
-                name: "find".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::String(_), "len") => Func::Native {
-                func: native::string_len,
-                params: Some(1),
-                name: "len".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Vec(_), "len") => Func::Native {
-                func: native::vec_len,
-                params: Some(1),
-                name: "len".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Dict(_), "len") => Func::Native {
-                func: native::dict_len,
-                params: Some(1),
-                name: "len".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Vec(_), "map") => Func::Native {
-                func: native::map,
-                params: Some(2),
-                name: "map".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Vec(_), "filter") => Func::Native {
-                func: native::filter,
-                params: Some(2),
-                name: "filter".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Vec(_), "forEach") => Func::Native {
-                func: native::for_each,
-                params: Some(2),
-                name: "forEach".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Vec(_), "clone") => Func::Native {
-                func: native::clone_vec,
-                params: Some(1),
-                name: "clone".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Dict(_), "clone") => Func::Native {
-                func: native::clone_dict,
-                params: Some(1),
-                name: "clone".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Vec(_), "toDict") => Func::Native {
-                func: native::vec_2_dict,
-                params: Some(1),
-                name: "toDict".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Dict(_), "toVec") => Func::Native {
-                func: native::dict_2_vec,
-                params: Some(1),
-                name: "toVec".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Vec(_), "contains") => Func::Native {
-                func: native::vec_contains,
-                params: Some(2),
-                name: "contains".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Dict(_), "contains") => Func::Native {
-                func: native::dict_contains,
-                params: Some(2),
-                name: "contains".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Vec(_), "remove") => Func::Native {
-                func: native::vec_remove,
-                params: Some(2),
-                name: "remove".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            (Value::Dict(_), "remove") => Func::Native {
-                func: native::dict_remove,
-                params: Some(2),
-                name: "remove".to_string(),
-                receiver: Some(Box::new(base)),
-            },
-            _ => panic!("no method found with this name"),
-        };
-        Value::Func(func)
+    pub fn build_native_method(&self, base: Value, method_name: &str) -> Option<Value> {
+        let method = METHODS.iter().find(|method| method.name == method_name && method.receiver.accepts(&base))?;
+
+        Some(Value::Func(Func::Native {
+            name: method.name.to_string(),
+            params: method.params,
+            func: method.func,
+            receiver: Some(Box::new(base)),
+        }))
     }
 }
+
+/// Names of the native methods that can be called on `val`, sorted.
+pub fn method_names(val: &Value) -> Vec<&'static str> {
+    let mut names = METHODS.iter()
+        .filter(|method| method.receiver.accepts(val))
+        .map(|method| method.name)
+        .collect::<Vec<&'static str>>();
+
+    names.sort_unstable();
+    names
+}
@@ -4,37 +4,88 @@ use std::env as std_env;
 use std::fmt::Debug;
 use std::mem;
 use std::rc::Rc;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use itertools::Itertools;
+
+pub use cmd::executables;
+pub use error::{ErrorKind, Frame, FrameKind, RuntimeError, RuntimeResult};
 pub use func::Func;
+pub use method::method_names;
+pub use signature::{arity_error, Param, Type};
+pub use range::Range;
 pub use value::Value;
 
-use crate::ast::{BinaryOp, Expr, Prog, Stmt, UnaryOp};
+use crate::ast::{BinaryOp, Expr, Pattern, Prog, Stmt, UnaryOp};
 use crate::interp::env::{Env, Var};
 use crate::lexer::new as new_lexer;
 use crate::parser;
+use crate::source::{Source, Span};
 
+mod args;
 mod cmd;
 mod env;
+mod error;
 mod value;
 mod func;
 mod native;
 mod method;
+mod signature;
+mod pattern;
+mod num;
+mod range;
 
 #[cfg(test)]
 mod test;
 
+const PRINT_PARAMS: &[Param] = &[Param::variadic("values", &[Type::Any])];
+const INPUT_PARAMS: &[Param] = &[Param::optional("prompt", &[Type::String])];
+const EXIT_PARAMS: &[Param] = &[Param::required("code", &[Type::Num])];
+const GLOB_PARAMS: &[Param] = &[Param::required("pattern", &[Type::String])];
+const ERREXIT_PARAMS: &[Param] = &[Param::required("on", &[Type::Bool])];
+const TRACE_PARAMS: &[Param] = &[Param::required("on", &[Type::Bool])];
+const PARSEARGS_PARAMS: &[Param] = &[Param::required("spec", &[Type::Dict]), Param::optional("args", &[Type::Vec])];
+
 pub struct Interpreter {
     env: Rc<RefCell<Env>>,
     collector: Option<String>,
     import_root: PathBuf,
+    // Calls to user functions and imports currently running, outermost first
+    frames: Vec<Frame>,
+    // Whether a failing command raises an error, like `set -e`
+    errexit: bool,
+    // Whether commands are printed before they run, like `set -x`
+    trace: bool,
+    // Number of conditions being evaluated, commands failing inside of them don't count for errexit
+    condition_depth: usize,
+    // Number of `try` statements with a `catch` running, commands that can't start inside of them raise an error
+    try_depth: usize,
 }
 
 #[derive(Debug)]
 enum Escape {
-    Break,
-    Continue,
-    Return(Value),
+    Break(Span),
+    Continue(Span),
+    Return(Box<Value>, Span),
+    Throw(RuntimeError),
+}
+
+impl From<RuntimeError> for Escape {
+    fn from(err: RuntimeError) -> Self {
+        Escape::Throw(err)
+    }
+}
+
+impl Escape {
+    /// Turns an escape that bubbled up to the top level of a program or function into an error.
+    fn into_error(self) -> RuntimeError {
+        match self {
+            Escape::Break(span) => RuntimeError::at(ErrorKind::Control, "break outside loop", &span),
+            Escape::Continue(span) => RuntimeError::at(ErrorKind::Control, "continue outside loop", &span),
+            Escape::Return(_, span) => RuntimeError::at(ErrorKind::Control, "return outside function", &span),
+            Escape::Throw(err) => err,
+        }
+    }
 }
 
 impl Interpreter {
@@ -43,6 +94,11 @@ impl Interpreter {
             env: Rc::new(RefCell::new(Env::new())),
             collector: None,
             import_root: std::env::current_dir().expect("couldn't get working dir"),
+            frames: Vec::new(),
+            errexit: false,
+            trace: false,
+            condition_depth: 0,
+            try_depth: 0,
         };
         interpreter.init_native_funcs();
         interpreter.import_os_env();
@@ -50,9 +106,26 @@ impl Interpreter {
         interpreter
     }
 
-    pub fn run(&mut self, prog: Prog) {
-        for stmt in prog.into_iter() {
-            self.run_stmt(stmt).expect("escape bubbled up to top level");
+    pub fn run(&mut self, prog: Prog) -> RuntimeResult<()> {
+        for stmt in prog {
+            self.run_stmt(stmt).map_err(Escape::into_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Like `run` but returns the value of the last statement if it's an expression, `nil` otherwise.
+    pub fn run_to_value(&mut self, mut prog: Prog) -> RuntimeResult<Value> {
+        let last = match prog.last() {
+            Some(Stmt::Expr(_)) => prog.pop(),
+            _ => None,
+        };
+
+        self.run(prog)?;
+
+        match last {
+            Some(Stmt::Expr(expr)) => self.eval(expr),
+            _ => Ok(Value::Nil),
         }
     }
 
@@ -65,37 +138,87 @@ impl Interpreter {
 
         self.get_env_mut().def("print".to_string(), Value::Func(Func::Native {
             name: "print".to_string(),
-            params: None,
+            params: PRINT_PARAMS,
             func: print,
             receiver: None,
         }));
 
         self.get_env_mut().def("input".to_string(), Value::Func(Func::Native {
             name: "input".to_string(),
-            params: None,
+            params: INPUT_PARAMS,
             func: input,
             receiver: None,
         }));
 
         self.get_env_mut().def("exit".to_string(), Value::Func(Func::Native {
             name: "exit".to_string(),
-            params: Some(1),
+            params: EXIT_PARAMS,
             func: exit,
             receiver: None,
         }));
 
         self.get_env_mut().def("glob".to_string(), Value::Func(Func::Native {
             name: "glob".to_string(),
-            params: Some(1),
+            params: GLOB_PARAMS,
             func: glob,
             receiver: None,
         }));
+
+        self.get_env_mut().def("errexit".to_string(), Value::Func(Func::Native {
+            name: "errexit".to_string(),
+            params: ERREXIT_PARAMS,
+            func: errexit,
+            receiver: None,
+        }));
+
+        self.get_env_mut().def("trace".to_string(), Value::Func(Func::Native {
+            name: "trace".to_string(),
+            params: TRACE_PARAMS,
+            func: trace,
+            receiver: None,
+        }));
+
+        self.get_env_mut().def("parseArgs".to_string(), Value::Func(Func::Native {
+            name: "parseArgs".to_string(),
+            params: PARSEARGS_PARAMS,
+            func: parse_args,
+            receiver: None,
+        }));
     }
 
     pub fn set_import_root(&mut self, import_root: PathBuf) {
         self.import_root = import_root;
     }
 
+    pub fn set_errexit(&mut self, errexit: bool) {
+        self.errexit = errexit;
+    }
+
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    pub fn import_root(&self) -> &Path {
+        &self.import_root
+    }
+
+    /// Value of the variable `name`, `nil` if it's not defined.
+    pub fn get(&self, name: &str) -> Value {
+        self.get_env().get(name)
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.get_env().is_defined(name)
+    }
+
+    /// Names of the natives, OS environment variables and anything else defined before the script runs.
+    pub fn global_names(&self) -> Vec<String> {
+        // `rc` is only defined after the first command runs but it's always fine to refer to it
+        let mut names = self.get_env().names();
+        names.push("rc".to_string());
+        names
+    }
+
     fn import_os_env(&mut self) {
         for (k, v) in std_env::vars() {
             RefCell::borrow_mut(&self.env).def(k, Value::String(v));
@@ -111,11 +234,11 @@ impl Interpreter {
         self.env = parent_env;
     }
 
-    fn get_env(&self) -> Ref<Env> {
+    fn get_env(&self) -> Ref<'_, Env> {
         RefCell::borrow(&self.env)
     }
 
-    fn get_env_mut(&mut self) -> RefMut<Env> {
+    fn get_env_mut(&mut self) -> RefMut<'_, Env> {
         RefCell::borrow_mut(&self.env)
     }
 
@@ -125,41 +248,59 @@ impl Interpreter {
                 let env = self.get_env().os_env();
 
                 if self.collector.is_some() {
-                    let output = self.run_cmd_capture(cmd, env, true);
+                    let output = self.run_cmd_capture(cmd, env, true)?;
                     self.collector.as_mut().unwrap().push_str(&output);
                 } else {
-                    self.run_cmd_pipe(cmd, env);
+                    self.run_cmd_pipe(cmd, env)?;
                 }
             }
-            Stmt::Let { name, init, is_exp } => {
+            Stmt::Let { pattern, init, is_exp, span } => {
                 let val = match init {
-                    Some(expr) => self.eval(expr),
+                    Some(expr) => self.eval(expr)?,
                     _ => Value::Nil,
                 };
 
+                let bindings = self.destructure(&pattern, val).map_err(|err| err.or_at(&span))?;
+
                 self.push_env();
-                self.get_env_mut().def(name, Var::new(val, is_exp));
+                for (name, val) in bindings {
+                    self.get_env_mut().def(name, Var::new(val, is_exp));
+                }
             }
-            Stmt::Import(mut base_path) => {
-                if !base_path.ends_with(".koi") {
-                    base_path.push_str(".koi");
+            Stmt::Import(base_path, span) => {
+                let mut path = import_path(&self.import_root, &base_path);
+
+                let text = std::fs::read_to_string(&path).map_err(|err| RuntimeError::at(
+                    ErrorKind::Io,
+                    format!("couldn't read imported file {}: {}", path.display(), err),
+                    &span,
+                ))?;
+                let source = Source::new(path.display().to_string(), text);
+
+                let (prog, errors) = parser::Parser::new(new_lexer(source)).parse();
+                if !errors.is_empty() {
+                    return Err(RuntimeError::at(
+                        ErrorKind::Syntax,
+                        format!("imported file doesn't parse\n{}", errors.iter().join("\n")),
+                        &span,
+                    ).into());
                 }
 
-                let mut path = self.import_root.clone();
-                path.push(&base_path);
-
-                let source = std::fs::read_to_string(&path)
-                    .expect("reading imported file's content");
-                let prog = parser::Parser::new(new_lexer(source)).parse();
-
                 path.pop();
 
+                let frame = Frame {
+                    kind: FrameKind::Import(base_path),
+                    call_site: span,
+                };
+
                 let old_import_root = std::mem::replace(&mut self.import_root, path);
-                self.run(prog);
+                let res = self.with_frame(Some(frame), |int| int.run(prog));
                 self.import_root = old_import_root;
+
+                res?
             }
             Stmt::Expr(expr) => {
-                self.eval(expr);
+                self.eval(expr)?;
             }
             Stmt::Block(stmts) => {
                 let original_env = Rc::clone(&self.env);
@@ -180,101 +321,105 @@ impl Interpreter {
 
                 res?
             }
-            Stmt::For { lvar, rvar, iterated, each_do } => {
-                let iterated = self.eval(iterated);
-
-                match iterated {
-                    Value::Range(l, r) => {
-                        assert!(rvar.is_none(), "for loop with range does not need a second variable");
-
-                        self.push_env();
-                        self.get_env_mut().def(lvar.clone(), Value::Num(l as f64));
-
-                        for i in l..r {
-                            self.get_env_mut().put(&lvar, Value::Num(i as f64));
-
-                            let res = self.run_stmt(*each_do.clone());
-                            match &res {
-                                Err(Escape::Continue) => continue,
-                                Err(Escape::Break) => break,
-                                _ => res?,
-                            };
+            Stmt::For { lvar, rvar, iterated, each_do, span } => {
+                let items: Box<dyn Iterator<Item=(Value, Value)>> = match self.eval(iterated)? {
+                    Value::Range(range) => {
+                        if rvar.is_some() {
+                            return Err(RuntimeError::at(ErrorKind::Type, "for loop with range does not need a second variable", &span).into());
                         }
 
-                        self.pop_env();
+                        Box::new(range.iter().map(|i| (Value::Int(i), Value::Nil)))
                     }
+                    // Iterate over a snapshot so that the body is free to modify the collection
                     Value::Vec(vec) => {
-                        let rvar = rvar.expect("for loop with vec does need a second variable");
-
-                        self.push_env();
-                        self.get_env_mut().def(lvar.clone(), Value::Nil);
-                        self.get_env_mut().def(rvar.clone(), Value::Nil);
-
-                        for (i, v) in RefCell::borrow(&vec).iter().enumerate() {
-                            self.get_env_mut().put(&lvar, Value::Num(i as f64));
-                            self.get_env_mut().put(&rvar, v.clone());
-
-                            let res = self.run_stmt(*each_do.clone());
-                            match &res {
-                                Err(Escape::Continue) => continue,
-                                Err(Escape::Break) => break,
-                                _ => res?,
-                            };
+                        if rvar.is_none() {
+                            return Err(RuntimeError::at(ErrorKind::Type, "for loop with vec does need a second variable", &span).into());
                         }
 
-                        self.pop_env();
+                        let vec = RefCell::borrow(&vec).clone();
+                        Box::new(vec.into_iter().enumerate().map(|(i, v)| (Value::Int(i as i64), v)))
                     }
                     Value::Dict(dict) => {
-                        let rvar = rvar.expect("for loop with vec does need a second variable");
+                        if rvar.is_none() {
+                            return Err(RuntimeError::at(ErrorKind::Type, "for loop with dict does need a second variable", &span).into());
+                        }
 
-                        self.push_env();
-                        self.get_env_mut().def(lvar.clone(), Value::Nil);
-                        self.get_env_mut().def(rvar.clone(), Value::Nil);
-
-                        for (k, v) in RefCell::borrow(&dict).iter() {
-                            self.get_env_mut().put(&lvar, Value::String(k.clone()));
-                            self.get_env_mut().put(&rvar, v.clone());
-
-                            let res = self.run_stmt(*each_do.clone());
-                            match &res {
-                                Err(Escape::Continue) => continue,
-                                Err(Escape::Break) => break,
-                                _ => res?,
-                            };
+                        let dict = RefCell::borrow(&dict).clone();
+                        Box::new(dict.into_iter().map(|(k, v)| (Value::String(k), v)))
+                    }
+                    val => return Err(RuntimeError::at(
+                        ErrorKind::Type,
+                        format!("can't iterate over {}", val.type_name()),
+                        &span,
+                    ).into()),
+                };
+
+                self.push_env();
+
+                let mut res = Ok(());
+                for (l, r) in items {
+                    let mut bindings = match self.destructure(&lvar, l) {
+                        Ok(bindings) => bindings,
+                        Err(err) => {
+                            res = Err(err.or_at(&span).into());
+                            break;
+                        }
+                    };
+                    if let Some(rvar) = &rvar {
+                        match self.destructure(rvar, r) {
+                            Ok(mut rvar_bindings) => bindings.append(&mut rvar_bindings),
+                            Err(err) => {
+                                res = Err(err.or_at(&span).into());
+                                break;
+                            }
                         }
+                    }
 
-                        self.pop_env();
+                    for (name, val) in bindings {
+                        self.get_env_mut().def(name, val);
+                    }
+
+                    match self.run_loop_body(&each_do) {
+                        Ok(true) => continue,
+                        Ok(false) => break,
+                        Err(escape) => {
+                            res = Err(escape);
+                            break;
+                        }
                     }
-                    _ => unreachable!()
                 }
+
+                self.pop_env();
+
+                res?
             }
             Stmt::While { cond, then_do } => {
-                while self.eval(cond.clone()).is_truthy() {
-                    let res = self.run_stmt(*then_do.clone());
-                    match &res {
-                        Err(Escape::Continue) => continue,
-                        Err(Escape::Break) => break,
-                        _ => res?,
-                    };
+                while self.eval_condition(cond.clone())?.is_truthy() {
+                    if !self.run_loop_body(&then_do)? {
+                        break;
+                    }
                 }
             }
             Stmt::If { cond, then_do, else_do } => {
-                if self.eval(cond).is_truthy() {
+                if self.eval_condition(cond)?.is_truthy() {
                     self.run_stmt(*then_do)?;
-                } else if else_do.is_some() {
-                    self.run_stmt(*else_do.unwrap())?;
+                } else if let Some(else_do) = else_do {
+                    self.run_stmt(*else_do)?;
                 }
             }
-            Stmt::Continue => return Err(Escape::Continue),
-            Stmt::Break => return Err(Escape::Break),
+            Stmt::Continue(span) => return Err(Escape::Continue(span)),
+            Stmt::Break(span) => return Err(Escape::Break(span)),
             Stmt::Func(func) => {
                 match func {
                     // Lambdas don't get parsed as Stmt::Func but Expr::Lambda, therefore a name should always be present
-                    Func::User { name, params, body, .. } => {
+                    Func::User { name, params, rest, body, doc, deps, .. } => {
                         let func = Value::Func(Func::User {
                             name: name.clone(),
                             params,
+                            rest,
                             body,
+                            doc,
+                            deps,
                             captured_env: Some(Rc::clone(&self.env)),
                         });
                         self.get_env_mut().def(name.unwrap(), func);
@@ -282,58 +427,137 @@ impl Interpreter {
                     Func::Native { .. } => unreachable!(),
                 }
             }
-            Stmt::Return(expr) => {
+            Stmt::Try { try_do, err_var, catch_do, finally_do } => {
+                let try_depth = if catch_do.is_some() { 1 } else { 0 };
+                self.try_depth += try_depth;
+                let res = self.run_stmt(*try_do);
+                self.try_depth -= try_depth;
+
+                let res = match (res, catch_do) {
+                    (Err(Escape::Throw(err)), Some(catch_do)) => {
+                        let err = self.capture_stack(err);
+
+                        self.push_env();
+                        if let Some(err_var) = err_var {
+                            self.get_env_mut().def(err_var, err.to_value());
+                        }
+                        let res = self.run_stmt(*catch_do);
+                        self.pop_env();
+                        res
+                    }
+                    (res, _) => res,
+                };
+
+                // An escape from `finally_do` takes the place of the one from the rest of the statement
+                if let Some(finally_do) = finally_do {
+                    self.run_stmt(*finally_do)?;
+                }
+
+                res?
+            }
+            Stmt::Match { subject, arms } => {
+                let val = self.eval(subject)?;
+
+                for arm in arms {
+                    let mut bindings = Vec::new();
+                    if !self.match_pattern(&arm.pattern, &val, &mut bindings)? {
+                        continue;
+                    }
+
+                    let original_env = Rc::clone(&self.env);
+
+                    self.push_env();
+                    for (name, val) in bindings {
+                        self.get_env_mut().def(name, val);
+                    }
+
+                    let res = self.run_stmt(arm.body);
+
+                    while !Rc::ptr_eq(&self.env, &original_env) {
+                        self.pop_env();
+                    }
+
+                    res?;
+                    break;
+                }
+            }
+            Stmt::Throw(expr, span) => {
+                let val = self.eval(expr)?;
+                return Err(RuntimeError::thrown(val, &span).into());
+            }
+            Stmt::Return(expr, span) => {
                 let ret_val = if let Some(expr) = expr {
-                    self.eval(expr)
+                    self.eval(expr)?
                 } else {
                     Value::Nil
                 };
 
-                return Err(Escape::Return(ret_val));
+                return Err(Escape::Return(Box::new(ret_val), span));
             }
         };
         Ok(())
     }
 
-    fn eval(&mut self, expr: Expr) -> Value {
-        match expr {
+    /// Runs one iteration of a loop, returns whether the loop should keep going.
+    fn run_loop_body(&mut self, body: &Stmt) -> Result<bool, Escape> {
+        match self.run_stmt(body.clone()) {
+            Ok(()) | Err(Escape::Continue(_)) => Ok(true),
+            Err(Escape::Break(_)) => Ok(false),
+            Err(escape) => Err(escape),
+        }
+    }
+
+    fn eval(&mut self, expr: Expr) -> RuntimeResult<Value> {
+        let val = match expr {
             Expr::Literal(value) => value,
             Expr::Vec(vec) => {
-                let vec = vec.into_iter().map(|expr| self.eval(expr)).collect::<Vec<Value>>();
+                let vec = vec.into_iter().map(|expr| self.eval(expr)).collect::<RuntimeResult<Vec<Value>>>()?;
                 let vec = Rc::new(RefCell::new(vec));
                 Value::Vec(vec)
             }
             Expr::Dict(dict) => {
-                let dict = dict.into_iter().map(|(key, expr)| (key, self.eval(expr))).collect::<HashMap<String, Value>>();
+                let dict = dict.into_iter()
+                    .map(|(key, expr)| Ok((key, self.eval(expr)?)))
+                    .collect::<RuntimeResult<HashMap<String, Value>>>()?;
                 let dict = Rc::new(RefCell::new(dict));
                 Value::Dict(dict)
             }
             Expr::Cmd(cmd) => {
                 let os_env = self.get_env().os_env();
-                Value::String(self.run_cmd_capture(cmd, os_env, false))
+                Value::String(self.run_cmd_capture(cmd, os_env, false)?)
             }
-            Expr::Get(name) => RefCell::borrow(&self.env).get(&name).clone(),
-            Expr::GetField { base, index } => {
-                let base = self.eval(*base);
-                let index = self.eval(*index);
+            Expr::Get(name, _) => RefCell::borrow(&self.env).get(&name),
+            Expr::GetField { base, index, span } => {
+                let base = self.eval(*base)?;
+                let index = self.eval(*index)?;
 
                 let val = match (base.clone(), index.clone()) {
-                    (Value::Vec(vec), Value::Range(from, to)) => {
+                    (Value::Vec(vec), Value::Range(range)) => {
                         let vec = RefCell::borrow(&vec);
-                        let vec = &vec[from..to];
-                        Some(Value::Vec(Rc::new(RefCell::new(vec.to_vec()))))
+                        let indices = range.indices(vec.len()).ok_or_else(|| RuntimeError::at(
+                            ErrorKind::Index,
+                            format!("range {} out of bounds for vec of len {}", range, vec.len()),
+                            &span,
+                        ))?;
+                        Some(Value::Vec(Rc::new(RefCell::new(indices.into_iter().map(|i| vec[i].clone()).collect()))))
                     }
-                    (Value::Vec(vec), Value::Num(index)) => {
-                        let index = if index.trunc() == index {
-                            index as usize
-                        } else {
-                            panic!("expected integer index")
-                        };
-
-                        RefCell::borrow(&vec).get(index).cloned()
+                    (Value::String(string), Value::Range(range)) => {
+                        let chars = string.chars().collect::<Vec<char>>();
+                        let indices = range.indices(chars.len()).ok_or_else(|| RuntimeError::at(
+                            ErrorKind::Index,
+                            format!("range {} out of bounds for string of len {}", range, chars.len()),
+                            &span,
+                        ))?;
+                        Some(Value::String(indices.into_iter().map(|i| chars[i]).collect()))
                     }
-                    (Value::Dict(dict), index @ Value::String(_) | index @ Value::Num(_)) => {
-                        let index = dict_key(index);
+                    (Value::Vec(vec), index @ (Value::Int(_) | Value::Num(_))) => {
+                        let index = index.as_int().ok_or_else(|| RuntimeError::at(ErrorKind::Type, "expected integer index", &span))?;
+                        let vec = RefCell::borrow(&vec);
+
+                        range::resolve_index(index, vec.len()).map(|index| vec[index].clone())
+                    }
+                    (Value::Dict(dict), index @ (Value::String(_) | Value::Int(_) | Value::Num(_))) => {
+                        let index = dict_key(index).map_err(|err| err.or_at(&span))?;
 
                         RefCell::borrow(&dict).get(&index).cloned()
                     }
@@ -346,38 +570,56 @@ impl Interpreter {
                     let method_name = if let Value::String(method_name) = index {
                         method_name
                     } else {
-                        panic!("expected string index");
+                        return Err(RuntimeError::at(ErrorKind::Type, "expected string index", &span));
                     };
 
-                    self.build_native_method(base, method_name)
+                    let type_name = base.type_name();
+
+                    self.build_native_method(base, &method_name).ok_or_else(|| RuntimeError::at(
+                        ErrorKind::Name,
+                        format!("no method {} for {}", method_name, type_name),
+                        &span,
+                    ))?
                 }
             }
-            Expr::Set(name, expr) => {
-                let value = self.eval(*expr);
-                self.get_env_mut().put(&name, value.clone());
+            Expr::Set(name, expr, span) => {
+                let value = self.eval(*expr)?;
+                if !self.get_env_mut().put(&name, value.clone()) {
+                    return Err(RuntimeError::at(ErrorKind::Name, format!("undefined variable {}", name), &span));
+                }
                 value
             }
-            Expr::SetField { base, index, expr } => {
-                let base = self.eval(*base);
-                let index = self.eval(*index);
-                let value = self.eval(*expr);
+            Expr::SetField { base, index, expr, span } => {
+                let base = self.eval(*base)?;
+                let index = self.eval(*index)?;
+                let value = self.eval(*expr)?;
 
                 match (base, index) {
-                    (Value::Vec(vec), Value::Num(index)) => {
-                        let index = if index.trunc() == index {
-                            index as usize
-                        } else {
-                            panic!("expected integer index")
-                        };
-
-                        vec.borrow_mut()[index] = value.clone();
+                    (Value::Vec(vec), index @ (Value::Int(_) | Value::Num(_))) => {
+                        let index = index.as_int().ok_or_else(|| RuntimeError::at(ErrorKind::Type, "expected integer index", &span))?;
+
+                        let mut vec = vec.borrow_mut();
+                        let len = vec.len();
+
+                        match range::resolve_index(index, len).map(|index| &mut vec[index]) {
+                            Some(elem) => *elem = value.clone(),
+                            None => return Err(RuntimeError::at(
+                                ErrorKind::Index,
+                                format!("index {} out of bounds for vec of len {}", index, len),
+                                &span,
+                            )),
+                        }
                     }
-                    (Value::Dict(dict), index @ Value::String(_) | index @ Value::Num(_)) => {
-                        let index = dict_key(index);
+                    (Value::Dict(dict), index @ (Value::String(_) | Value::Int(_) | Value::Num(_))) => {
+                        let index = dict_key(index).map_err(|err| err.or_at(&span))?;
 
                         dict.borrow_mut().insert(index, value.clone());
                     }
-                    _ => panic!("bad assignment target"),
+                    (base, _) => return Err(RuntimeError::at(
+                        ErrorKind::Type,
+                        format!("can't assign to a field of {}", base.type_name()),
+                        &span,
+                    )),
                 };
 
                 value
@@ -388,28 +630,25 @@ impl Interpreter {
                 out += &strings.remove(0);
 
                 for expr in exprs {
-                    let str = self.eval(expr).to_string();
+                    let str = self.eval(expr)?.to_string();
                     out += &str;
                     out += &strings.remove(0);
                 }
 
                 Value::String(out)
             }
-            Expr::Range { l, r, inclusive } => {
-                let l = self.eval(*l);
-                let r = self.eval(*r);
-
-                match (l, r) {
-                    // The x.trunc() == x part is to check that the numbers are integers
-                    (Value::Num(l), Value::Num(r)) if l.trunc() == l && r.trunc() == r => {
-                        Value::Range(l as usize, r as usize + if inclusive { 1 } else { 0 })
-                    }
-                    _ => panic!("range must evaluate to integers")
+            Expr::Range { l, r, inclusive, step, span } => {
+                let l = self.eval(*l)?;
+                let r = self.eval(*r)?;
+                let step = step.map(|step| self.eval(*step)).transpose()?.unwrap_or(Value::Int(1));
+
+                match (l.as_int(), r.as_int(), step.as_int()) {
+                    (Some(l), Some(r), Some(step)) => Value::Range(Range::new(l, r, step, inclusive).map_err(|err| err.or_at(&span))?),
+                    _ => return Err(RuntimeError::at(ErrorKind::Type, "range bounds and step must be integers", &span)),
                 }
             }
-            Expr::Binary(lhs, BinaryOp::Sum, rhs) => {
-                match (self.eval(*lhs), self.eval(*rhs)) {
-                    (Value::Num(lhs), Value::Num(rhs)) => Value::Num(lhs + rhs),
+            Expr::Binary(lhs, BinaryOp::Sum, rhs, span) => {
+                match (self.eval(*lhs)?, self.eval(*rhs)?) {
                     (Value::String(lhs), Value::String(rhs)) => Value::String(lhs + &rhs),
                     (Value::Vec(lhs), Value::Vec(rhs)) => {
                         let lhs = RefCell::borrow(&lhs);
@@ -435,84 +674,110 @@ impl Interpreter {
 
                         Value::Dict(Rc::new(RefCell::new(res)))
                     }
-                    _ => panic!("invalid operands types for op {:?}", BinaryOp::Sum),
+                    (lhs, rhs) => match num::apply(BinaryOp::Sum, &lhs, &rhs) {
+                        Some(res) => res.map_err(|err| err.or_at(&span))?,
+                        None => return Err(operands_error(BinaryOp::Sum, &lhs, &rhs, &span)),
+                    },
                 }
             }
-            Expr::Binary(lhs, op, rhs) if [
-                BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Div,
-                BinaryOp::Mod, BinaryOp::Pow, BinaryOp::Less, BinaryOp::Great
-            ].contains(&op) => {
-                let (lhs, rhs) = match (self.eval(*lhs), self.eval(*rhs)) {
-                    (Value::Num(lhs), Value::Num(rhs)) => (lhs, rhs),
-                    _ => panic!("invalid operands types for op {:?}", op),
-                };
+            Expr::Binary(lhs, op, rhs, span) if ![BinaryOp::Equal, BinaryOp::And, BinaryOp::Or].contains(&op) => {
+                let (lhs, rhs) = (self.eval(*lhs)?, self.eval(*rhs)?);
 
-                match op {
-                    BinaryOp::Sub => Value::Num(lhs - rhs),
-                    BinaryOp::Mul => Value::Num(lhs * rhs),
-                    BinaryOp::Div => Value::Num(lhs / rhs),
-                    BinaryOp::Mod => Value::Num(lhs % rhs),
-                    BinaryOp::Pow => Value::Num(lhs.powf(rhs)),
-                    BinaryOp::Less => Value::Bool(lhs < rhs),
-                    BinaryOp::Great => Value::Bool(lhs > rhs),
-                    _ => unreachable!(),
+                match num::apply(op, &lhs, &rhs) {
+                    Some(res) => res.map_err(|err| err.or_at(&span))?,
+                    None => return Err(operands_error(op, &lhs, &rhs, &span)),
                 }
             }
-            Expr::Binary(lhs, BinaryOp::And, rhs) => {
-                let lhs = self.eval(*lhs);
+            Expr::Binary(lhs, BinaryOp::And, rhs, _) => {
+                let lhs = self.eval_condition(*lhs)?;
                 if lhs.is_truthy() {
-                    self.eval(*rhs)
+                    self.eval(*rhs)?
                 } else {
                     lhs
                 }
             }
-            Expr::Binary(lhs, BinaryOp::Or, rhs) => {
-                let lhs = self.eval(*lhs);
+            Expr::Binary(lhs, BinaryOp::Or, rhs, _) => {
+                let lhs = self.eval_condition(*lhs)?;
                 if lhs.is_truthy() {
                     lhs
                 } else {
-                    self.eval(*rhs)
+                    self.eval(*rhs)?
                 }
             }
-            Expr::Binary(lhs, BinaryOp::Equal, rhs) => Value::Bool(self.eval(*lhs) == self.eval(*rhs)),
-            Expr::Unary(UnaryOp::Not, expr) => Value::Bool(!self.eval(*expr).is_truthy()),
-            Expr::Unary(UnaryOp::Neg, expr) => {
-                let num = if let Value::Num(num) = self.eval(*expr) {
-                    num
-                } else {
-                    panic!("invalid operand type for op {:?}", UnaryOp::Neg);
-                };
-
-                Value::Num(-num)
+            Expr::Binary(lhs, BinaryOp::Equal, rhs, _) => Value::Bool(self.eval(*lhs)? == self.eval(*rhs)?),
+            Expr::Unary(UnaryOp::Not, expr, _) => Value::Bool(!self.eval(*expr)?.is_truthy()),
+            Expr::Unary(UnaryOp::Neg, expr, span) => {
+                match self.eval(*expr)? {
+                    Value::Int(int) => Value::Int(int.checked_neg().ok_or_else(|| RuntimeError::at(
+                        ErrorKind::Value,
+                        "integer overflow",
+                        &span,
+                    ))?),
+                    Value::Num(num) => Value::Num(-num),
+                    val => return Err(RuntimeError::at(
+                        ErrorKind::Type,
+                        format!("invalid operand type for op {:?}: {}", UnaryOp::Neg, val.type_name()),
+                        &span,
+                    )),
+                }
             }
-            Expr::Call { func, args } => {
-                let func = self.eval(*func);
-                let args: Vec<Value> = args.into_iter().map(|expr| self.eval(expr)).collect();
+            Expr::Call { func, args, named, span } => {
+                let func = self.eval(*func)?;
+                let args = args.into_iter().map(|expr| self.eval(expr)).collect::<RuntimeResult<Vec<Value>>>()?;
+                let named = named.into_iter()
+                    .map(|(name, expr)| Ok((name, self.eval(expr)?)))
+                    .collect::<RuntimeResult<Vec<(String, Value)>>>()?;
+
+                let frame = match &func {
+                    Value::Func(Func::User { name, .. }) => Some(Frame {
+                        kind: name.clone().map(FrameKind::Func).unwrap_or(FrameKind::Lambda),
+                        call_site: span.clone(),
+                    }),
+                    _ => None,
+                };
 
-                self.call(func, args)
+                self.with_frame(frame, |int| int.call_named(func, args, named)).map_err(|err| err.or_at(&span))?
             }
             Expr::Lambda(func) => match func {
-                Func::User { name, params, body, .. } => Value::Func(Func::User {
+                Func::User { name, params, rest, body, doc, deps, .. } => Value::Func(Func::User {
                     name,
                     params,
+                    rest,
                     body,
+                    doc,
+                    deps,
                     captured_env: Some(Rc::clone(&self.env)),
                 }),
                 Func::Native { .. } => unreachable!()
             }
             _ => unreachable!()
-        }
+        };
+
+        Ok(val)
+    }
+
+    /// Evaluates an expression whose truthiness decides what runs next, commands failing in it are not errors.
+    fn eval_condition(&mut self, cond: Expr) -> RuntimeResult<Value> {
+        self.condition_depth += 1;
+        let res = self.eval(cond);
+        self.condition_depth -= 1;
+        res
     }
 
-    fn call(&mut self, func: Value, mut args: Vec<Value>) -> Value {
+    fn call(&mut self, func: Value, args: Vec<Value>) -> RuntimeResult<Value> {
+        self.call_named(func, args, Vec::new())
+    }
+
+    /// Calls `func` with `args` by position, followed by the `named` ones.
+    fn call_named(&mut self, func: Value, mut args: Vec<Value>, named: Vec<(String, Value)>) -> RuntimeResult<Value> {
         let func = match func {
             Value::Func(func) => func,
-            _ => panic!("attempt to call non-function"),
+            val => return Err(RuntimeError::new(ErrorKind::Type, format!("attempt to call {}", val.type_name()))),
         };
 
         match func {
-            Func::User { params, body, captured_env, .. } => {
-                assert_eq!(params.len(), args.len(), "number of arguments does not match number of parameters");
+            Func::User { name, params, rest, body, captured_env, .. } => {
+                let callee = name.map(|name| format!("{}()", name)).unwrap_or_else(|| "lambda".to_string());
 
                 let func_env = Rc::new(RefCell::new(if let Some(captured_env) = captured_env {
                     Env::new_from(&captured_env)
@@ -522,27 +787,31 @@ impl Interpreter {
 
                 let mut callee_env = mem::replace(&mut self.env, func_env);
 
-                for (param, arg) in params.into_iter().zip(args.into_iter()) {
-                    self.get_env_mut().def(param, arg);
-                }
-
-                let res = self.run_stmt(*body);
+                let res = self.bind_params(&callee, &params, rest.as_deref(), args, named)
+                    .map_err(Escape::from)
+                    .and_then(|()| self.run_stmt(*body));
 
                 mem::swap(&mut self.env, &mut callee_env);
 
                 match res {
-                    Err(Escape::Return(val)) => val,
-                    Err(_) => panic!("non return escape outside function"),
-                    _ => Value::Nil,
+                    Ok(()) => Ok(Value::Nil),
+                    Err(Escape::Return(val, _)) => Ok(*val),
+                    Err(escape) => Err(escape.into_error()),
                 }
             }
-            Func::Native { func, params, receiver, .. } => {
-                if let Some(receiver) = receiver {
-                    args.insert(0, *receiver);
+            Func::Native { name, func, params, receiver } => {
+                if let Some((arg_name, _)) = named.first() {
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!(
+                        "{}() doesn't take named arguments, got {}",
+                        name,
+                        arg_name,
+                    )));
                 }
 
-                if let Some(params) = params {
-                    assert_eq!(params, args.len(), "number of arguments does not match number of parameters");
+                signature::check_args(&name, params, &args)?;
+
+                if let Some(receiver) = receiver {
+                    args.insert(0, *receiver);
                 }
 
                 func(self, args)
@@ -550,17 +819,115 @@ impl Interpreter {
         }
     }
 
+    /// Defines the parameters of the user function `callee` in the current env. Arguments are given by position and then
+    /// by name, the ones left over go to the rest parameter and the parameters without one take their default.
+    fn bind_params(
+        &mut self,
+        callee: &str,
+        params: &[Pattern],
+        rest: Option<&Pattern>,
+        mut args: Vec<Value>,
+        named: Vec<(String, Value)>,
+    ) -> RuntimeResult<()> {
+        let min = params.iter().filter(|param| !matches!(param, Pattern::Default { .. })).count();
+        let max = if rest.is_some() { None } else { Some(params.len()) };
+
+        let extra = args.split_off(args.len().min(params.len()));
+        if !extra.is_empty() && rest.is_none() || named.is_empty() && args.len() < min {
+            return Err(signature::arity_error(callee, min, max, args.len() + extra.len()));
+        }
+
+        let mut vals = args.into_iter().map(Some).collect::<Vec<Option<Value>>>();
+        vals.resize(params.len(), None);
+
+        for (name, val) in named {
+            let i = params.iter().position(|param| param.param_name() == Some(&name)).ok_or_else(|| RuntimeError::new(
+                ErrorKind::Arity,
+                format!("{} has no parameter {}", callee, name),
+            ))?;
+
+            if vals[i].is_some() {
+                return Err(RuntimeError::new(ErrorKind::Arity, format!("{} got argument {} twice", callee, name)));
+            }
+            vals[i] = Some(val);
+        }
+
+        let rest = rest.map(|rest| (rest, Value::Vec(Rc::new(RefCell::new(extra)))));
+
+        for (param, val) in params.iter().zip(vals).chain(rest.map(|(rest, val)| (rest, Some(val)))) {
+            let bindings = match (param, val) {
+                (param, Some(val)) => self.destructure(param, val)?,
+                (Pattern::Default { pattern, default, .. }, None) => {
+                    let default = self.eval(default.clone())?;
+                    self.destructure(pattern, default)?
+                }
+                (param, None) => return Err(RuntimeError::new(
+                    ErrorKind::Arity,
+                    format!("{} is missing argument {}", callee, param),
+                )),
+            };
+
+            for (name, val) in bindings {
+                self.get_env_mut().def(name, val);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `f` with `frame` on top of the call stack.
+    fn with_frame<T>(&mut self, frame: Option<Frame>, f: impl FnOnce(&mut Self) -> RuntimeResult<T>) -> RuntimeResult<T> {
+        let is_pushed = frame.is_some();
+        if let Some(frame) = frame {
+            self.frames.push(frame);
+        }
+
+        let res = f(self).map_err(|err| self.capture_stack(err));
+
+        if is_pushed {
+            self.frames.pop();
+        }
+
+        res
+    }
+
+    /// Records the current call stack in `err` unless it already has the one from where it was raised.
+    fn capture_stack(&self, mut err: RuntimeError) -> RuntimeError {
+        if err.stack.is_empty() {
+            err.stack = self.frames.clone();
+        }
+        err
+    }
+
     pub fn set_args(&mut self, args: Vec<String>) {
         self.get_env_mut().def("args".to_string(), Value::Vec(Rc::new(RefCell::new(
-            args.into_iter().map(|s| Value::String(s)).collect()
+            args.into_iter().map(Value::String).collect()
         ))));
     }
 }
 
-fn dict_key(val: Value) -> String {
+/// Returns the path of the file imported by `import base_path` from a file in `import_root`.
+pub fn import_path(import_root: &Path, base_path: &str) -> PathBuf {
+    if base_path.ends_with(".koi") {
+        import_root.join(base_path)
+    } else {
+        import_root.join(format!("{}.koi", base_path))
+    }
+}
+
+fn operands_error(op: BinaryOp, lhs: &Value, rhs: &Value, span: &Span) -> RuntimeError {
+    RuntimeError::at(
+        ErrorKind::Type,
+        format!("invalid operands types for op {:?}: {} and {}", op, lhs.type_name(), rhs.type_name()),
+        span,
+    )
+}
+
+fn dict_key(val: Value) -> RuntimeResult<String> {
     match val {
-        Value::String(str) => str,
-        Value::Num(num) => num.to_string(),
-        _ => panic!("expected num or string"),
+        Value::String(str) => Ok(str),
+        Value::Int(int) => Ok(int.to_string()),
+        Value::Num(num) => Ok(num.to_string()),
+        val => Err(RuntimeError::new(ErrorKind::Type, format!("expected int, float or string as dict key, got {}", val.type_name()))),
     }
 }
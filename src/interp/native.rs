@@ -1,5 +1,6 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io;
 use std::io::{BufRead, Write};
 use std::process;
@@ -11,244 +12,268 @@ use serde_json::{from_str as json_from_str, Value as JSONValue};
 
 use crate::interp::dict_key;
 
-use super::Interpreter;
+use super::args::{ArgsError, Spec};
+use super::range;
+use super::range::Range;
+use super::{ErrorKind, Interpreter, RuntimeError, RuntimeResult};
 use super::value::Value;
 
-pub fn print(int: &mut Interpreter, args: Vec<Value>) -> Value {
+pub fn print(int: &mut Interpreter, args: Vec<Value>) -> RuntimeResult<Value> {
     let res = args.iter().map(|arg| arg.to_string()).join(" ");
 
     if let Some(str) = &mut int.collector {
         str.push_str(&res);
-        str.push_str("\n");
+        str.push('\n');
     } else {
         println!("{}", res);
     }
 
-    Value::Nil
+    Ok(Value::Nil)
 }
 
-pub fn input(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn input(int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     if int.collector.is_some() {
-        panic!("called input in testing");
+        return Err(RuntimeError::new(ErrorKind::Io, "called input in testing"));
     }
 
-    let msg = match args.remove(0) {
-        Value::String(msg) => msg,
-        _ => panic!("expected arg to be string")
-    };
-
-    print!("{}", msg);
-    io::stdout().flush().unwrap();
+    if let Some(Value::String(prompt)) = args.pop() {
+        print!("{}", prompt);
+        io::stdout().flush().map_err(io_error)?;
+    }
 
     let mut buf = String::new();
 
     let stdin = io::stdin();
-    stdin.lock().read_line(&mut buf).unwrap();
+    stdin.lock().read_line(&mut buf).map_err(io_error)?;
 
-    Value::String(buf)
+    Ok(Value::String(buf))
 }
 
-pub fn exit(_: &mut Interpreter, mut args: Vec<Value>) -> Value {
-    let code = match args.remove(0) {
-        Value::Num(num) if num.trunc() == num => num as i32,
-        _ => panic!("expected integer")
-    };
+pub fn exit(_: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    let code = args.remove(0);
+    let code = code.as_int().and_then(|int| i32::try_from(int).ok()).ok_or_else(|| integer_error("code", &code))?;
 
     process::exit(code);
 }
 
-pub fn glob(_: &mut Interpreter, mut args: Vec<Value>) -> Value {
-    let pat = match args.remove(0) {
-        Value::String(msg) => msg,
-        _ => panic!("expected arg to be string")
+pub fn errexit(int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    int.errexit = if let Value::Bool(errexit) = args.remove(0) { errexit } else { unreachable!() };
+
+    Ok(Value::Nil)
+}
+
+pub fn trace(int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    int.trace = if let Value::Bool(trace) = args.remove(0) { trace } else { unreachable!() };
+
+    Ok(Value::Nil)
+}
+
+pub fn parse_args(int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    let spec = Spec::from_value(&args.remove(0))?;
+
+    // The arguments of the script by default
+    let argv = match args.pop().unwrap_or_else(|| int.get("args")) {
+        Value::Vec(vec) => RefCell::borrow(&vec).iter().map(|arg| arg.to_string()).collect(),
+        _ => Vec::new(),
     };
 
-    let glob_res = glob::glob(&pat).expect("error while globbing");
+    // Left to whoever runs the script to print and exit on, so that `finally` blocks still run
+    spec.parse(&argv).map_err(|err| match err {
+        ArgsError::Help(help) => RuntimeError::new(ErrorKind::Help, help),
+        ArgsError::Invalid(message, usage) => RuntimeError::new(ErrorKind::Usage, format!("{}\n\n{}", message, usage)),
+    })
+}
+
+pub fn glob(_: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    let pat = if let Value::String(pat) = args.remove(0) { pat } else { unreachable!() };
+
+    let glob_res = glob::glob(&pat).map_err(|err| RuntimeError::new(
+        ErrorKind::Value,
+        format!("invalid glob pattern {}: {}", pat, err),
+    ))?;
 
-    Value::Vec(Rc::new(RefCell::new(
+    Ok(Value::Vec(Rc::new(RefCell::new(
         glob_res.filter_map(|entry| {
             match entry {
-                Ok(path) => Some(Value::String(
-                    path.to_str().expect("path is not a valid string").to_owned()
-                )),
+                Ok(path) => Some(Value::String(path.to_string_lossy().into_owned())),
                 Err(_) => None,
             }
         }).collect()
-    )))
+    ))))
 }
 
-pub fn string(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
-    Value::String(args.remove(0).to_string())
+pub fn string(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    Ok(Value::String(args.remove(0).to_string()))
 }
 
-pub fn bool(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
-    Value::Bool(args.remove(0).is_truthy())
+pub fn bool(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    Ok(Value::Bool(args.remove(0).is_truthy()))
 }
 
-pub fn typ(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
-    Value::String(String::from(match args.remove(0) {
-        Value::Nil => "nil",
-        Value::Num(_) => "num",
-        Value::String(_) => "string",
-        Value::Bool(_) => "bool",
-        Value::Vec(_) => "vec",
-        Value::Dict(_) => "dict",
-        Value::Range(_, _) => "range",
-        Value::Func(_) => "func"
-    }))
+pub fn typ(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    Ok(Value::String(args.remove(0).type_name().to_string()))
 }
 
-pub fn to_json(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
-    let json = JSONValue::from(args.remove(0));
-    Value::String(json.to_string())
+pub fn to_json(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    let json = JSONValue::try_from(args.remove(0))?;
+    Ok(Value::String(json.to_string()))
 }
 
-pub fn parse_json(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn parse_json(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
-    let val: JSONValue = json_from_str(&recv).unwrap();
-    Value::from(val)
+    let val: JSONValue = json_from_str(&recv).map_err(|err| RuntimeError::new(
+        ErrorKind::Value,
+        format!("invalid json: {}", err),
+    ))?;
+    Ok(Value::from(val))
 }
 
-pub fn strip(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn strip(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
-    Value::String(recv.trim().to_string())
+    Ok(Value::String(recv.trim().to_string()))
 }
 
-pub fn string_contains(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn string_contains(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
 
-    let target = match args.remove(0) {
-        Value::String(sep) => sep,
-        _ => panic!("expected arg to be string")
-    };
+    let target = if let Value::String(target) = args.remove(0) { target } else { unreachable!() };
 
-    Value::Bool(recv.contains(&target))
+    Ok(Value::Bool(recv.contains(&target)))
 }
 
-pub fn lower(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn lower(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
-    Value::String(recv.to_lowercase())
+    Ok(Value::String(recv.to_lowercase()))
 }
 
-pub fn upper(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn upper(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
-    Value::String(recv.to_uppercase())
+    Ok(Value::String(recv.to_uppercase()))
 }
 
-pub fn parse_bool(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn parse_bool(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
-    Value::Bool(recv.parse::<bool>().unwrap())
+    let bool = recv.parse::<bool>().map_err(|_| RuntimeError::new(
+        ErrorKind::Value,
+        format!("can't parse {:?} as bool", recv),
+    ))?;
+    Ok(Value::Bool(bool))
 }
 
-pub fn parse_num(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn parse_num(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
-    Value::Num(recv.parse::<f64>().unwrap())
+    if let Ok(int) = recv.parse() {
+        return Ok(Value::Int(int));
+    }
+
+    let num = recv.parse::<f64>().map_err(|_| RuntimeError::new(
+        ErrorKind::Value,
+        format!("can't parse {:?} as a number", recv),
+    ))?;
+    Ok(Value::Num(num))
 }
 
-pub fn replace(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn replace(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
 
-    let (from, to) = match (args.remove(0), args.remove(0)) {
-        (Value::String(from), Value::String(to)) => (from, to),
-        _ => panic!("expected args to be two strings")
-    };
+    let from = if let Value::String(from) = args.remove(0) { from } else { unreachable!() };
+    let to = if let Value::String(to) = args.remove(0) { to } else { unreachable!() };
 
-    Value::String(recv.replace(&from, &to))
+    Ok(Value::String(recv.replace(&from, &to)))
 }
 
-pub fn split(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn split(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
 
-    let sep = match args.remove(0) {
-        Value::String(sep) => sep,
-        _ => panic!("expected arg to be string")
-    };
+    let sep = if let Value::String(sep) = args.remove(0) { sep } else { unreachable!() };
 
     let vec: Vec<Value> = recv.split(&sep).map(|piece| Value::String(piece.to_string())).collect();
 
-    Value::Vec(Rc::new(RefCell::new(vec)))
+    Ok(Value::Vec(Rc::new(RefCell::new(vec))))
 }
 
-pub fn join(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn join(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
 
-    let vec = match args.remove(0) {
-        Value::Vec(vec) => vec,
-        _ => panic!("expected arg to be vec")
-    };
+    let vec = if let Value::Vec(vec) = args.remove(0) { vec } else { unreachable!() };
 
     let res = RefCell::borrow(&vec).iter().join(&recv);
 
-    Value::String(res)
+    Ok(Value::String(res))
 }
 
-pub fn string_len(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn string_len(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
-    Value::Num(recv.len() as f64)
+    Ok(Value::Int(recv.chars().count() as i64))
 }
 
-pub fn vec_len(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn vec_len(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
-    Value::Num(recv.len() as f64)
+    Ok(Value::Int(recv.len() as i64))
 }
 
-pub fn dict_len(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn dict_len(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
-    Value::Num(recv.len() as f64)
+    Ok(Value::Int(recv.len() as i64))
 }
 
-pub fn map(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn map(int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
 
     let f = args.remove(0);
 
-    let vec: Vec<Value> = recv.iter().map(|val| int.call(f.clone(), vec![val.clone()])).collect();
+    let vec = recv.iter().map(|val| int.call(f.clone(), vec![val.clone()])).collect::<RuntimeResult<Vec<Value>>>()?;
     let vec = Rc::new(RefCell::new(vec));
 
-    Value::Vec(vec)
+    Ok(Value::Vec(vec))
 }
 
-pub fn filter(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn filter(int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
 
     let f = args.remove(0);
 
-    let vec: Vec<Value> = recv.iter().filter(|&val| int.call(f.clone(), vec![val.clone()]).is_truthy()).cloned().collect();
+    let mut vec = Vec::new();
+    for val in recv.iter() {
+        if int.call(f.clone(), vec![val.clone()])?.is_truthy() {
+            vec.push(val.clone());
+        }
+    }
     let vec = Rc::new(RefCell::new(vec));
 
-    Value::Vec(vec)
+    Ok(Value::Vec(vec))
 }
 
-pub fn for_each(int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn for_each(int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
 
     let f = args.remove(0);
 
-    recv.iter().for_each(|val| {
-        int.call(f.clone(), vec![val.clone()]);
-    });
+    for val in recv.iter() {
+        int.call(f.clone(), vec![val.clone()])?;
+    }
 
-    Value::Nil
+    Ok(Value::Nil)
 }
 
-pub fn clone_vec(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn clone_vec(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
-    Value::Vec(Rc::new(RefCell::new(recv.clone())))
+    Ok(Value::Vec(Rc::new(RefCell::new(recv.clone()))))
 }
 
-pub fn clone_dict(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn clone_dict(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
-    Value::Dict(Rc::new(RefCell::new(recv.clone())))
+    Ok(Value::Dict(Rc::new(RefCell::new(recv.clone()))))
 }
 
-pub fn vec_2_dict(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn vec_2_dict(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
 
@@ -256,23 +281,23 @@ pub fn vec_2_dict(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
 
     for tuple in recv.iter() {
         let tuple = if let Value::Vec(tuple) = tuple {
-            RefCell::borrow(&tuple)
+            RefCell::borrow(tuple)
         } else {
-            panic!("expected all elements to be vec");
+            return Err(RuntimeError::new(ErrorKind::Type, format!("expected all elements to be vec, got {}", tuple.type_name())));
         };
 
         if tuple.len() != 2 {
-            panic!("expected all elements to have len 2");
+            return Err(RuntimeError::new(ErrorKind::Value, "expected all elements to have len 2"));
         }
 
-        let key = dict_key(tuple[0].clone());
+        let key = dict_key(tuple[0].clone())?;
         map.insert(key, tuple[1].clone());
     }
 
-    Value::Dict(Rc::new(RefCell::new(map)))
+    Ok(Value::Dict(Rc::new(RefCell::new(map))))
 }
 
-pub fn dict_2_vec(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn dict_2_vec(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
 
@@ -285,76 +310,116 @@ pub fn dict_2_vec(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
         ]))));
     }
 
-    Value::Vec(Rc::new(RefCell::new(vec)))
+    Ok(Value::Vec(Rc::new(RefCell::new(vec))))
 }
 
-pub fn matches(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn matches(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
 
-    let pat = match args.remove(0) {
-        Value::String(pat) => pat,
-        _ => panic!("expected arg to be string")
-    };
+    let pat = if let Value::String(pat) = args.remove(0) { pat } else { unreachable!() };
 
-    let re = Regex::new(&pat).unwrap();
+    let re = Regex::new(&pat).map_err(|err| RuntimeError::new(
+        ErrorKind::Value,
+        format!("invalid regex: {}", err),
+    ))?;
 
-    Value::Bool(re.is_match(&recv))
+    Ok(Value::Bool(re.is_match(&recv)))
 }
 
-pub fn find(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn find(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::String(recv) = args.remove(0) { recv } else { unreachable!() };
 
-    let pat = match args.remove(0) {
-        Value::String(pat) => pat,
-        _ => panic!("expected arg to be string")
-    };
+    let pat = if let Value::String(pat) = args.remove(0) { pat } else { unreachable!() };
 
-    let re = Regex::new(&pat).unwrap();
+    let re = Regex::new(&pat).map_err(|err| RuntimeError::new(
+        ErrorKind::Value,
+        format!("invalid regex: {}", err),
+    ))?;
 
     let matches = re.captures_iter(&recv).map(|match_| {
         let groups = match_.iter().map(|group|
-            Value::String(group.unwrap().as_str().to_string())
+            group.map(|group| Value::String(group.as_str().to_string())).unwrap_or(Value::Nil)
         ).collect::<Vec<Value>>();
 
         Value::Vec(Rc::new(RefCell::new(groups)))
     }).collect::<Vec<Value>>();
 
-    Value::Vec(Rc::new(RefCell::new(matches)))
+    Ok(Value::Vec(Rc::new(RefCell::new(matches))))
 }
 
-pub fn vec_contains(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn vec_contains(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
 
-    Value::Bool(recv.contains(&args.remove(0)))
+    Ok(Value::Bool(recv.contains(&args.remove(0))))
 }
 
-pub fn dict_contains(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn dict_contains(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
     let recv = RefCell::borrow(&recv);
 
-    let key = dict_key(args.remove(0));
+    let key = dict_key(args.remove(0))?;
 
-    Value::Bool(recv.contains_key(&key))
+    Ok(Value::Bool(recv.contains_key(&key)))
 }
 
-pub fn vec_remove(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn vec_remove(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Vec(recv) = args.remove(0) { recv } else { unreachable!() };
     let mut recv = RefCell::borrow_mut(&recv);
 
-    let index = match args.remove(0) {
-        Value::Num(index) if index.trunc() == index => index as usize,
-        _ => panic!("expected integer index")
-    };
+    let index = args.remove(0);
+    let index = index.as_int().ok_or_else(|| integer_error("index", &index))?;
+
+    let resolved = range::resolve_index(index, recv.len()).ok_or_else(|| RuntimeError::new(
+        ErrorKind::Index,
+        format!("index {} out of bounds for vec of len {}", index, recv.len()),
+    ))?;
 
-    recv.remove(index)
+    Ok(recv.remove(resolved))
 }
 
-pub fn dict_remove(_int: &mut Interpreter, mut args: Vec<Value>) -> Value {
+pub fn dict_remove(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
     let recv = if let Value::Dict(recv) = args.remove(0) { recv } else { unreachable!() };
     let mut recv = RefCell::borrow_mut(&recv);
 
-    let index = dict_key(args.remove(0));
+    let index = dict_key(args.remove(0))?;
+
+    recv.remove(&index).ok_or_else(|| RuntimeError::new(ErrorKind::Key, format!("key {} not found", index)))
+}
+
+pub fn range_len(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    let recv = if let Value::Range(recv) = args.remove(0) { recv } else { unreachable!() };
+    Ok(Value::Int(recv.len() as i64))
+}
+
+pub fn range_contains(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    let recv = if let Value::Range(recv) = args.remove(0) { recv } else { unreachable!() };
+    Ok(Value::Bool(args.remove(0).as_int().is_some_and(|int| recv.contains(int))))
+}
+
+pub fn rev(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    let recv = if let Value::Range(recv) = args.remove(0) { recv } else { unreachable!() };
+    Ok(Value::Range(recv.rev()))
+}
+
+pub fn step(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    let recv = if let Value::Range(recv) = args.remove(0) { recv } else { unreachable!() };
+
+    let step = args.remove(0);
+    let step = step.as_int().ok_or_else(|| integer_error("step", &step))?;
+
+    Ok(Value::Range(Range::new(recv.start, recv.end, step, recv.inclusive)?))
+}
+
+pub fn range_2_vec(_int: &mut Interpreter, mut args: Vec<Value>) -> RuntimeResult<Value> {
+    let recv = if let Value::Range(recv) = args.remove(0) { recv } else { unreachable!() };
+    Ok(Value::Vec(Rc::new(RefCell::new(recv.iter().map(Value::Int).collect()))))
+}
+
+fn integer_error(param: &str, got: &Value) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Value, format!("expected `{}` to be an integer, got {}", param, got))
+}
 
-    recv.remove(&index).expect("key not found")
+fn io_error(err: io::Error) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Io, err.to_string())
 }
@@ -0,0 +1,86 @@
+use std::convert::TryFrom;
+
+use crate::ast::BinaryOp;
+
+use super::{ErrorKind, RuntimeError, RuntimeResult, Value};
+
+/// Applies the arithmetic, comparison or bitwise `op` to two numbers, or returns `None` if it doesn't apply to them.
+/// Ints stay ints, except through `/`, and become floats when mixed with one. Bitwise ops only apply to ints.
+pub fn apply(op: BinaryOp, lhs: &Value, rhs: &Value) -> Option<RuntimeResult<Value>> {
+    match (lhs, rhs) {
+        (Value::Int(lhs), Value::Int(rhs)) => Some(apply_int(op, *lhs, *rhs)),
+        (Value::Int(_) | Value::Num(_), Value::Int(_) | Value::Num(_)) => apply_float(op, to_float(lhs), to_float(rhs)).map(Ok),
+        _ => None,
+    }
+}
+
+fn apply_int(op: BinaryOp, lhs: i64, rhs: i64) -> RuntimeResult<Value> {
+    let int = match op {
+        BinaryOp::Sum => lhs.checked_add(rhs),
+        BinaryOp::Sub => lhs.checked_sub(rhs),
+        BinaryOp::Mul => lhs.checked_mul(rhs),
+        BinaryOp::Div => return Ok(Value::Num(lhs as f64 / rhs as f64)),
+        BinaryOp::IntDiv | BinaryOp::Mod if rhs == 0 => {
+            return Err(RuntimeError::new(ErrorKind::Value, "division by zero"));
+        }
+        // Rounds towards negative infinity like `//` on floats
+        BinaryOp::IntDiv => lhs.checked_div(rhs).map(|quot| {
+            if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) { quot - 1 } else { quot }
+        }),
+        // Takes the sign of the divisor so that `a == (a // b) * b + a % b`
+        BinaryOp::Mod => lhs.checked_rem(rhs).map(|rem| {
+            if rem != 0 && (rem < 0) != (rhs < 0) { rem + rhs } else { rem }
+        }),
+        BinaryOp::Pow => match u32::try_from(rhs) {
+            Ok(rhs) => lhs.checked_pow(rhs),
+            Err(_) => return Ok(Value::Num((lhs as f64).powf(rhs as f64))),
+        },
+
+        BinaryOp::BitAnd => Some(lhs & rhs),
+        BinaryOp::BitOr => Some(lhs | rhs),
+        BinaryOp::BitXor => Some(lhs ^ rhs),
+        BinaryOp::Shl | BinaryOp::Shr => {
+            let shift = u32::try_from(rhs).ok().filter(|shift| *shift < i64::BITS).ok_or_else(|| RuntimeError::new(
+                ErrorKind::Value,
+                format!("can't shift by {}", rhs),
+            ))?;
+
+            Some(if op == BinaryOp::Shl { lhs << shift } else { lhs >> shift })
+        }
+
+        BinaryOp::Less => return Ok(Value::Bool(lhs < rhs)),
+        BinaryOp::Great => return Ok(Value::Bool(lhs > rhs)),
+        _ => unreachable!(),
+    };
+
+    int.map(Value::Int).ok_or_else(|| RuntimeError::new(ErrorKind::Value, "integer overflow"))
+}
+
+fn apply_float(op: BinaryOp, lhs: f64, rhs: f64) -> Option<Value> {
+    let num = match op {
+        BinaryOp::Sum => lhs + rhs,
+        BinaryOp::Sub => lhs - rhs,
+        BinaryOp::Mul => lhs * rhs,
+        BinaryOp::Div => lhs / rhs,
+        BinaryOp::IntDiv => (lhs / rhs).floor(),
+        BinaryOp::Mod => {
+            let rem = lhs % rhs;
+            if rem != 0.0 && (rem < 0.0) != (rhs < 0.0) { rem + rhs } else { rem }
+        }
+        BinaryOp::Pow => lhs.powf(rhs),
+
+        BinaryOp::Less => return Some(Value::Bool(lhs < rhs)),
+        BinaryOp::Great => return Some(Value::Bool(lhs > rhs)),
+        _ => return None,
+    };
+
+    Some(Value::Num(num))
+}
+
+fn to_float(val: &Value) -> f64 {
+    match val {
+        Value::Int(int) => *int as f64,
+        Value::Num(num) => *num,
+        _ => unreachable!(),
+    }
+}
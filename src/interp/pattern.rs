@@ -0,0 +1,174 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use regex::Regex;
+
+use crate::ast::{BinaryOp, Pattern};
+
+use super::{num, ErrorKind, Interpreter, RuntimeError, RuntimeResult, Value};
+
+impl Interpreter {
+    /// Whether `val` matches `pattern`. The variables bound by the pattern are added to `bindings`, which is only
+    /// meaningful if it matches.
+    pub(super) fn match_pattern(&mut self, pattern: &Pattern, val: &Value, bindings: &mut Vec<(String, Value)>) -> RuntimeResult<bool> {
+        let is_match = match pattern {
+            Pattern::Any => true,
+            Pattern::Bind(name, _) => {
+                bindings.push((name.clone(), val.clone()));
+                true
+            }
+            Pattern::Value(expr) => self.eval(expr.clone())? == *val,
+            // Compared like `<` does, so ints only become floats next to a float
+            Pattern::Range { l, r, inclusive } => match (is_less(val, l), is_less(val, r)) {
+                (Some(below), Some(before_end)) => !below && (before_end || *inclusive && *val == **r),
+                _ => false,
+            },
+            Pattern::Regex(pattern) => {
+                let string = match val {
+                    Value::String(string) => string,
+                    _ => return Ok(false),
+                };
+
+                let re = Regex::new(pattern).map_err(|err| RuntimeError::new(
+                    ErrorKind::Value,
+                    format!("invalid regex: {}", err),
+                ))?;
+
+                let captures = match re.captures(string) {
+                    Some(captures) => captures,
+                    None => return Ok(false),
+                };
+
+                for name in re.capture_names().flatten() {
+                    let group = captures.name(name)
+                        .map(|group| Value::String(group.as_str().to_string()))
+                        .unwrap_or(Value::Nil);
+                    bindings.push((name.to_string(), group));
+                }
+
+                true
+            }
+            Pattern::Glob(pattern) => {
+                let string = match val {
+                    Value::String(string) => string,
+                    _ => return Ok(false),
+                };
+
+                glob::Pattern::new(pattern).map_err(|err| RuntimeError::new(
+                    ErrorKind::Value,
+                    format!("invalid glob: {}", err),
+                ))?.matches(string)
+            }
+            Pattern::Vec { items, rest } => {
+                let vec = match val {
+                    Value::Vec(vec) => RefCell::borrow(vec).clone(),
+                    _ => return Ok(false),
+                };
+
+                let (before, after) = items.split_at(rest.as_ref().map_or(items.len(), |(at, _)| *at));
+
+                // Items after the rest match the end of the vec, the ones before it the start, where the ones with a
+                // default may be missing
+                if vec.len() < after.len() {
+                    return Ok(false);
+                }
+                let (head, tail) = vec.split_at(vec.len() - after.len());
+
+                if rest.is_none() && head.len() > before.len() {
+                    return Ok(false);
+                }
+
+                for (i, pattern) in before.iter().enumerate() {
+                    let is_match = match head.get(i) {
+                        Some(val) => self.match_pattern(pattern, val, bindings)?,
+                        None => self.match_missing(pattern, bindings)?,
+                    };
+                    if !is_match {
+                        return Ok(false);
+                    }
+                }
+
+                for (pattern, val) in after.iter().zip(tail) {
+                    if !self.match_pattern(pattern, val, bindings)? {
+                        return Ok(false);
+                    }
+                }
+
+                if let Some((_, Some(name))) = rest {
+                    let rest = head.get(before.len()..).unwrap_or_default().to_vec();
+                    bindings.push((name.clone(), Value::Vec(Rc::new(RefCell::new(rest)))));
+                }
+
+                true
+            }
+            Pattern::Dict(entries) => {
+                let dict = match val {
+                    Value::Dict(dict) => RefCell::borrow(dict).clone(),
+                    _ => return Ok(false),
+                };
+
+                for (key, pattern) in entries {
+                    let is_match = match dict.get(key) {
+                        Some(val) => self.match_pattern(pattern, val, bindings)?,
+                        None => self.match_missing(pattern, bindings)?,
+                    };
+                    if !is_match {
+                        return Ok(false);
+                    }
+                }
+
+                true
+            }
+            Pattern::Or(alternatives) => {
+                for alternative in alternatives {
+                    let mut alternative_bindings = Vec::new();
+                    if self.match_pattern(alternative, val, &mut alternative_bindings)? {
+                        bindings.append(&mut alternative_bindings);
+                        return Ok(true);
+                    }
+                }
+
+                false
+            }
+            Pattern::Default { pattern, .. } => self.match_pattern(pattern, val, bindings)?,
+        };
+
+        Ok(is_match)
+    }
+
+    /// Whether `pattern` matches an item that's missing from a vec or dict, which only ones with a default do.
+    fn match_missing(&mut self, pattern: &Pattern, bindings: &mut Vec<(String, Value)>) -> RuntimeResult<bool> {
+        match pattern {
+            Pattern::Default { pattern, default, .. } => {
+                let default = self.eval(default.clone())?;
+                self.match_pattern(pattern, &default, bindings)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Binds the variables of `pattern` to the parts of `val`, failing if it doesn't match.
+    pub(super) fn destructure(&mut self, pattern: &Pattern, val: Value) -> RuntimeResult<Vec<(String, Value)>> {
+        if let Pattern::Bind(name, _) = pattern {
+            return Ok(vec![(name.clone(), val)]);
+        }
+
+        let mut bindings = Vec::new();
+        if !self.match_pattern(pattern, &val, &mut bindings)? {
+            return Err(RuntimeError::new(
+                ErrorKind::Value,
+                format!("can't destructure {} with {}", val.to_string_quoted(), pattern),
+            ));
+        }
+
+        Ok(bindings)
+    }
+}
+
+/// Whether the number `lhs` is less than the number `rhs`, or `None` if `lhs` isn't a number.
+fn is_less(lhs: &Value, rhs: &Value) -> Option<bool> {
+    match num::apply(BinaryOp::Less, lhs, rhs) {
+        Some(Ok(Value::Bool(is_less))) => Some(is_less),
+        _ => None,
+    }
+}
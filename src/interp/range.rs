@@ -0,0 +1,91 @@
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+
+use super::{ErrorKind, RuntimeError, RuntimeResult};
+
+/// The ints from `start` to `end` every `step`, which counts down when it's negative, as in `10..0 step -2`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    pub fn new(start: i64, end: i64, step: i64, inclusive: bool) -> RuntimeResult<Range> {
+        if step == 0 {
+            return Err(RuntimeError::new(ErrorKind::Value, "range step can't be 0"));
+        }
+
+        Ok(Range { start, end, step, inclusive })
+    }
+
+    pub fn len(&self) -> usize {
+        // Wide enough for the distance between any two ints
+        let step = self.step as i128;
+        let end = self.end as i128 + if self.inclusive { step.signum() } else { 0 };
+        let distance = end - self.start as i128;
+
+        if distance == 0 || distance.signum() != step.signum() {
+            return 0;
+        }
+
+        usize::try_from((distance.abs() + step.abs() - 1) / step.abs()).unwrap_or(usize::MAX)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=i64> {
+        let Range { start, step, .. } = *self;
+        (0..self.len()).map(move |i| (start as i128 + i as i128 * step as i128) as i64)
+    }
+
+    pub fn contains(&self, int: i64) -> bool {
+        let offset = int as i128 - self.start as i128;
+        let step = self.step as i128;
+
+        offset % step == 0 && (0..self.len() as i128).contains(&(offset / step))
+    }
+
+    /// The same ints in the opposite order.
+    pub fn rev(&self) -> Range {
+        let last = match self.iter().last() {
+            Some(last) => last,
+            None => return Range { end: self.start, inclusive: false, ..*self },
+        };
+
+        Range {
+            start: last,
+            end: self.start,
+            step: -self.step,
+            inclusive: true,
+        }
+    }
+
+    /// The indices of a vec or string of length `len` picked by the range, where negative bounds count from the end.
+    /// Returns `None` if any of them is out of bounds.
+    pub fn indices(&self, len: usize) -> Option<Vec<usize>> {
+        let from_end = |bound: i64| if bound < 0 { bound + len as i64 } else { bound };
+        let range = Range { start: from_end(self.start), end: from_end(self.end), ..*self };
+
+        range.iter().map(|index| usize::try_from(index).ok().filter(|index| *index < len)).collect()
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}{}", self.start, if self.inclusive { "=" } else { "" }, self.end)?;
+
+        if self.step != 1 {
+            write!(f, " step {}", self.step)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The index of a vec or string of length `len` that `index` refers to, where negative ones count from the end.
+pub fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { index + len as i64 } else { index };
+    usize::try_from(index).ok().filter(|index| *index < len)
+}
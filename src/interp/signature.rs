@@ -0,0 +1,144 @@
+use std::fmt::{Display, Formatter};
+use std::fmt;
+
+use itertools::Itertools;
+
+use super::{ErrorKind, RuntimeError, RuntimeResult, Value};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Type {
+    Any,
+    Nil,
+    Num,
+    String,
+    Bool,
+    Vec,
+    Dict,
+    Range,
+    Func,
+}
+
+impl Type {
+    pub fn accepts(&self, val: &Value) -> bool {
+        matches!((self, val),
+            (Type::Any, _) |
+            (Type::Nil, Value::Nil) |
+            (Type::Num, Value::Int(_) | Value::Num(_)) |
+            (Type::String, Value::String(_)) |
+            (Type::Bool, Value::Bool(_)) |
+            (Type::Vec, Value::Vec(_)) |
+            (Type::Dict, Value::Dict(_)) |
+            (Type::Range, Value::Range(..)) |
+            (Type::Func, Value::Func(_))
+        )
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            Type::Any => "any",
+            Type::Nil => "nil",
+            Type::Num => "int or float",
+            Type::String => "string",
+            Type::Bool => "bool",
+            Type::Vec => "vec",
+            Type::Dict => "dict",
+            Type::Range => "range",
+            Type::Func => "func",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Arity {
+    Required,
+    Optional,
+    // Takes all the remaining arguments, only allowed for the last parameter
+    Variadic,
+}
+
+/// A parameter of a native function. The receiver of a method is not part of its parameters.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Param {
+    pub name: &'static str,
+    pub types: &'static [Type],
+    pub arity: Arity,
+}
+
+impl Param {
+    pub const fn required(name: &'static str, types: &'static [Type]) -> Param {
+        Param { name, types, arity: Arity::Required }
+    }
+
+    pub const fn optional(name: &'static str, types: &'static [Type]) -> Param {
+        Param { name, types, arity: Arity::Optional }
+    }
+
+    pub const fn variadic(name: &'static str, types: &'static [Type]) -> Param {
+        Param { name, types, arity: Arity::Variadic }
+    }
+}
+
+impl Display for Param {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.arity {
+            Arity::Required => write!(f, "{}: {}", self.name, self.types.iter().join(" | ")),
+            Arity::Optional => write!(f, "{}?: {}", self.name, self.types.iter().join(" | ")),
+            Arity::Variadic => write!(f, "...{}: {}", self.name, self.types.iter().join(" | ")),
+        }
+    }
+}
+
+/// Formats a signature like `split(sep: string)`.
+pub fn describe(name: &str, params: &[Param]) -> String {
+    format!("{}({})", name, params.iter().join(", "))
+}
+
+/// Checks that `args` are valid for a native function called `name` that takes `params`.
+pub fn check_args(name: &str, params: &[Param], args: &[Value]) -> RuntimeResult<()> {
+    let min = params.iter().filter(|param| param.arity == Arity::Required).count();
+    let max = match params.last() {
+        Some(Param { arity: Arity::Variadic, .. }) => None,
+        _ => Some(params.len()),
+    };
+
+    if args.len() < min || max.is_some_and(|max| args.len() > max) {
+        return Err(arity_error(&format!("{}()", name), min, max, args.len()));
+    }
+
+    for (i, arg) in args.iter().enumerate() {
+        // Extra arguments all belong to the variadic parameter
+        let param = &params[i.min(params.len() - 1)];
+
+        if !param.types.iter().any(|ty| ty.accepts(arg)) {
+            return Err(RuntimeError::new(ErrorKind::Type, format!(
+                "{}() expects argument {} `{}` to be {}, got {}",
+                name,
+                i + 1,
+                param.name,
+                param.types.iter().join(" or "),
+                arg.type_name(),
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// The error for calling `callee` with `got` arguments when it takes `min` to `max` of them, or any number from `min`.
+pub fn arity_error(callee: &str, min: usize, max: Option<usize>, got: usize) -> RuntimeError {
+    let expected = match max {
+        Some(max) if max == min => format!("{}", min),
+        Some(max) => format!("{} to {}", min, max),
+        None => format!("at least {}", min),
+    };
+
+    RuntimeError::new(ErrorKind::Arity, format!(
+        "{} expects {} argument{}, got {}",
+        callee,
+        expected,
+        if min == 1 && max.is_none_or(|max| max == 1) { "" } else { "s" },
+        got,
+    ))
+}
@@ -7,9 +7,10 @@ use super::*;
 use super::value::Value;
 
 fn output(source: &str, import_root: Option<PathBuf>) -> String {
-    let lexer = new_lexer(source.to_owned());
+    let lexer = new_lexer(Source::new(String::new(), source.to_owned()));
     let mut parser = Parser::new(lexer);
-    let prog = parser.parse();
+    let (prog, errors) = parser.parse();
+    assert!(errors.is_empty());
 
     let mut interpreter = Interpreter::new();
     interpreter.do_collect();
@@ -18,11 +19,23 @@ fn output(source: &str, import_root: Option<PathBuf>) -> String {
         interpreter.set_import_root(import_root);
     }
 
-    interpreter.run(prog);
+    interpreter.run(prog).unwrap();
 
     interpreter.collector.take().unwrap()
 }
 
+fn error(source: &str) -> RuntimeError {
+    let lexer = new_lexer(Source::new("test.koi".to_owned(), source.to_owned()));
+    let mut parser = Parser::new(lexer);
+    let (prog, errors) = parser.parse();
+    assert!(errors.is_empty());
+
+    let mut interpreter = Interpreter::new();
+    interpreter.do_collect();
+
+    interpreter.run(prog).unwrap_err()
+}
+
 #[test]
 fn vec_equality() {
     assert_eq!(Value::Vec(Rc::new(RefCell::new(vec![Value::Num(1.0)]))), Value::Vec(Rc::new(RefCell::new(vec![Value::Num(1.0)]))));
@@ -107,7 +120,7 @@ fn arithmetic() {
     assert_eq!(output("print(3*2)", None), "6\n".to_string());
     assert_eq!(output("print(8/2)", None), "4\n".to_string());
     assert_eq!(output("print(11%5)", None), "1\n".to_string());
-    assert_eq!(output("print(2^4)", None), "16\n".to_string());
+    assert_eq!(output("print(2**4)", None), "16\n".to_string());
 }
 
 #[test]
@@ -117,7 +130,7 @@ fn arithmetic_assignment() {
     assert_eq!(output("let x = 15 x *= 2 print(x)", None), "30\n".to_string());
     assert_eq!(output("let x = 6 x /= 2 print(x)", None), "3\n".to_string());
     assert_eq!(output("let x = 6 x %= 2 print(x)", None), "0\n".to_string());
-    assert_eq!(output("let x = 6 x ^= 2 print(x)", None), "36\n".to_string());
+    assert_eq!(output("let x = 6 x **= 2 print(x)", None), "36\n".to_string());
 }
 
 #[test]
@@ -173,7 +186,7 @@ fn func() {
 
 #[test]
 fn func_return() {
-    assert_eq!(output("fn p(x) {return x^4} print(p(2))", None), "16\n".to_string());
+    assert_eq!(output("fn p(x) {return x**4} print(p(2))", None), "16\n".to_string());
 }
 
 #[test]
@@ -187,21 +200,201 @@ fn func_no_return() {
 }
 
 #[test]
-#[should_panic]
 fn uncaught_break() {
-    output("break", None);
+    assert_eq!(error("break").kind, ErrorKind::Control);
 }
 
 #[test]
-#[should_panic]
 fn uncaught_return() {
-    output("return", None);
+    assert_eq!(error("return").kind, ErrorKind::Control);
 }
 
 #[test]
-#[should_panic]
 fn uncaught_break_func_call() {
-    output("fn p() {break} p()", None);
+    assert_eq!(error("fn p() {break} p()").kind, ErrorKind::Control);
+}
+
+#[test]
+fn reports_error_location() {
+    let err = error("let x = 1\nprint(x + 'a')");
+    assert_eq!(err.kind, ErrorKind::Type);
+    assert_eq!(err.message, "invalid operands types for op Sum: int and string");
+    assert_eq!(err.span.unwrap().to_string(), "test.koi:2:9");
+}
+
+#[test]
+fn reports_undefined_variable() {
+    let err = error("x = 1");
+    assert_eq!(err.kind, ErrorKind::Name);
+    assert_eq!(err.span.unwrap().to_string(), "test.koi:1:1");
+}
+
+#[test]
+fn reports_native_error_at_call() {
+    let err = error("let d = {}\nd.remove('foo')");
+    assert_eq!(err.kind, ErrorKind::Key);
+    assert_eq!(err.span.unwrap().to_string(), "test.koi:2:9");
+}
+
+#[test]
+fn reports_arity_error() {
+    let err = error("fn f(a, b) {}\nf(1)");
+    assert_eq!(err.kind, ErrorKind::Arity);
+    assert_eq!(err.message, "f() expects 2 arguments, got 1");
+
+    assert_eq!(error("fn f(a, b = 1) {}\nf()").message, "f() expects 1 to 2 arguments, got 0");
+    assert_eq!(error("fn f(a, ...rest) {}\nf()").message, "f() expects at least 1 argument, got 0");
+    assert_eq!(error("let f = fn(a) {}\nf(1, 2)").message, "lambda expects 1 argument, got 2");
+}
+
+#[test]
+fn reports_bad_named_args() {
+    let err = error("fn f(a, b) {}\nf(b: 1)");
+    assert_eq!(err.kind, ErrorKind::Arity);
+    assert_eq!(err.message, "f() is missing argument a");
+
+    assert_eq!(error("fn f(a) {}\nf(b: 1)").message, "f() has no parameter b");
+    assert_eq!(error("fn f(a) {}\nf(1, a: 2)").message, "f() got argument a twice");
+    assert_eq!(error("fn f([a]) {}\nf(a: 1)").message, "f() has no parameter a");
+    assert_eq!(error("print(x: 1)").message, "print() doesn't take named arguments, got x");
+}
+
+#[test]
+fn reports_failed_destructuring() {
+    let err = error("let [a, b] = [1]");
+    assert_eq!(err.kind, ErrorKind::Value);
+    assert_eq!(err.message, "can't destructure [1] with [a, b]");
+    assert_eq!(err.span.unwrap().to_string(), "test.koi:1:5");
+
+    let err = error("fn f({name, port = 80}) {}\nf('x')");
+    assert_eq!(err.message, "can't destructure 'x' with {name, port = 80}");
+    assert_eq!(err.span.unwrap().to_string(), "test.koi:2:2");
+}
+
+#[test]
+fn validates_native_args() {
+    let err = error("let parts = 'a,b'.split(1)");
+    assert_eq!(err.kind, ErrorKind::Type);
+    assert_eq!(err.message, "split() expects argument 1 `sep` to be string, got int");
+
+    let err = error("let s = 'a'.replace('a')");
+    assert_eq!(err.kind, ErrorKind::Arity);
+    assert_eq!(err.message, "replace() expects 2 arguments, got 1");
+
+    assert_eq!(error("input(1)").message, "input() expects argument 1 `prompt` to be string, got int");
+    assert_eq!(error("input('a', 'b')").message, "input() expects 0 to 1 arguments, got 2");
+    assert_eq!(error("exit('a')").message, "exit() expects argument 1 `code` to be int or float, got string");
+    assert_eq!(error("let v = [1]\nv.remove(0.5)").message, "expected `index` to be an integer, got 0.5");
+}
+
+#[test]
+fn describes_native_funcs() {
+    assert_eq!(Interpreter::new().get_env().get("print").to_string(), "<native func print(...values: any)>");
+    assert_eq!(output("print(''.split)", None), "<native func split(sep: string)>\n");
+}
+
+#[test]
+fn throws_uncaught_error() {
+    let err = error("throw 'oops'");
+    assert_eq!(err.kind, ErrorKind::User);
+    assert_eq!(err.message, "oops");
+
+    assert_eq!(error("throw 1").kind, ErrorKind::Type);
+    assert_eq!(error("throw {}").kind, ErrorKind::Value);
+}
+
+#[test]
+fn rethrows_caught_error() {
+    let err = error("let d = {}\ntry { d.remove('a') } catch err { throw err }");
+    assert_eq!(err.message, "key a not found");
+    assert!(err.thrown.unwrap().to_string().contains("kind: 'key'"));
+}
+
+#[test]
+fn errexit_stops_at_failing_command() {
+    let err = error("errexit(true)\npython -c 'exit(3)'\nprint('unreachable')");
+    assert_eq!(err.kind, ErrorKind::Command);
+    assert_eq!(err.message, "command `python -c 'exit(3)'` exited with status 3");
+    assert_eq!(err.span.unwrap().to_string(), "test.koi:2:1");
+
+    assert_eq!(error("errexit(true)\nlet out = $(python -c 'exit(1)')").kind, ErrorKind::Command);
+}
+
+#[test]
+fn errexit_stops_at_failing_end_of_chain() {
+    let err = error("errexit(true)\ntrue && false\nprint('unreachable')");
+    assert_eq!(err.kind, ErrorKind::Command);
+    assert_eq!(err.message, "command `true && false` exited with status 1");
+
+    assert_eq!(error("errexit(true)\nfalse || false").kind, ErrorKind::Command);
+    assert_eq!(error("errexit(true)\nfalse && true || false").kind, ErrorKind::Command);
+}
+
+#[test]
+fn errexit_exempts_guarded_commands() {
+    assert_eq!(output("errexit(true)
+python -c 'exit(1)' || python -c 'exit(0)'
+python -c 'exit(1)' && python -c 'exit(0)'
+if $(python -c 'exit(1)') == '' { print('cond') }
+let out = $(python -c 'exit(1)') || 'default'
+print(out)
+errexit(false)
+python -c 'exit(1)'
+print(rc)", None), "cond\n\n1\n");
+}
+
+#[test]
+fn reports_bad_ranges() {
+    let err = error("let r = 0..1.5");
+    assert_eq!(err.kind, ErrorKind::Type);
+    assert_eq!(err.message, "range bounds and step must be integers");
+
+    assert_eq!(error("let s = 'abc'\nprint(s[1..4])").message, "range 1..4 out of bounds for string of len 3");
+}
+
+#[test]
+fn reports_spawn_failures() {
+    // Like in a shell the script carries on with the status of a missing command
+    assert_eq!(output("not_a_real_command_koi --flag\nprint(rc)", None), "127\n");
+
+    let err = error("errexit(true)\nnot_a_real_command_koi --flag");
+    assert_eq!(err.kind, ErrorKind::Spawn);
+    assert_eq!(err.message, "command not found: not_a_real_command_koi");
+    assert_eq!(err.span.unwrap().to_string(), "test.koi:2:1");
+
+    assert_eq!(output("fn f() {\n    not_a_real_command_koi\n}\ntry {\n    f()\n} catch err {\n    print(err.kind)\n}\nf()\nprint('after')", None), "spawn\nafter\n");
+    assert_eq!(output("try {\n    not_a_real_command_koi\n} catch err {\n    print(err.kind)\n}", None), "spawn\n");
+
+    // Guarded commands only print the error and carry on with the status a shell would give
+    assert_eq!(output("not_a_real_command_koi || python -c 'print(1)'\nprint(rc)", None), "1\n0\n");
+    assert_eq!(output("not_a_real_command_koi || not_a_real_command_koi\nprint(rc)", None), "127\n");
+    assert_eq!(error("errexit(true)\nnot_a_real_command_koi | python -c 'print(1)'").kind, ErrorKind::Spawn);
+}
+
+#[test]
+fn records_call_stack() {
+    let err = error("fn f() { g() }\nfn g() { [][1] = 0 }\nf()");
+    assert_eq!(err.kind, ErrorKind::Index);
+    assert_eq!(err.span.unwrap().to_string(), "test.koi:2:12");
+    assert_eq!(err.stack.iter().map(|frame| frame.to_string()).collect::<Vec<String>>(), vec!["f()", "g()"]);
+    assert_eq!(err.stack[1].call_site.to_string(), "test.koi:1:11");
+}
+
+#[test]
+fn records_lambda_and_import_frames() {
+    let err = error("let f = fn() { let x = nil + 1 }\nf()");
+    assert_eq!(err.stack.iter().map(|frame| frame.to_string()).collect::<Vec<String>>(), vec!["<lambda>"]);
+
+    let err = error("import 'does_not_exist'");
+    assert!(err.stack.is_empty());
+}
+
+#[test]
+fn exposes_call_stack_to_catch() {
+    assert_eq!(
+        output("fn f() { throw 'oops' }\ntry { f() } catch err { print(err.stack[0].name, err.stack[0].location.line) }", None),
+        "f() 2\n",
+    );
 }
 
 #[test]
@@ -302,7 +495,7 @@ fn native_dict_len() {
 
 #[test]
 fn native_map() {
-    assert_eq!(output("print([2 4 6].map(fn(i){return i^2}))", None), "[4, 16, 36]\n".to_string());
+    assert_eq!(output("print([2 4 6].map(fn(i){return i**2}))", None), "[4, 16, 36]\n".to_string());
 }
 
 #[test]
@@ -395,7 +588,7 @@ fn test_golden() {
         let content = fs::read_to_string(path.unwrap().path()).unwrap();
         let (source, want) = {
             let split = content.split("\n#---\n").collect::<Vec<&str>>();
-            (split[0].clone(), split[1].clone())
+            (split[0], split[1])
         };
 
         assert_eq!(output(source, Some(import_root.clone())), want);
@@ -408,3 +601,142 @@ fn native_bool() {
     assert_eq!(output("print(false.bool())", None), "false\n".to_string());
     assert_eq!(output("print(123.bool())", None), "true\n".to_string());
 }
+
+fn args_spec(spec: &str) -> args::Spec {
+    let lexer = new_lexer(Source::new(String::new(), format!("let spec = {}", spec)));
+    let (prog, errors) = Parser::new(lexer).parse();
+    assert!(errors.is_empty());
+
+    let mut interpreter = Interpreter::new();
+    interpreter.run(prog).unwrap();
+
+    args::Spec::from_value(&interpreter.get("spec")).unwrap()
+}
+
+fn parse_args(spec: &args::Spec, args: &[&str]) -> Result<Value, args::ArgsError> {
+    spec.parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>())
+}
+
+#[test]
+fn parses_args() {
+    let source = "let opts = parseArgs({flags: {v: 'Verbose'}, positionals: ['env']}, ['--v', 'prod'])\nprint(opts.env, opts.v)";
+    assert_eq!(output(source, None), "prod true\n");
+
+    let spec = args_spec("{
+        flags: {verbose: {short: 'v'}},
+        options: {count: {type: 'num', default: 1, short: 'n'}, dry: {type: 'bool'}},
+        positionals: ['env', {name: 'region', default: 'eu'}],
+    }");
+
+    let parsed = parse_args(&spec, &["prod", "-n3", "--dry=true", "-v"]).unwrap();
+    let parsed = if let Value::Dict(parsed) = parsed { parsed.borrow().clone() } else { unreachable!() };
+
+    assert_eq!(parsed["env"], Value::String("prod".to_owned()));
+    assert_eq!(parsed["region"], Value::String("eu".to_owned()));
+    assert_eq!(parsed["count"], Value::Num(3.0));
+    assert_eq!(parsed["dry"], Value::Bool(true));
+    assert_eq!(parsed["verbose"], Value::Bool(true));
+
+    let parsed = parse_args(&spec, &["--", "-v", "us"]).unwrap();
+    let parsed = if let Value::Dict(parsed) = parsed { parsed.borrow().clone() } else { unreachable!() };
+
+    assert_eq!(parsed["env"], Value::String("-v".to_owned()));
+    assert_eq!(parsed["count"], Value::Num(1.0));
+    assert_eq!(parsed["verbose"], Value::Bool(false));
+}
+
+#[test]
+fn parses_grouped_short_flags() {
+    let spec = args_spec("{
+        flags: {all: {short: 'a'}, long: {short: 'l'}},
+        options: {count: {type: 'num', short: 'n'}},
+    }");
+
+    let parsed = parse_args(&spec, &["-al", "-an", "3"]).unwrap();
+    let parsed = if let Value::Dict(parsed) = parsed { parsed.borrow().clone() } else { unreachable!() };
+
+    assert_eq!(parsed["all"], Value::Bool(true));
+    assert_eq!(parsed["long"], Value::Bool(true));
+    assert_eq!(parsed["count"], Value::Int(3));
+
+    let parsed = parse_args(&spec, &["-ln5"]).unwrap();
+    let parsed = if let Value::Dict(parsed) = parsed { parsed.borrow().clone() } else { unreachable!() };
+
+    assert_eq!(parsed["long"], Value::Bool(true));
+    assert_eq!(parsed["count"], Value::Int(5));
+
+    assert!(matches!(parse_args(&spec, &["-ax"]), Err(args::ArgsError::Invalid(message, _)) if message == "unknown option -x"));
+}
+
+#[test]
+fn rejects_mistyped_option_defaults() {
+    assert_eq!(
+        error("parseArgs({options: {count: {type: 'num', default: 'one'}}}, [])").message,
+        "invalid parseArgs spec: default of option count must be int or float, got string",
+    );
+    assert_eq!(
+        error("parseArgs({options: {dry: {type: 'bool', default: 1}}}, [])").message,
+        "invalid parseArgs spec: default of option dry must be bool, got int",
+    );
+}
+
+#[test]
+fn rejects_invalid_args() {
+    let spec = args_spec("{name: 'deploy', options: {count: {type: 'num'}}, positionals: ['env']}");
+
+    let message = |args: &[&str]| match parse_args(&spec, args) {
+        Err(args::ArgsError::Invalid(message, usage)) => {
+            assert_eq!(usage, "USAGE:\n    deploy [OPTIONS] <env>");
+            message
+        }
+        res => panic!("expected invalid args, got {:?}", res),
+    };
+
+    assert_eq!(message(&[]), "missing argument <env>");
+    assert_eq!(message(&["a", "b"]), "unexpected argument b");
+    assert_eq!(message(&["--nope", "a"]), "unknown option --nope");
+    assert_eq!(message(&["--count", "x", "a"]), "invalid value 'x' for option --count: expected int or float");
+    assert_eq!(message(&["a", "--count"]), "missing value for option --count");
+
+    assert!(matches!(parse_args(&spec, &["--help"]), Err(args::ArgsError::Help(_))));
+}
+
+#[test]
+fn throws_help_and_usage_from_parse_args() {
+    let err = error("parseArgs({name: 'deploy', positionals: ['env']}, ['--help'])");
+    assert_eq!((err.kind, err.exit_status()), (ErrorKind::Help, 0));
+    assert!(err.to_string().starts_with("USAGE:\n    deploy"));
+
+    let err = error("parseArgs({name: 'deploy', positionals: ['env']}, [])");
+    assert_eq!((err.kind, err.exit_status()), (ErrorKind::Usage, 2));
+    assert_eq!(err.to_string(), "error: missing argument <env>\n\nUSAGE:\n    deploy <env>\n\nFor more information try --help\n");
+
+    assert_eq!(output("try {\n    parseArgs({}, ['-h'])\n} catch err {\n    print(err.kind)\n} finally {\n    print('finally')\n}", None), "help\nfinally\n");
+}
+
+#[test]
+fn generates_help() {
+    let spec = args_spec("{
+        name: 'deploy',
+        help: 'Deploys the app',
+        flags: {verbose: {short: 'v', help: 'Print more'}},
+        options: {count: {type: 'num', default: 1, help: 'How many'}},
+        positionals: [{name: 'env', help: 'Where'}],
+    }");
+
+    assert_eq!(parse_args(&spec, &["-h"]), Err(args::ArgsError::Help("\
+Deploys the app
+
+USAGE:
+    deploy [FLAGS] [OPTIONS] <env>
+
+FLAGS:
+    -h, --help       Prints help information
+    -v, --verbose    Print more
+
+OPTIONS:
+        --count <num>    How many [default: 1]
+
+ARGS:
+    <env>    Where".to_owned())));
+}
@@ -1,16 +1,20 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 
 use itertools::Itertools;
 use serde_json::{Map as JSONMap, Number as JSONNumber, Value as JSONValue};
 
+use crate::interp::error::{ErrorKind, RuntimeError};
 use crate::interp::func::Func;
+use crate::interp::range::Range;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub enum Value {
     Nil,
+    Int(i64),
     Num(f64),
     String(String),
     Bool(bool),
@@ -18,7 +22,7 @@ pub enum Value {
     Vec(Rc<RefCell<Vec<Value>>>),
     Dict(Rc<RefCell<HashMap<String, Value>>>),
 
-    Range(usize, usize),
+    Range(Range),
 
     Func(Func),
 }
@@ -27,6 +31,7 @@ impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Nil => write!(f, "nil"),
+            Value::Int(int) => write!(f, "{}", int),
             Value::Num(num) => write!(f, "{}", num),
             Value::String(string) => write!(f, "{}", string),
             Value::Bool(bool) => write!(f, "{}", bool),
@@ -39,41 +44,92 @@ impl Display for Value {
                 write!(f, "{{{}}}", dict.iter().map(|(k, v)| format!("{}: {}", k, v.to_string_quoted())).join(", "))
             }
             Value::Func(func) => write!(f, "{:?}", func),
-            Value::Range(l, r) => write!(f, "{}..{}", l, r),
+            Value::Range(range) => write!(f, "{}", range),
+        }
+    }
+}
+
+// Ints and floats are equal when they're the same number
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Int(lhs), Value::Int(rhs)) => lhs == rhs,
+            (Value::Num(lhs), Value::Num(rhs)) => lhs == rhs,
+            (Value::Int(int), Value::Num(num)) | (Value::Num(num), Value::Int(int)) => *int as f64 == *num,
+            (Value::String(lhs), Value::String(rhs)) => lhs == rhs,
+            (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+            (Value::Vec(lhs), Value::Vec(rhs)) => lhs == rhs,
+            (Value::Dict(lhs), Value::Dict(rhs)) => lhs == rhs,
+            (Value::Range(lhs), Value::Range(rhs)) => lhs == rhs,
+            (Value::Func(lhs), Value::Func(rhs)) => lhs == rhs,
+            _ => false,
         }
     }
 }
 
 impl Value {
     pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Int(_) => "int",
+            Value::Num(_) => "float",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Vec(_) => "vec",
+            Value::Dict(_) => "dict",
+            Value::Range(_) => "range",
+            Value::Func(_) => "func",
+        }
+    }
+
+    /// The value as an int if it's one or a float without a fractional part.
+    pub fn as_int(&self) -> Option<i64> {
         match self {
-            Value::Nil => false,
-            Value::Bool(false) => false,
-            _ => true,
+            Value::Int(int) => Some(*int),
+            // Floats past the range of ints saturate when cast, so they're left out
+            Value::Num(num) if num.trunc() == *num && num.abs() < i64::MAX as f64 => Some(*num as i64),
+            _ => None,
         }
     }
 
+    /// Parses an int, or else a finite float.
+    pub fn parse_num(string: &str) -> Option<Value> {
+        string.parse().map(Value::Int).ok()
+            .or_else(|| string.parse().ok().filter(|num: &f64| num.is_finite()).map(Value::Num))
+    }
+
     pub fn to_string_quoted(&self) -> String {
         if !matches!(self, Value::String(..)) {
             self.to_string()
         } else {
-            format!("\'{}\'", self.to_string())
+            format!("\'{}\'", self)
         }
     }
 }
 
-impl From<Value> for JSONValue {
-    fn from(val: Value) -> Self {
-        match val {
+impl TryFrom<Value> for JSONValue {
+    type Error = RuntimeError;
+
+    fn try_from(val: Value) -> Result<Self, Self::Error> {
+        Ok(match val {
             Value::Nil => JSONValue::Null,
-            Value::Num(num) => JSONValue::Number(JSONNumber::from_f64(num).unwrap()),
+            Value::Int(int) => JSONValue::Number(int.into()),
+            Value::Num(num) => match JSONNumber::from_f64(num) {
+                Some(num) => JSONValue::Number(num),
+                None => return Err(RuntimeError::new(ErrorKind::Value, format!("can't serialize {} to json", num))),
+            },
             Value::String(str) => JSONValue::String(str),
             Value::Bool(bool) => JSONValue::Bool(bool),
             Value::Vec(vec) => {
                 let mut json_vec = Vec::new();
 
                 for val in RefCell::borrow(&vec).iter() {
-                    json_vec.push(val.clone().into());
+                    json_vec.push(val.clone().try_into()?);
                 }
 
                 JSONValue::Array(json_vec)
@@ -82,16 +138,19 @@ impl From<Value> for JSONValue {
                 let mut json_map = JSONMap::new();
 
                 for (k, v) in RefCell::borrow(&map).iter() {
-                    if matches!(v, Value::Func(_) | Value::Range(_, _)) {
+                    if matches!(v, Value::Func(_) | Value::Range(_)) {
                         continue;
                     }
-                    json_map.insert(k.clone(), v.clone().into());
+                    json_map.insert(k.clone(), v.clone().try_into()?);
                 }
 
                 JSONValue::Object(json_map)
             }
-            Value::Func(_) | Value::Range(_, _) => panic!("unserializable value")
-        }
+            val @ (Value::Func(_) | Value::Range(_)) => return Err(RuntimeError::new(
+                ErrorKind::Type,
+                format!("can't serialize {} to json", val.type_name()),
+            )),
+        })
     }
 }
 
@@ -99,7 +158,10 @@ impl From<JSONValue> for Value {
     fn from(json_val: JSONValue) -> Self {
         match json_val {
             JSONValue::Null => Value::Nil,
-            JSONValue::Number(json_num) => Value::Num(json_num.as_f64().unwrap()),
+            JSONValue::Number(json_num) => match json_num.as_i64() {
+                Some(int) => Value::Int(int),
+                None => Value::Num(json_num.as_f64().unwrap()),
+            },
             JSONValue::String(json_str) => Value::String(json_str),
             JSONValue::Bool(json_bool) => Value::Bool(json_bool),
             JSONValue::Array(json_vec) => {
@@ -1,7 +1,11 @@
+use std::rc::Rc;
+
 pub use peek::PeekableLexer as Lexer;
 use raw::RawLexer;
 use record::RecordingLexer;
 
+use crate::source::Source;
+
 mod peek;
 mod record;
 mod raw;
@@ -9,7 +13,7 @@ mod raw;
 #[cfg(test)]
 mod test;
 
-pub fn new(source: String) -> Lexer {
+pub fn new(source: Rc<Source>) -> Lexer {
     Lexer::new(
         RecordingLexer::new(
             RawLexer::new(source)
@@ -1,4 +1,7 @@
-use crate::token::{Token, TokenKind};
+use std::cell::Ref;
+
+use crate::source::Span;
+use crate::token::{Comment, Token, TokenKind};
 
 use super::record::RecordingLexer;
 
@@ -8,6 +11,9 @@ pub struct PeekableLexer {
     peeked: Option<Token>,
 
     pub is_new_line: bool,
+
+    // Span of the last token returned by `next`, used to locate errors when the next token is missing
+    pub last_span: Span,
 }
 
 impl PeekableLexer {
@@ -18,9 +24,16 @@ impl PeekableLexer {
             peeked: None,
 
             is_new_line: true,
+
+            last_span: Span::default(),
         }
     }
 
+    /// Comments met so far.
+    pub fn comments(&self) -> Ref<'_, Vec<Comment>> {
+        self.wrapped.comments()
+    }
+
     pub fn peek(&mut self) -> Option<&Token> {
         if self.peeked.is_none() {
             self.peeked = self.wrapped.next();
@@ -59,6 +72,11 @@ impl Iterator for PeekableLexer {
         };
 
         self.is_new_line = self.wrapped.is_new_line;
+
+        if let Some(token) = &token {
+            self.last_span = token.span.clone();
+        }
+
         token
     }
 }
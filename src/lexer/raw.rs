@@ -1,34 +1,72 @@
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+
 use itertools::Itertools;
 
-use crate::token::{Token, TokenKind};
+use crate::source::{Source, Span};
+use crate::token::{Comment, Token, TokenKind};
 
 pub struct RawLexer {
     source: Vec<char>,
     cursor: usize,
 
+    file: Rc<Source>,
+    // Byte offset of every char (plus one past the end) and index of the first char of every line
+    byte_offsets: Rc<Vec<usize>>,
+    line_starts: Rc<Vec<usize>>,
+
     interp_count: u8,
     braces_count: u8,
 
     buffer: Vec<Token>,
 
+    // Shared with the lexers of interpolated expressions
+    comments: Rc<RefCell<Vec<Comment>>>,
+
     pub is_new_line: bool,
 }
 
 impl RawLexer {
-    pub fn new(source: String) -> RawLexer {
+    pub fn new(file: Rc<Source>) -> RawLexer {
+        let source: Vec<char> = file.text.chars().collect();
+
+        let mut byte_offsets = Vec::with_capacity(source.len() + 1);
+        let mut line_starts = vec![0];
+
+        let mut offset = 0;
+        for (i, c) in source.iter().enumerate() {
+            byte_offsets.push(offset);
+            offset += c.len_utf8();
+
+            if *c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        byte_offsets.push(offset);
+
         RawLexer {
-            source: source.chars().collect(),
+            source,
             cursor: 0,
 
+            file,
+            byte_offsets: Rc::new(byte_offsets),
+            line_starts: Rc::new(line_starts),
+
             interp_count: 0,
             braces_count: 0,
 
             buffer: Vec::new(),
 
+            comments: Rc::new(RefCell::new(Vec::new())),
+
             is_new_line: true,
         }
     }
 
+    pub fn comments(&self) -> Ref<'_, Vec<Comment>> {
+        RefCell::borrow(&self.comments)
+    }
+
     fn char_at(&self, offset: usize) -> Option<char> {
         if self.cursor + offset >= self.source.len() {
             return None;
@@ -38,7 +76,22 @@ impl RawLexer {
     }
 
     fn make_lexeme(&self, from: usize, to: usize) -> String {
-        (&self.source[from..to]).iter().collect()
+        self.source[from..to].iter().collect()
+    }
+
+    fn make_span(&self, from: usize, to: usize) -> Span {
+        let line = match self.line_starts.binary_search(&from) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+
+        Span {
+            source: Rc::clone(&self.file),
+            start: self.byte_offsets[from],
+            end: self.byte_offsets[to],
+            line: line + 1,
+            col: from - self.line_starts[line] + 1,
+        }
     }
 
     fn scan_symbol(&mut self) -> Token {
@@ -61,11 +114,8 @@ impl RawLexer {
 
             ' ' | '\t' => {
                 let mut length = 1;
-                loop {
-                    match self.char_at(length) {
-                        Some(' ') | Some('\t') => length += 1,
-                        _ => break,
-                    }
+                while let Some(' ') | Some('\t') = self.char_at(length) {
+                    length += 1;
                 }
                 (TokenKind::Space, length)
             }
@@ -83,19 +133,18 @@ impl RawLexer {
                 (TokenKind::LeftBrace, 1)
             }
             '}' => {
-                self.braces_count -= 1;
+                // Unbalanced braces are reported by the parser
+                self.braces_count = self.braces_count.saturating_sub(1);
                 (TokenKind::RightBrace, 1)
             }
 
             // Chars that may only appear by themselves or followed by an equals sign
-            '!' | '=' | '/' | '^' | '%' | '<' => {
+            '!' | '=' | '^' | '%' => {
                 let (kind, equal_kind) = match self.char_at(0).unwrap() {
                     '!' => (TokenKind::Bang, TokenKind::BangEqual),
                     '=' => (TokenKind::Equal, TokenKind::EqualEqual),
-                    '/' => (TokenKind::Slash, TokenKind::SlashEqual),
                     '^' => (TokenKind::Caret, TokenKind::CaretEqual),
                     '%' => (TokenKind::Perc, TokenKind::PercEqual),
-                    '<' => (TokenKind::Less, TokenKind::LessEqual),
                     _ => unreachable!(),
                 };
 
@@ -106,9 +155,26 @@ impl RawLexer {
                 }
             }
 
-            '>' => match self.char_at(1) {
-                Some('>') => (TokenKind::GreatGreat, 2),
-                Some('=') => (TokenKind::GreatEqual, 2),
+            // Chars that may also be doubled, with or without an equals sign after them
+            '/' | '<' => {
+                let (kind, equal_kind, double_kind, double_equal_kind) = match self.char_at(0).unwrap() {
+                    '/' => (TokenKind::Slash, TokenKind::SlashEqual, TokenKind::SlashSlash, TokenKind::SlashSlashEqual),
+                    '<' => (TokenKind::Less, TokenKind::LessEqual, TokenKind::LessLess, TokenKind::LessLessEqual),
+                    _ => unreachable!(),
+                };
+
+                match (self.char_at(1), self.char_at(2)) {
+                    (Some('='), _) => (equal_kind, 2),
+                    (Some(c), Some('=')) if c == self.char_at(0).unwrap() => (double_equal_kind, 3),
+                    (Some(c), _) if c == self.char_at(0).unwrap() => (double_kind, 2),
+                    _ => (kind, 1),
+                }
+            }
+
+            '>' => match (self.char_at(1), self.char_at(2)) {
+                (Some('>'), Some('=')) => (TokenKind::GreatGreatEqual, 3),
+                (Some('>'), _) => (TokenKind::GreatGreat, 2),
+                (Some('='), _) => (TokenKind::GreatEqual, 2),
                 _ => (TokenKind::Great, 1)
             }
 
@@ -123,6 +189,10 @@ impl RawLexer {
 
             '*' => match self.char_at(1) {
                 Some('=') => (TokenKind::StarEqual, 2),
+                Some('*') => match self.char_at(2) {
+                    Some('=') => (TokenKind::StarStarEqual, 3),
+                    _ => (TokenKind::StarStar, 2),
+                },
                 Some('>') => match self.char_at(2) {
                     Some('>') => (TokenKind::StarGreatGreat, 3),
                     _ => (TokenKind::StarGreat, 2)
@@ -133,6 +203,7 @@ impl RawLexer {
 
             '|' => match self.char_at(1) {
                 Some('|') => (TokenKind::PipePipe, 2),
+                Some('=') => (TokenKind::PipeEqual, 2),
                 _ => (TokenKind::Pipe, 1),
             },
 
@@ -143,43 +214,101 @@ impl RawLexer {
                     _ => (TokenKind::AmperGreat, 2),
                 },
                 Some('|') => (TokenKind::AmperPipe, 2),
-                _ => panic!("unexpected character"),
+                Some('=') => (TokenKind::AmperEqual, 2),
+                _ => (TokenKind::Amper, 1),
             },
 
             c => (TokenKind::UnknownChar(c), 1),
         };
 
         let lexeme = self.make_lexeme(self.cursor, self.cursor + length);
+        let span = self.make_span(self.cursor, self.cursor + length);
 
         self.cursor += length;
 
         Token {
             lexeme,
             kind,
+            span,
         }
     }
 
+    /// Scans an int, as in `42`, `1_000`, `0xff`, `0o755` or `0b1010`, or a float, as in `3.25`, `.5` or `1e-3`.
     fn scan_number(&mut self) -> Token {
         let start = self.cursor;
 
-        while self.cursor < self.source.len() && self.source[self.cursor].is_ascii_digit() {
-            self.cursor += 1;
+        let radix = match (self.char_at(0), self.char_at(1)) {
+            (Some('0'), Some('x' | 'X')) => 16,
+            (Some('0'), Some('o' | 'O')) => 8,
+            (Some('0'), Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+
+        // A prefix that isn't followed by a digit is left to be scanned as a word, as in `0x`
+        if radix != 10 && matches!(self.char_at(2), Some(c) if c.is_digit(radix)) {
+            self.cursor += 2;
+            self.consume_digits(radix);
+
+            let digits = self.make_lexeme(start + 2, self.cursor).replace('_', "");
+            let kind = match i64::from_str_radix(&digits, radix) {
+                Ok(int) => TokenKind::Int(int),
+                Err(_) => TokenKind::LargeInt,
+            };
+
+            return self.make_number(start, kind);
         }
 
-        if self.cursor < self.source.len() && self.source[self.cursor] == '.' && self.source[self.cursor+1].is_ascii_digit() {
+        self.consume_digits(10);
+
+        let mut is_float = false;
+
+        if matches!(self.char_at(0), Some('.')) && matches!(self.char_at(1), Some(c) if c.is_ascii_digit()) {
             self.cursor += 1;
+            self.consume_digits(10);
+            is_float = true;
+        }
 
-            while self.cursor < self.source.len() && self.source[self.cursor].is_ascii_digit() {
-                self.cursor += 1;
-            }
+        let exponent_len = match (self.char_at(1), self.char_at(2)) {
+            (Some(c), _) if c.is_ascii_digit() => 1,
+            (Some('+' | '-'), Some(c)) if c.is_ascii_digit() => 2,
+            _ => 0,
+        };
+        if matches!(self.char_at(0), Some('e' | 'E')) && exponent_len > 0 {
+            self.cursor += exponent_len;
+            self.consume_digits(10);
+            is_float = true;
         }
 
-        let lexeme = self.make_lexeme(start, self.cursor);
-        let value = lexeme.parse().expect("could not parse number literal");
+        let digits = self.make_lexeme(start, self.cursor).replace('_', "");
+        let kind = if is_float {
+            // Only digits, at most one dot and an exponent have been consumed so this can't fail
+            TokenKind::Num(digits.parse().unwrap())
+        } else {
+            match digits.parse() {
+                Ok(int) => TokenKind::Int(int),
+                Err(_) => TokenKind::LargeInt,
+            }
+        };
+
+        self.make_number(start, kind)
+    }
 
+    /// Consumes digits in `radix`, which may be separated by underscores as in `1_000`.
+    fn consume_digits(&mut self, radix: u32) {
+        loop {
+            match (self.char_at(0), self.char_at(1)) {
+                (Some(c), _) if c.is_digit(radix) => self.cursor += 1,
+                (Some('_'), Some(c)) if c.is_digit(radix) => self.cursor += 2,
+                _ => break,
+            }
+        }
+    }
+
+    fn make_number(&self, start: usize, kind: TokenKind) -> Token {
         Token {
-            lexeme,
-            kind: TokenKind::Num(value),
+            lexeme: self.make_lexeme(start, self.cursor),
+            kind,
+            span: self.make_span(start, self.cursor),
         }
     }
 
@@ -201,23 +330,31 @@ impl RawLexer {
             "continue" => Some(TokenKind::Continue),
             "let" => Some(TokenKind::Let),
             "exp" => Some(TokenKind::Exp),
+            "try" => Some(TokenKind::Try),
+            "catch" => Some(TokenKind::Catch),
+            "finally" => Some(TokenKind::Finally),
+            "throw" => Some(TokenKind::Throw),
+            "match" => Some(TokenKind::Match),
             "true" => Some(TokenKind::True),
             "false" => Some(TokenKind::False),
             "nil" => Some(TokenKind::Nil),
             _ => None,
         };
 
+        let span = self.make_span(self.cursor, self.cursor + word.len());
         self.cursor += word.len();
 
         if let Some(kind) = kw_kind {
             Token {
                 lexeme: word.clone(),
                 kind,
+                span,
             }
         } else {
             Token {
                 lexeme: word.clone(),
                 kind: TokenKind::Identifier(word.clone()),
+                span,
             }
         }
     }
@@ -244,7 +381,8 @@ impl RawLexer {
             let ch = if let Some(ch) = self.char_at(0) {
                 ch
             } else {
-                panic!("unterminated string");
+                tokens.push(self.make_error(lexeme_start, "unterminated string"));
+                break;
             };
 
             self.cursor += 1;
@@ -256,6 +394,7 @@ impl RawLexer {
                         value: literal_piece.clone(),
                         does_interp: false,
                     },
+                    span: self.make_span(lexeme_start, self.cursor),
                 });
                 break;
             }
@@ -267,17 +406,24 @@ impl RawLexer {
                         value: literal_piece.clone(),
                         does_interp: true,
                     },
+                    span: self.make_span(lexeme_start, self.cursor),
                 });
 
                 let lexer = &mut RawLexer {
                     source: self.source.clone(),
                     cursor: self.cursor,
 
+                    file: Rc::clone(&self.file),
+                    byte_offsets: Rc::clone(&self.byte_offsets),
+                    line_starts: Rc::clone(&self.line_starts),
+
                     interp_count: self.interp_count + 1,
                     braces_count: 0,
 
                     buffer: Vec::new(),
 
+                    comments: Rc::clone(&self.comments),
+
                     is_new_line: true,
                 };
 
@@ -285,9 +431,10 @@ impl RawLexer {
 
                 self.cursor = lexer.cursor;
 
-                match self.char_at(0) {
-                    Some('}') => (),
-                    _ => panic!("expected closing brace at end of interpolated expression")
+                if !matches!(self.char_at(0), Some('}')) {
+                    // The interpolated expression ran until the end of the source
+                    tokens.push(self.make_error(lexeme_start, "unterminated string"));
+                    break;
                 }
 
                 lexeme_start = self.cursor;
@@ -311,7 +458,10 @@ impl RawLexer {
                         literal_piece.push('\\');
                         c
                     }
-                    None => panic!("unterminated string"),
+                    None => {
+                        tokens.push(self.make_error(lexeme_start, "unterminated string"));
+                        break;
+                    }
                 };
 
                 literal_piece.push(ch);
@@ -326,11 +476,27 @@ impl RawLexer {
         first
     }
 
+    fn make_error(&self, from: usize, message: &str) -> Token {
+        Token {
+            lexeme: self.make_lexeme(from, self.cursor),
+            kind: TokenKind::Error(message.to_string()),
+            span: self.make_span(from, self.cursor),
+        }
+    }
+
     fn consume_comment(&mut self) {
+        let start = self.cursor;
+
         self.cursor += 1;
         while self.cursor < self.source.len() && !matches!(self.char_at(0), Some('\n')) {
             self.cursor += 1;
         }
+
+        self.comments.borrow_mut().push(Comment {
+            text: self.make_lexeme(start + 1, self.cursor),
+            span: self.make_span(start, self.cursor),
+            is_own_line: self.is_new_line,
+        });
     }
 }
 
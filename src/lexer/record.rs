@@ -1,4 +1,6 @@
-use crate::token::Token;
+use std::cell::Ref;
+
+use crate::token::{Comment, Token};
 
 use super::raw::RawLexer;
 
@@ -32,6 +34,10 @@ impl RecordingLexer {
         self.is_recording = true;
     }
 
+    pub fn comments(&self) -> Ref<'_, Vec<Comment>> {
+        self.wrapped.comments()
+    }
+
     pub fn stop_recording(&mut self, playback: bool) {
         self.is_recording = false;
         self.is_playing_back = playback;
@@ -43,7 +49,7 @@ impl Iterator for RecordingLexer {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.is_playing_back {
-            if self.record.len() > 0 {
+            if !self.record.is_empty() {
                 let (token, is_new_line) = self.record.remove(0);
                 self.is_new_line = is_new_line;
                 return Some(token);
@@ -1,14 +1,20 @@
+use crate::source::{Source, Span};
 use crate::token::{Token, TokenKind};
 
 use super::*;
 
 fn make_lexer(source: &str) -> Lexer {
-    new(source.to_owned())
+    new(Source::new(String::new(), source.to_owned()))
+}
+
+// Spans are left out of the comparisons, the tests about them look at them directly
+fn without_span(token: Token) -> Token {
+    Token { span: Span::default(), ..token }
 }
 
 fn scan(source: &str) -> Vec<Token> {
     let lexer = make_lexer(source);
-    lexer.collect()
+    lexer.map(without_span).collect()
 }
 
 #[test]
@@ -22,6 +28,7 @@ fn scans_spaces() {
         Token {
             kind: TokenKind::Space,
             lexeme: "   ".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -32,38 +39,47 @@ fn scans_keywords() {
         Token {
             kind: TokenKind::Import,
             lexeme: "import".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Space,
             lexeme: " ".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::While,
             lexeme: "while".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Space,
             lexeme: " ".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::For,
             lexeme: "for".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Space,
             lexeme: " ".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Return,
             lexeme: "return".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Space,
             lexeme: " ".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Continue,
             lexeme: "continue".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -74,6 +90,7 @@ fn unexpected_symbol() {
         Token {
             kind: TokenKind::UnknownChar('~'),
             lexeme: "~".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -84,6 +101,7 @@ fn scans_identifiers() {
         Token {
             kind: TokenKind::Identifier("whilee".to_owned()),
             lexeme: "whilee".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -97,6 +115,7 @@ fn scans_simple_string_literal() {
                 does_interp: false,
             },
             lexeme: "\"hello world\"".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -110,36 +129,71 @@ fn scans_string_literal_with_escape_chars() {
                 does_interp: false,
             },
             lexeme: "\"\\n\\t\\r\\\\\"".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
 
 #[test]
 fn scans_number_literals() {
-    assert_eq!(scan("12 3.14 .5"), vec![
+    assert_eq!(scan("12 3.25 .5"), vec![
         Token {
-            kind: TokenKind::Num(12.0),
+            kind: TokenKind::Int(12),
             lexeme: "12".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Space,
             lexeme: " ".to_owned(),
+            span: Span::default(),
         },
         Token {
-            kind: TokenKind::Num(3.14),
-            lexeme: "3.14".to_owned(),
+            kind: TokenKind::Num(3.25),
+            lexeme: "3.25".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Space,
             lexeme: " ".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Num(0.5),
             lexeme: ".5".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
 
+#[test]
+fn scans_radix_and_exponent_literals() {
+    let kinds = |source| scan(source).into_iter()
+        .filter(|token| token.kind != TokenKind::Space)
+        .map(|token| token.kind)
+        .collect::<Vec<TokenKind>>();
+
+    assert_eq!(kinds("0xff 0o755 0b1010 1_000 0xFF_FF"), vec![
+        TokenKind::Int(255),
+        TokenKind::Int(493),
+        TokenKind::Int(10),
+        TokenKind::Int(1000),
+        TokenKind::Int(65535),
+    ]);
+    assert_eq!(kinds("1e3 2.5e-2 1E+2"), vec![TokenKind::Num(1000.0), TokenKind::Num(0.025), TokenKind::Num(100.0)]);
+
+    // Prefixes and exponents without digits after them aren't part of the number
+    assert_eq!(kinds("0x 2e 1_"), vec![
+        TokenKind::Int(0),
+        TokenKind::Identifier("x".to_owned()),
+        TokenKind::Int(2),
+        TokenKind::Identifier("e".to_owned()),
+        TokenKind::Int(1),
+        TokenKind::Identifier("_".to_owned()),
+    ]);
+
+    assert_eq!(kinds("9223372036854775808"), vec![TokenKind::LargeInt]);
+}
+
 #[test]
 fn scans_interpolated_string() {
     assert_eq!(scan("\"a{for}b\""), vec![
@@ -149,10 +203,12 @@ fn scans_interpolated_string() {
                 does_interp: true,
             },
             lexeme: "\"a{".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::For,
             lexeme: "for".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::String {
@@ -160,6 +216,7 @@ fn scans_interpolated_string() {
                 does_interp: false,
             },
             lexeme: "}b\"".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -173,10 +230,12 @@ fn scans_interpolated_string_trimmed() {
                 does_interp: true,
             },
             lexeme: "\"{".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::For,
             lexeme: "for".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::String {
@@ -184,6 +243,7 @@ fn scans_interpolated_string_trimmed() {
                 does_interp: false,
             },
             lexeme: "}\"".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -197,6 +257,7 @@ fn scans_interpolated_string_empty() {
                 does_interp: true,
             },
             lexeme: "\"a{".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::String {
@@ -204,6 +265,7 @@ fn scans_interpolated_string_empty() {
                 does_interp: false,
             },
             lexeme: "}b\"".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -217,6 +279,7 @@ fn scans_interpolated_string_nested() {
                 does_interp: true,
             },
             lexeme: "\"a{".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::String {
@@ -224,10 +287,12 @@ fn scans_interpolated_string_nested() {
                 does_interp: true,
             },
             lexeme: "\"b{".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::For,
             lexeme: "for".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::String {
@@ -235,6 +300,7 @@ fn scans_interpolated_string_nested() {
                 does_interp: false,
             },
             lexeme: "}b\"".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::String {
@@ -242,6 +308,7 @@ fn scans_interpolated_string_nested() {
                 does_interp: false,
             },
             lexeme: "}a\"".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -255,26 +322,32 @@ fn scans_interpolated_string_dict() {
                 does_interp: true,
             },
             lexeme: "\"a{".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::LeftBrace,
             lexeme: "{".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Identifier("x".to_owned()),
             lexeme: "x".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::Colon,
             lexeme: ":".to_owned(),
+            span: Span::default(),
         },
         Token {
-            kind: TokenKind::Num(1.0),
+            kind: TokenKind::Int(1),
             lexeme: "1".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::RightBrace,
             lexeme: "}".to_owned(),
+            span: Span::default(),
         },
         Token {
             kind: TokenKind::String {
@@ -282,6 +355,7 @@ fn scans_interpolated_string_dict() {
                 does_interp: false,
             },
             lexeme: "}b\"".to_owned(),
+            span: Span::default(),
         }
     ]);
 }
@@ -304,10 +378,10 @@ fn scans_lexemes() {
 fn peeks() {
     let mut lexer = make_lexer("+-");
 
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
@@ -316,14 +390,14 @@ fn records() {
 
     lexer.start_recording();
 
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
 
     lexer.stop_recording(true);
 
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
@@ -332,33 +406,33 @@ fn records_no_playback() {
 
     lexer.start_recording();
 
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
 
     lexer.stop_recording(false);
 
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Caret, lexeme: "^".to_owned() }));
-    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Caret, lexeme: "^".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
 fn newline_while_peeking() {
     let mut lexer = make_lexer("+\n-");
 
-    assert_eq!(lexer.is_new_line, true);
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.is_new_line, true);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.is_new_line, false);
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() }));
-    assert_eq!(lexer.is_new_line, false);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() }));
-    assert_eq!(lexer.is_new_line, true);
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.is_new_line, true);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.is_new_line, false);
-    assert_eq!(lexer.next(), None);
+    assert!(lexer.is_new_line);
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert!(lexer.is_new_line);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert!(!lexer.is_new_line);
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() }));
+    assert!(!lexer.is_new_line);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() }));
+    assert!(lexer.is_new_line);
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert!(lexer.is_new_line);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert!(!lexer.is_new_line);
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
@@ -367,34 +441,34 @@ fn newline_while_recording() {
 
     lexer.start_recording();
 
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() }));
 
     lexer.stop_recording(true);
 
-    assert_eq!(lexer.is_new_line, true);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.is_new_line, false);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.is_new_line, false);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() }));
-    assert_eq!(lexer.is_new_line, true);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.is_new_line, false);
-    assert_eq!(lexer.next(), None);
+    assert!(lexer.is_new_line);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert!(!lexer.is_new_line);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert!(!lexer.is_new_line);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() }));
+    assert!(lexer.is_new_line);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert!(!lexer.is_new_line);
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
 fn records_with_peeked() {
     let mut lexer = make_lexer("+");
 
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
     lexer.start_recording();
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
     lexer.stop_recording(true);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
@@ -402,11 +476,11 @@ fn peeks_while_recording() {
     let mut lexer = make_lexer("+-");
 
     lexer.start_recording();
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
     lexer.stop_recording(true);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
@@ -414,13 +488,13 @@ fn peeks_while_recording_and_consumes() {
     let mut lexer = make_lexer("+-");
 
     lexer.start_recording();
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
     lexer.stop_recording(true);
 
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
@@ -428,13 +502,13 @@ fn peeks_after_record() {
     let mut lexer = make_lexer("+-");
 
     lexer.start_recording();
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
     lexer.stop_recording(true);
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
 
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
@@ -442,12 +516,12 @@ fn peeks_after_record_without_playback() {
     let mut lexer = make_lexer("+-");
 
     lexer.start_recording();
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
     lexer.stop_recording(false);
 
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned() }));
-    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Minus, lexeme: "-".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
@@ -455,21 +529,21 @@ fn peeks_while_recording_nothing() {
     let mut lexer = make_lexer("+");
 
     lexer.start_recording();
-    assert_eq!(lexer.peek(), Some(&Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
+    assert_eq!(lexer.peek().cloned().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
     lexer.stop_recording(true);
 
-    assert_eq!(lexer.next(), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned() }));
-    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.next().map(without_span), Some(Token { kind: TokenKind::Plus, lexeme: "+".to_owned(), span: Span::default() }));
+    assert_eq!(lexer.next().map(without_span), None);
 }
 
 #[test]
 fn consumes_whitespace() {
     assert_eq!(scan(" \n \n   "), vec![
-        Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
-        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() },
-        Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
-        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() },
-        Token { kind: TokenKind::Space, lexeme: "   ".to_owned() },
+        Token { kind: TokenKind::Space, lexeme: " ".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Space, lexeme: " ".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Space, lexeme: "   ".to_owned(), span: Span::default() },
     ]);
 }
 
@@ -481,22 +555,22 @@ fn consumes_whitespace_recording() {
     lexer.by_ref().for_each(drop);
     lexer.stop_recording(true);
 
-    assert_eq!(lexer.collect::<Vec<Token>>(), vec![
-        Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
-        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() },
-        Token { kind: TokenKind::Space, lexeme: " ".to_owned() },
-        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() },
-        Token { kind: TokenKind::Space, lexeme: "   ".to_owned() },
+    assert_eq!(lexer.map(without_span).collect::<Vec<Token>>(), vec![
+        Token { kind: TokenKind::Space, lexeme: " ".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Space, lexeme: " ".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Space, lexeme: "   ".to_owned(), span: Span::default() },
     ]);
 }
 
 #[test]
 fn ignores_comments() {
     assert_eq!(scan("a# i am a comment\nb#another comment\n"), vec![
-        Token { kind: TokenKind::Identifier("a".to_string()), lexeme: "a".to_string() },
-        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() },
-        Token { kind: TokenKind::Identifier("b".to_string()), lexeme: "b".to_string() },
-        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned() },
+        Token { kind: TokenKind::Identifier("a".to_string()), lexeme: "a".to_string(), span: Span::default() },
+        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Identifier("b".to_string()), lexeme: "b".to_string(), span: Span::default() },
+        Token { kind: TokenKind::Newline, lexeme: "\n".to_owned(), span: Span::default() },
     ]);
 }
 
@@ -504,8 +578,68 @@ fn ignores_comments() {
 #[test]
 fn crlf_line_ending() {
     assert_eq!(scan("x\r\ny"), vec![
-        Token { kind: TokenKind::Identifier("x".to_string()), lexeme: "x".to_string() },
-        Token { kind: TokenKind::Newline, lexeme: "\r\n".to_owned() },
-        Token { kind: TokenKind::Identifier("y".to_string()), lexeme: "y".to_string() },
+        Token { kind: TokenKind::Identifier("x".to_string()), lexeme: "x".to_string(), span: Span::default() },
+        Token { kind: TokenKind::Newline, lexeme: "\r\n".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Identifier("y".to_string()), lexeme: "y".to_string(), span: Span::default() },
+    ]);
+}
+
+fn positions(source: &str) -> Vec<(usize, usize, usize, usize)> {
+    make_lexer(source)
+        .filter(|t| t.kind != TokenKind::Space)
+        .map(|t| (t.span.line, t.span.col, t.span.start, t.span.end))
+        .collect()
+}
+
+#[test]
+fn tracks_spans() {
+    assert_eq!(positions("let x\n  = 1"), vec![
+        (1, 1, 0, 3),
+        (1, 5, 4, 5),
+        (1, 6, 5, 6),
+        (2, 3, 8, 9),
+        (2, 5, 10, 11),
+    ]);
+}
+
+#[test]
+fn tracks_spans_in_interpolation() {
+    assert_eq!(positions("'a{x}b'\n\"{y}\""), vec![
+        (1, 1, 0, 3),
+        (1, 4, 3, 4),
+        (1, 5, 4, 7),
+        (1, 8, 7, 8),
+        (2, 1, 8, 10),
+        (2, 3, 10, 11),
+        (2, 4, 11, 13),
+    ]);
+}
+
+#[test]
+fn tracks_byte_offsets() {
+    assert_eq!(positions("'è' x"), vec![
+        (1, 1, 0, 4),
+        (1, 5, 5, 6),
+    ]);
+}
+
+#[test]
+fn unterminated_string() {
+    assert_eq!(scan("x 'abc"), vec![
+        Token { kind: TokenKind::Identifier("x".to_string()), lexeme: "x".to_string(), span: Span::default() },
+        Token { kind: TokenKind::Space, lexeme: " ".to_owned(), span: Span::default() },
+        Token { kind: TokenKind::Error("unterminated string".to_string()), lexeme: "'abc".to_owned(), span: Span::default() },
     ]);
 }
+
+#[test]
+fn keeps_comments() {
+    let mut lexer = make_lexer("# one\nx # two");
+    lexer.by_ref().for_each(drop);
+
+    let comments = lexer.comments().iter()
+        .map(|comment| (comment.text.clone(), comment.span.line, comment.is_own_line))
+        .collect::<Vec<(String, usize, bool)>>();
+
+    assert_eq!(comments, vec![(" one".to_owned(), 1, true), (" two".to_owned(), 2, false)]);
+}
@@ -1,21 +1,28 @@
-#![feature(test)]
-
 use std::env;
 use std::fs;
 use std::io;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use itertools::Itertools;
 
 use crate::lexer::new as new_lexer;
+use crate::source::{Source, Span};
 
+mod source;
 mod token;
 mod lexer;
 mod ast;
 mod parser;
 mod interp;
+mod check;
+mod resolver;
+mod repl;
+mod complete;
+mod task;
+mod watch;
 
 fn split_args() -> (Vec<String>, Vec<String>) {
     let args = env::args().collect_vec();
@@ -48,6 +55,15 @@ fn main() {
                 .help("Read script from stdin.")
                 .conflicts_with("path")
         )
+        .arg(
+            Arg::with_name("command")
+                .short("c")
+                .long("command")
+                .value_name("CODE")
+                .takes_value(true)
+                .help("Run the given code.")
+                .conflicts_with_all(&["path", "stdin"])
+        )
         .arg(
             Arg::with_name("fn")
                 .short("f")
@@ -55,40 +71,512 @@ fn main() {
                 .takes_value(true)
                 .help("Function to call.")
         )
+        .arg(
+            Arg::with_name("no-deps")
+                .long("no-deps")
+                .takes_value(false)
+                .help("Call the function without the ones it needs first.")
+                .requires("fn")
+        )
+        .arg(
+            Arg::with_name("no-fn-args")
+                .long("no-fn-args")
+                .takes_value(false)
+                .hidden(true)
+                .help("Leave the script arguments to `args` instead of binding them to the function, like for the ones it needs.")
+                .requires("fn")
+        )
+        .arg(
+            Arg::with_name("no-top-level")
+                .long("no-top-level")
+                .takes_value(false)
+                .hidden(true)
+                .help("Only define the functions and imports of the script before calling --fn, for --parallel.")
+                .requires("fn")
+        )
+        .arg(
+            Arg::with_name("parallel")
+                .short("j")
+                .long("parallel")
+                .takes_value(false)
+                .help("Call the functions needed by --fn in parallel when they don't need each other, in processes that don't share variables.")
+                .requires("fn")
+        )
+        .arg(
+            Arg::with_name("list")
+                .short("l")
+                .long("list")
+                .takes_value(false)
+                .help("List the functions that can be called with --fn.")
+                .conflicts_with("fn")
+        )
+        .arg(
+            Arg::with_name("no-search")
+                .long("no-search")
+                .takes_value(false)
+                .help("Only look for the Koifile in the current directory, not in its parents.")
+        )
+        .arg(
+            Arg::with_name("chdir")
+                .long("chdir")
+                .takes_value(false)
+                .help("Run from the directory of the script.")
+                .conflicts_with_all(&["stdin", "command"])
+        )
+        .arg(
+            Arg::with_name("watch")
+                .short("w")
+                .long("watch")
+                .value_name("PATTERN")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Run again whenever files matching the glob pattern change.")
+                .conflicts_with_all(&["stdin", "list"])
+        )
+        .arg(
+            Arg::with_name("debounce")
+                .long("debounce")
+                .value_name("MS")
+                .takes_value(true)
+                .default_value("200")
+                .help("How long files must stay unchanged before running again.")
+        )
+        .arg(
+            Arg::with_name("on-change")
+                .long("on-change")
+                .takes_value(true)
+                .possible_values(&["restart", "wait"])
+                .default_value("restart")
+                .help("Whether a run still going when files change is killed or finishes first.")
+        )
+        .arg(
+            Arg::with_name("trace")
+                .short("x")
+                .long("trace")
+                .takes_value(false)
+                .help("Print every command before it runs.")
+        )
+        .arg(
+            Arg::with_name("errexit")
+                .short("e")
+                .long("errexit")
+                .takes_value(false)
+                .help("Stop with an error when a command fails.")
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Parses a file and everything it imports without running them.")
+                .arg(
+                    Arg::with_name("path")
+                        .value_name("PATH")
+                        .index(1)
+                        .takes_value(true)
+                        .help("Path to source file.")
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .takes_value(false)
+                        .help("Print errors as JSON to stdout.")
+                )
+                .arg(
+                    Arg::with_name("no-search")
+                        .long("no-search")
+                        .takes_value(false)
+                        .help("Only look for the Koifile in the current directory, not in its parents.")
+                )
+                .arg(
+                    Arg::with_name("chdir")
+                        .long("chdir")
+                        .takes_value(false)
+                        .help("Check from the directory of the file.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("complete")
+                .about("Prints the ways to complete the code read from stdin, one per line.")
+                .arg(
+                    Arg::with_name("cursor")
+                        .long("cursor")
+                        .takes_value(true)
+                        .help("Byte offset of the cursor, defaults to the end of the code.")
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .takes_value(false)
+                        .help("Print the completions as JSON, with the offset where they start.")
+                )
+        )
         .get_matches_from(koi_args);
 
+    if let Some(check_matches) = matches.subcommand_matches("check") {
+        run_check(&matches, check_matches);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("complete") {
+        run_complete(matches);
+    }
+
+    let koifile = find_koifile(matches.is_present("no-search"));
+
+    // Without a script to run there's nothing to do but ask for one
+    let is_interactive = !matches.is_present("stdin")
+        && !matches.is_present("command")
+        && !matches.is_present("path")
+        && !matches.is_present("fn")
+        && !matches.is_present("list")
+        && !matches.is_present("watch");
+    if is_interactive && koifile.is_none() {
+        run_repl(&matches, script_args);
+    }
+
+    let mut script_path = None;
     let source = if matches.is_present("stdin") {
         let mut buffer = String::new();
         io::stdin().read_to_string(&mut buffer).unwrap();
-        buffer
+        Source::new("<stdin>".to_string(), buffer)
+    } else if let Some(code) = matches.value_of("command") {
+        Source::new("<command>".to_string(), code.to_string())
     } else {
-        fs::read_to_string(matches.value_of("path").unwrap_or("Koifile"))
-            .expect("couldn't read the source file (if you didn't provide a path to a .koi file, then it defaults to ./Kofile")
+        let path = match matches.value_of("path") {
+            Some(path) => PathBuf::from(path),
+            None => koifile.unwrap_or_else(|| {
+                if matches.is_present("no-search") {
+                    eprintln!("error: no Koifile in the current directory");
+                } else {
+                    eprintln!("error: no Koifile in the current directory or its parents");
+                }
+                process::exit(1);
+            }),
+        };
+
+        let text = fs::read_to_string(&path).unwrap_or_else(|err| {
+            eprintln!("error: couldn't read {}: {}", path.display(), err);
+            process::exit(1);
+        });
+
+        let source = Source::new(path.display().to_string(), text);
+        script_path = Some(fs::canonicalize(&path).expect("couldn't resolve the script path"));
+        source
     };
 
+    if matches.is_present("watch") {
+        run_watch(&matches, script_path.as_deref(), &script_args);
+    }
+
     let lexer = new_lexer(source);
 
     let mut parser = parser::Parser::new(lexer);
-    let prog = parser.parse();
+    let (prog, errors) = parser.parse();
+    if !errors.is_empty() {
+        eprint!("{}", errors.iter().join("\n"));
+        process::exit(1);
+    }
+
+    if matches.is_present("list") {
+        print_funcs(&prog);
+        process::exit(0);
+    }
 
     let mut interpreter = interp::Interpreter::new();
-    interpreter.set_args(script_args);
-    if let Some(path) = matches.value_of("path") {
-        let mut import_root = std::fs::canonicalize(PathBuf::from(path))
-            .expect("couldn't set import root");
-        import_root.pop();
-        interpreter.set_import_root(import_root);
+    interpreter.set_args(script_args.clone());
+    interpreter.set_errexit(matches.is_present("errexit"));
+    interpreter.set_trace(matches.is_present("trace"));
+    if let Some(dir) = script_path.as_deref().and_then(Path::parent) {
+        interpreter.set_import_root(dir.to_path_buf());
+
+        if matches.is_present("chdir") {
+            env::set_current_dir(dir).unwrap_or_else(|err| {
+                eprintln!("error: couldn't change directory to {}: {}", dir.display(), err);
+                process::exit(1);
+            });
+        }
+    }
+
+    let diagnostics = resolver::resolve(&prog, interpreter.global_names(), interpreter.import_root());
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    if diagnostics.iter().any(|diagnostic| diagnostic.level == resolver::Level::Error) {
+        process::exit(1);
+    }
+
+    // The process that started this one already ran the rest of the top level
+    let prog = if matches.is_present("no-top-level") { task::definitions(prog) } else { prog };
+
+    if let Err(err) = interpreter.run(prog) {
+        exit_with_error(&err);
     }
-    interpreter.run(prog);
 
     if let Some(f) = matches.value_of("fn") {
-        use ast::{Stmt, Expr};
+        let mut args = match interpreter.get(f) {
+            _ if matches.is_present("no-fn-args") => Default::default(),
+            // Functions without parameters can still get the arguments from `args`
+            interp::Value::Func(interp::Func::User { params, rest, .. }) if !params.is_empty() || rest.is_some() => {
+                task::bind_args(f, &params, rest.as_deref(), &script_args).unwrap_or_else(|err| {
+                    eprint!("{}", err);
+                    process::exit(1);
+                })
+            }
+            _ => Default::default(),
+        };
 
-        interpreter.run(vec![
-            Stmt::Expr(Expr::Call {
-                func: Box::new(Expr::Get(f.to_string())),
-                args: vec![],
+        let stages = if matches.is_present("no-deps") {
+            vec![vec![f.to_string()]]
+        } else {
+            task::plan(&interpreter, f).unwrap_or_else(|err| {
+                eprint!("{}", err);
+                process::exit(1);
             })
-        ]);
+        };
+
+        for stage in stages {
+            if matches.is_present("parallel") && stage.len() > 1 {
+                call_in_parallel(&matches, script_path.as_deref(), &stage, &script_args);
+                continue;
+            }
+
+            for name in stage {
+                let (args, named) = if name == f { std::mem::take(&mut args) } else { Default::default() };
+
+                if let Err(err) = call_fn(&mut interpreter, &name, args, named) {
+                    exit_with_error(&err);
+                }
+            }
+        }
     }
 }
+
+/// Prints the error that ended the script and exits with its status. The help asked for with `parseArgs` is the
+/// script's output, not an error, so it goes to stdout.
+fn exit_with_error(err: &interp::RuntimeError) -> ! {
+    if err.kind == interp::ErrorKind::Help {
+        print!("{}", err);
+    } else {
+        eprint!("{}", err);
+    }
+    process::exit(err.exit_status());
+}
+
+fn call_fn(
+    interpreter: &mut interp::Interpreter,
+    name: &str,
+    args: Vec<interp::Value>,
+    named: Vec<(String, interp::Value)>,
+) -> interp::RuntimeResult<()> {
+    use ast::{Stmt, Expr};
+
+    // Errors about the function itself (e.g. it doesn't exist) point at the name given on the command line
+    let span = Span {
+        source: Source::new("<command line>".to_string(), name.to_string()),
+        start: 0,
+        end: name.len(),
+        line: 1,
+        col: 1,
+    };
+
+    interpreter.run(vec![
+        Stmt::Expr(Expr::Call {
+            func: Box::new(Expr::Get(name.to_string(), span.clone())),
+            args: args.into_iter().map(Expr::Literal).collect(),
+            named: named.into_iter().map(|(name, val)| (name, Expr::Literal(val))).collect(),
+            span,
+        })
+    ])
+}
+
+/// Calls every function in `names` in its own koi process and exits if any of them fails. The processes only define the
+/// functions and imports of the script, so they don't see the variables of this one or each other's.
+fn call_in_parallel(matches: &ArgMatches, path: Option<&Path>, names: &[String], script_args: &[String]) {
+    let path = path.unwrap_or_else(|| {
+        eprintln!("error: --parallel needs the script to be in a file");
+        process::exit(1);
+    });
+
+    let exe = env::current_exe().expect("couldn't find the koi executable");
+    let flags = ["errexit", "trace"].iter().copied().filter(|flag| matches.is_present(flag)).collect::<Vec<&str>>();
+
+    let children = names.iter().map(|name| {
+        task::call_cmd(&exe, path, name, &flags, script_args).spawn().unwrap_or_else(|err| {
+            eprintln!("error: couldn't start koi to call {}: {}", name, err);
+            process::exit(1);
+        })
+    }).collect::<Vec<process::Child>>();
+
+    let mut code = 0;
+    for mut child in children {
+        match child.wait() {
+            Ok(status) if status.success() => (),
+            Ok(status) => code = status.code().unwrap_or(1),
+            Err(_) => code = 1,
+        }
+    }
+
+    if code != 0 {
+        process::exit(code);
+    }
+}
+
+/// Finds the Koifile to run when no path is given, like `make` looking in the current directory and, unless
+/// `only_cwd`, in its parents. Files in the current directory keep a relative path, for shorter error messages.
+fn find_koifile(only_cwd: bool) -> Option<PathBuf> {
+    if only_cwd {
+        return Some(PathBuf::from("Koifile")).filter(|path| path.is_file());
+    }
+
+    let cwd = env::current_dir().ok()?;
+    let path = task::find_koifile(&cwd)?;
+
+    Some(path.strip_prefix(&cwd).map(Path::to_path_buf).unwrap_or(path))
+}
+
+/// Prints the functions defined at the top level of `prog` with the comments above them.
+fn print_funcs(prog: &ast::Prog) {
+    for stmt in prog {
+        if let ast::Stmt::Func(interp::Func::User { name: Some(name), params, rest, doc, deps, .. }) = stmt {
+            let params = params.iter().map(ToString::to_string).chain(rest.iter().map(|rest| format!("...{}", rest))).join(", ");
+            if deps.is_empty() {
+                println!("{}({})", name, params);
+            } else {
+                println!("{}({}) needs {}", name, params, deps.join(", "));
+            }
+
+            for line in doc.iter().flat_map(|doc| doc.lines()) {
+                println!("    {}", line);
+            }
+        }
+    }
+}
+
+/// Runs the script in a new koi process every time the watched files change.
+fn run_watch(matches: &ArgMatches, path: Option<&Path>, script_args: &[String]) -> ! {
+    let patterns = matches.values_of("watch").unwrap().map(str::to_string).collect::<Vec<String>>();
+    if let Some(err) = patterns.iter().find_map(|pattern| glob::Pattern::new(pattern).err()) {
+        eprintln!("error: invalid watch pattern: {}", err);
+        process::exit(1);
+    }
+
+    let debounce = matches.value_of("debounce").unwrap().parse().unwrap_or_else(|_| {
+        eprintln!("error: --debounce must be a number of milliseconds");
+        process::exit(1);
+    });
+
+    let on_change = match matches.value_of("on-change") {
+        Some("wait") => watch::OnChange::Wait,
+        _ => watch::OnChange::Restart,
+    };
+
+    let mut cmd = process::Command::new(env::current_exe().expect("couldn't find the koi executable"));
+    match (path, matches.value_of("command")) {
+        (Some(path), _) => cmd.arg(path),
+        (None, Some(code)) => cmd.arg("--command").arg(code),
+        (None, None) => unreachable!(),
+    };
+    if let Some(f) = matches.value_of("fn") {
+        cmd.arg("--fn").arg(f);
+    }
+    for flag in &["no-deps", "parallel", "chdir", "errexit", "trace"] {
+        if matches.is_present(flag) {
+            cmd.arg(format!("--{}", flag));
+        }
+    }
+    cmd.arg("--").args(script_args);
+
+    if let Err(err) = watch::watch(&patterns, std::time::Duration::from_millis(debounce), on_change, &mut cmd) {
+        eprintln!("error: couldn't run koi: {}", err);
+    }
+    process::exit(1);
+}
+
+fn run_repl(matches: &ArgMatches, script_args: Vec<String>) -> ! {
+    let mut interpreter = interp::Interpreter::new();
+    interpreter.set_args(script_args);
+    interpreter.set_errexit(matches.is_present("errexit"));
+    interpreter.set_trace(matches.is_present("trace"));
+
+    repl::run(&mut interpreter);
+
+    process::exit(0);
+}
+
+fn run_complete(matches: &ArgMatches) -> ! {
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text).unwrap();
+
+    let cursor = match matches.value_of("cursor").map(str::parse::<usize>) {
+        None => text.len(),
+        Some(Ok(cursor)) if cursor <= text.len() && text.is_char_boundary(cursor) => cursor,
+        Some(_) => {
+            eprintln!("error: invalid cursor position");
+            process::exit(1);
+        }
+    };
+
+    let completions = complete::complete(&interp::Interpreter::new(), &text, cursor);
+
+    if matches.is_present("json") {
+        println!("{}", serde_json::json!({
+            "start": completions.start,
+            "candidates": completions.candidates,
+        }));
+    } else {
+        for candidate in completions.candidates {
+            println!("{}", candidate);
+        }
+    }
+
+    process::exit(0);
+}
+
+/// Checks the file given to the subcommand or, like a plain `koi`, the Koifile found from the current directory.
+/// `--no-search` and `--chdir` work the same whether they come before or after `check`.
+fn run_check(global: &ArgMatches, matches: &ArgMatches) -> ! {
+    let is_present = |name| global.is_present(name) || matches.is_present(name);
+
+    let mut path = match matches.value_of("path") {
+        Some(path) => PathBuf::from(path),
+        None => find_koifile(is_present("no-search")).unwrap_or_else(|| {
+            if is_present("no-search") {
+                eprintln!("error: no Koifile in the current directory");
+            } else {
+                eprintln!("error: no Koifile in the current directory or its parents");
+            }
+            process::exit(1);
+        }),
+    };
+
+    if is_present("chdir") {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            env::set_current_dir(dir).unwrap_or_else(|err| {
+                eprintln!("error: couldn't change directory to {}: {}", dir.display(), err);
+                process::exit(1);
+            });
+            path = PathBuf::from(path.file_name().expect("the checked path has no file name"));
+        }
+    }
+
+    let errors = match check::check(&path) {
+        Ok(errors) => errors,
+        Err(err) => {
+            eprintln!("error: couldn't read {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    };
+
+    if matches.is_present("json") {
+        let diagnostics = errors.iter().map(|err| serde_json::json!({
+            "file": err.span.source.name,
+            "line": err.span.line,
+            "column": err.span.col,
+            "message": err.message,
+        })).collect::<Vec<serde_json::Value>>();
+
+        println!("{}", serde_json::Value::Array(diagnostics));
+    } else {
+        eprint!("{}", errors.iter().join("\n"));
+    }
+
+    process::exit(if errors.is_empty() { 0 } else { 1 });
+}
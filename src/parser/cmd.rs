@@ -2,23 +2,23 @@ use crate::ast::{Cmd, CmdOp, Expr};
 use crate::interp::Value;
 use crate::token::{Token, TokenKind};
 
-use super::Parser;
+use super::{ParseError, ParseResult, Parser};
 
 impl Parser {
-    pub fn parse_cmd(&mut self, min_bp: u8) -> Cmd {
+    pub fn parse_cmd(&mut self, min_bp: u8) -> ParseResult<Cmd> {
         self.lexer.consume_whitespace(self.is_multiline);
 
         let mut lhs = if let Some(Token { kind: TokenKind::LeftParen, .. }) = self.lexer.peek() {
             self.lexer.next();
-            let cmd = self.parse_cmd(0);
+            let cmd = self.parse_cmd(0)?;
 
             if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::RightParen, .. })) {
-                panic!("expected right parenthesis");
+                return self.error("expected right parenthesis");
             }
 
             cmd
         } else {
-            self.parse_cmd_atom()
+            self.parse_cmd_atom()?
         };
 
         self.lexer.consume_whitespace(self.is_multiline);
@@ -35,7 +35,7 @@ impl Parser {
             }
 
             let op = self.lexer.next().unwrap().kind;
-            let rhs = self.parse_cmd(r_bp);
+            let rhs = self.parse_cmd(r_bp)?;
 
             lhs = Cmd::Op(
                 Box::new(lhs),
@@ -64,60 +64,69 @@ impl Parser {
             );
         }
 
-        lhs
+        Ok(lhs)
     }
 
-    fn parse_cmd_atom(&mut self) -> Cmd {
+    fn parse_cmd_atom(&mut self) -> ParseResult<Cmd> {
         let mut segments = Vec::new();
 
+        let span = match self.lexer.peek() {
+            Some(token) => token.span.clone(),
+            None => self.lexer.last_span.clone(),
+        };
+
         loop {
             let mut exprs = Vec::new();
 
             self.lexer.consume_whitespace(self.is_multiline);
 
             loop {
-                if self.lexer.peek().is_none() {
-                    break;
-                }
+                let is_atom_end = match self.lexer.peek() {
+                    Some(t) => t.is_cmd_op() || [TokenKind::Space, TokenKind::Newline, TokenKind::RightParen].contains(&t.kind),
+                    None => true,
+                };
 
-                if {
-                    let t = self.lexer.peek().unwrap();
-                    t.is_cmd_op() || [TokenKind::Space, TokenKind::Newline, TokenKind::RightParen].contains(&t.kind)
-                } {
+                if is_atom_end {
                     break;
                 }
 
                 let expr = match self.lexer.next().unwrap() {
-                    t @ Token { kind: TokenKind::String { .. }, .. } => self.continue_parse_string_expr(t),
+                    t @ Token { kind: TokenKind::String { .. }, .. } => self.continue_parse_string_expr(t)?,
                     Token { kind: TokenKind::LeftBrace, .. } => {
                         self.lexer.consume_whitespace(self.is_multiline);
-                        let expr = self.parse_expr(0);
+                        let expr = self.parse_expr(0)?;
                         self.lexer.consume_whitespace(self.is_multiline);
 
                         if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::RightBrace, .. })) {
-                            panic!("expected right brace");
+                            return self.error("expected right brace");
                         }
 
                         expr
                     }
+                    Token { kind: TokenKind::Error(message), span, .. } => {
+                        return Err(ParseError { message, span });
+                    }
                     t => Expr::Literal(Value::String(t.lexeme)),
                 };
 
                 exprs.push(expr);
             }
 
-            if exprs.len() > 0 {
+            if !exprs.is_empty() {
                 segments.push(exprs);
             } else {
                 break;
             }
         }
 
-        if segments.len() == 0 {
-            panic!("empty command");
+        if segments.is_empty() {
+            return Err(ParseError {
+                message: "empty command".to_string(),
+                span,
+            });
         }
 
-        Cmd::Atom(segments)
+        Ok(Cmd::Atom(segments, span.to(&self.lexer.last_span)))
     }
 }
 
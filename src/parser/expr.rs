@@ -2,61 +2,71 @@ use std::collections::HashMap;
 
 use crate::ast::{BinaryOp, Expr, UnaryOp};
 use crate::interp::Value;
+use crate::source::Span;
 use crate::token::{Token, TokenKind};
 
-use super::Parser;
+use super::{ParseError, ParseResult, Parser};
 
 impl Parser {
-    pub fn parse_expr(&mut self, min_bp: u8) -> Expr {
+    pub fn parse_expr(&mut self, min_bp: u8) -> ParseResult<Expr> {
         let mut lhs = match self.lexer.next() {
+            Some(Token { kind: TokenKind::Int(int), .. }) => Expr::Literal(Value::Int(int)),
             Some(Token { kind: TokenKind::Num(num), .. }) => Expr::Literal(Value::Num(num)),
-            Some(Token { kind: TokenKind::Identifier(name), .. }) => Expr::Get(name),
+            Some(Token { kind: TokenKind::Identifier(name), span, .. }) => Expr::Get(name, span),
 
-            Some(t @ Token { kind: TokenKind::String { .. }, .. }) => self.continue_parse_string_expr(t),
+            Some(t @ Token { kind: TokenKind::String { .. }, .. }) => self.continue_parse_string_expr(t)?,
 
             Some(Token { kind: TokenKind::True, .. }) => Expr::Literal(Value::Bool(true)),
             Some(Token { kind: TokenKind::False, .. }) => Expr::Literal(Value::Bool(false)),
 
             Some(Token { kind: TokenKind::Nil, .. }) => Expr::Literal(Value::Nil),
 
-            Some(Token { kind: TokenKind::LeftBracket, .. }) => self.parse_vec_literal(),
-            Some(Token { kind: TokenKind::LeftBrace, .. }) => self.parse_dict_literal(),
+            Some(Token { kind: TokenKind::LeftBracket, .. }) => self.parse_vec_literal()?,
+            Some(Token { kind: TokenKind::LeftBrace, .. }) => self.parse_dict_literal()?,
 
-            Some(Token { kind: TokenKind::Fn, .. }) => self.parse_fn_lambda(),
+            Some(Token { kind: TokenKind::Fn, .. }) => self.parse_fn_lambda()?,
 
             Some(t @ Token { .. }) if t.is_prefix_op() => {
-                let kind = t.kind;
-                let ((), r_bp) = prefix_binding_power(&kind).unwrap();
+                let ((), r_bp) = prefix_binding_power(&t.kind).unwrap();
 
                 self.lexer.consume_whitespace(self.is_multiline);
-                let rhs = self.parse_expr(r_bp);
+                let rhs = self.parse_expr(r_bp)?;
 
-                make_prefix_expr(&kind, rhs)
+                make_prefix_expr(t, rhs)
             }
 
             Some(Token { kind: TokenKind::LeftParen, .. }) => {
                 self.lexer.consume_whitespace(self.is_multiline);
-                let expr = self.parse_expr(0);
+                let expr = self.parse_expr(0)?;
                 self.lexer.consume_whitespace(self.is_multiline);
 
                 if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::RightParen, .. })) {
-                    panic!("expected right parenthesis");
+                    return self.error("expected right parenthesis");
                 }
 
                 expr
             }
 
             Some(Token { kind: TokenKind::DollarLeftParen, .. }) => {
-                let cmd = self.parse_cmd(0);
+                let cmd = self.parse_cmd(0)?;
 
                 if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::RightParen, .. })) {
-                    panic!("expected right parenthesis");
+                    return self.error("expected right parenthesis");
                 }
 
                 Expr::Cmd(cmd)
             }
 
-            _ => panic!("bad token"),
+            Some(Token { kind: TokenKind::Error(message), span, .. }) => {
+                return Err(ParseError { message, span });
+            }
+            Some(Token { kind: TokenKind::LargeInt, span, .. }) => {
+                return Err(ParseError { message: "integer literal is too large".to_string(), span });
+            }
+
+            None => return self.error("expected expression, found end of file"),
+
+            _ => return self.error("expected expression"),
         };
 
         loop {
@@ -66,8 +76,9 @@ impl Parser {
                 break;
             }
 
-            if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::DotDot, ..})) {
-                self.lexer.next().unwrap();
+            // Ranges bind looser than any other operator but assignment, so that `-1..n + 1` is a range
+            if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::DotDot, ..})) && min_bp <= RANGE_BP {
+                let span = self.lexer.next().unwrap().span;
 
                 self.lexer.consume_whitespace(self.is_multiline);
                 let inclusive = if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Equal, ..})) {
@@ -78,13 +89,25 @@ impl Parser {
                 };
 
                 self.lexer.consume_whitespace(self.is_multiline);
-                let rhs = self.parse_expr(0);
+                let rhs = self.parse_expr(0)?;
+
+                // `step` is only a keyword after a range, as in `10..0 step -2`
+                self.lexer.consume_whitespace(false);
+                let step = if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Identifier(word), ..}) if word == "step") {
+                    self.lexer.next();
+                    self.lexer.consume_whitespace(self.is_multiline);
+                    Some(Box::new(self.parse_expr(0)?))
+                } else {
+                    None
+                };
 
-                return Expr::Range {
+                return Ok(Expr::Range {
                     l: Box::new(lhs),
                     r: Box::new(rhs),
                     inclusive,
-                };
+                    step,
+                    span,
+                });
             }
 
             let op = &self.lexer.peek().unwrap().kind;
@@ -94,53 +117,52 @@ impl Parser {
                     break;
                 }
 
-                let op = self.lexer.next().unwrap().kind;
+                let op = self.lexer.next().unwrap();
 
-                lhs = match op {
+                lhs = match op.kind {
                     TokenKind::LeftBracket => {
                         self.lexer.consume_whitespace(self.is_multiline);
-                        let index = self.parse_expr(0);
+                        let index = self.parse_expr(0)?;
                         self.lexer.consume_whitespace(self.is_multiline);
 
                         if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::RightBracket, .. })) {
-                            panic!("expected right bracket");
+                            return self.error("expected right bracket");
                         }
 
                         Expr::GetField {
                             base: Box::new(lhs),
                             index: Box::new(index),
+                            span: op.span.to(&self.lexer.last_span),
                         }
                     }
                     TokenKind::Dot => {
                         self.lexer.consume_whitespace(self.is_multiline);
-                        let name = match self.lexer.next() {
-                            Some(Token { kind: TokenKind::Identifier(name), .. }) => name,
-                            _ => panic!("expected identifier"),
-                        };
+                        let name = self.must_identifier()?;
 
                         Expr::GetField {
                             base: Box::new(lhs),
                             index: Box::new(Expr::Literal(Value::String(name))),
+                            span: op.span.to(&self.lexer.last_span),
                         }
                     }
-                    TokenKind::LeftParen => self.parse_call(lhs),
+                    TokenKind::LeftParen => self.parse_call(lhs, op.span)?,
                     _ => unreachable!(),
                 };
 
                 continue;
             }
 
-            if let Some((l_bp, r_bp)) = infix_binding_power(&op) {
+            if let Some((l_bp, r_bp)) = infix_binding_power(op) {
                 if l_bp < min_bp {
                     break;
                 }
 
-                let op = self.lexer.next().unwrap().kind;
+                let op = self.lexer.next().unwrap();
 
                 self.lexer.consume_whitespace(self.is_multiline);
-                let rhs = self.parse_expr(r_bp);
+                let rhs = self.parse_expr(r_bp)?;
 
-                lhs = make_infix_expr(lhs, &op, rhs);
+                lhs = make_infix_expr(lhs, op, rhs)?;
 
                 continue;
             }
@@ -148,20 +170,21 @@ impl Parser {
             break;
         }
 
-        lhs
+        Ok(lhs)
     }
 
-    fn consume_comma(&mut self) {
+    pub fn consume_comma(&mut self) {
         self.lexer.consume_whitespace(self.is_multiline);
         if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Comma, ..})) {
             self.lexer.next();
         }
     }
 
-    fn parse_call(&mut self, func: Expr) -> Expr {
+    fn parse_call(&mut self, func: Expr, span: Span) -> ParseResult<Expr> {
         let func = Box::new(func);
 
         let mut args = Vec::new();
+        let mut named = Vec::new();
 
         loop {
             self.lexer.consume_whitespace(self.is_multiline);
@@ -171,18 +194,36 @@ impl Parser {
                 break;
             }
 
-            args.push(self.parse_expr(0));
+            let arg = self.parse_expr(0)?;
+
+            // A name followed by a colon names the parameter the argument is for, as in `deploy(env: 'prod')`
+            self.lexer.consume_whitespace(false);
+            match arg {
+                Expr::Get(name, _) if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Colon, ..})) => {
+                    self.lexer.next();
+                    self.lexer.consume_whitespace(self.is_multiline);
+
+                    if named.iter().any(|(other, _)| *other == name) {
+                        return self.error(&format!("argument {} is given twice", name));
+                    }
+                    named.push((name, self.parse_expr(0)?));
+                }
+                arg if named.is_empty() => args.push(arg),
+                _ => return self.error("positional arguments can't follow named ones"),
+            }
 
             self.consume_comma();
         }
 
-        Expr::Call {
+        Ok(Expr::Call {
             args,
+            named,
             func,
-        }
+            span: span.to(&self.lexer.last_span),
+        })
     }
 
-    fn parse_vec_literal(&mut self) -> Expr {
+    fn parse_vec_literal(&mut self) -> ParseResult<Expr> {
         let mut vec = Vec::new();
 
         loop {
@@ -193,15 +234,15 @@ impl Parser {
                 break;
             }
 
-            vec.push(self.parse_expr(0));
+            vec.push(self.parse_expr(0)?);
 
             self.consume_comma();
         }
 
-        Expr::Vec(vec)
+        Ok(Expr::Vec(vec))
     }
 
-    fn parse_dict_literal(&mut self) -> Expr {
+    fn parse_dict_literal(&mut self) -> ParseResult<Expr> {
         let mut dict = HashMap::new();
 
         loop {
@@ -215,36 +256,37 @@ impl Parser {
             let k = match self.lexer.next() {
                 Some(Token { kind: TokenKind::String { value, does_interp }, .. }) if !does_interp => value,
                 Some(Token { kind: TokenKind::Identifier(name), .. }) => name,
+                Some(Token { kind: TokenKind::Int(int), .. }) => int.to_string(),
                 Some(Token { kind: TokenKind::Num(num), .. }) => num.to_string(),
-                _ => panic!("bad dict key")
+                _ => return self.error("bad dict key")
             };
 
             self.lexer.consume_whitespace(self.is_multiline);
             if !matches!(self.lexer.next(), Some(Token {kind: TokenKind::Colon, ..})) {
-                panic!("expected colon");
+                return self.error("expected colon");
             }
 
             self.lexer.consume_whitespace(self.is_multiline);
-            let v = self.parse_expr(0);
+            let v = self.parse_expr(0)?;
 
             dict.insert(k, v);
 
             self.consume_comma();
         }
 
-        Expr::Dict(dict)
+        Ok(Expr::Dict(dict))
     }
 
-    fn parse_fn_lambda(&mut self) -> Expr {
+    fn parse_fn_lambda(&mut self) -> ParseResult<Expr> {
         self.lexer.consume_whitespace(self.is_multiline);
 
-        Expr::Lambda(self.continue_parse_fn())
+        Ok(Expr::Lambda(self.continue_parse_fn()?))
     }
 
-    pub fn continue_parse_string_expr(&mut self, t: Token) -> Expr {
+    pub fn continue_parse_string_expr(&mut self, t: Token) -> ParseResult<Expr> {
         match t {
             Token { kind: TokenKind::String { value, does_interp: false }, .. } => {
-                Expr::Literal(Value::String(value))
+                Ok(Expr::Literal(Value::String(value)))
             }
             Token { kind: TokenKind::String { value, does_interp: true }, .. } => {
                 let mut strings = Vec::new();
@@ -254,57 +296,70 @@ impl Parser {
 
                 loop {
                     self.lexer.consume_whitespace(self.is_multiline);
-                    exprs.push(self.parse_expr(0));
+                    exprs.push(self.parse_expr(0)?);
                     self.lexer.consume_whitespace(self.is_multiline);
 
-                    if let Some(Token { kind: TokenKind::String { value, does_interp }, .. }) = self.lexer.next() {
-                        strings.push(value);
+                    match self.lexer.next() {
+                        Some(Token { kind: TokenKind::String { value, does_interp }, .. }) => {
+                            strings.push(value);
 
-                        if !does_interp {
-                            break;
+                            if !does_interp {
+                                break;
+                            }
+                        }
+                        Some(Token { kind: TokenKind::Error(message), span, .. }) => {
+                            return Err(ParseError { message, span });
                         }
-                    } else {
-                        panic!("bad token");
+                        _ => return self.error("expected closing brace at end of interpolated expression"),
                     }
                 }
 
-                Expr::Interp { strings, exprs }
+                Ok(Expr::Interp { strings, exprs })
             }
-            _ => panic!("bad token")
+            _ => unreachable!()
         }
     }
 }
 
-fn make_prefix_expr(op: &TokenKind, rhs: Expr) -> Expr {
-    match *op {
+fn make_prefix_expr(op: Token, rhs: Expr) -> Expr {
+    match op.kind {
         TokenKind::Plus => rhs,
-        TokenKind::Minus => Expr::Unary(UnaryOp::Neg, Box::new(rhs)),
-        TokenKind::Bang => Expr::Unary(UnaryOp::Not, Box::new(rhs)),
+        TokenKind::Minus => Expr::Unary(UnaryOp::Neg, Box::new(rhs), op.span),
+        TokenKind::Bang => Expr::Unary(UnaryOp::Not, Box::new(rhs), op.span),
 
         _ => unreachable!()
     }
 }
 
-fn make_infix_expr(lhs: Expr, op: &TokenKind, rhs: Expr) -> Expr {
+fn make_infix_expr(lhs: Expr, op: Token, rhs: Expr) -> ParseResult<Expr> {
     let lhs = Box::new(lhs);
     let rhs = Box::new(rhs);
 
-    match *op {
-        TokenKind::Plus => Expr::Binary(lhs, BinaryOp::Sum, rhs),
-        TokenKind::Minus => Expr::Binary(lhs, BinaryOp::Sub, rhs),
-        TokenKind::Star => Expr::Binary(lhs, BinaryOp::Mul, rhs),
-        TokenKind::Slash => Expr::Binary(lhs, BinaryOp::Div, rhs),
-        TokenKind::Perc => Expr::Binary(lhs, BinaryOp::Mod, rhs),
-        TokenKind::Caret => Expr::Binary(lhs, BinaryOp::Pow, rhs),
+    let Token { kind: op, span, .. } = op;
+
+    let expr = match op {
+        TokenKind::Plus => Expr::Binary(lhs, BinaryOp::Sum, rhs, span),
+        TokenKind::Minus => Expr::Binary(lhs, BinaryOp::Sub, rhs, span),
+        TokenKind::Star => Expr::Binary(lhs, BinaryOp::Mul, rhs, span),
+        TokenKind::Slash => Expr::Binary(lhs, BinaryOp::Div, rhs, span),
+        TokenKind::SlashSlash => Expr::Binary(lhs, BinaryOp::IntDiv, rhs, span),
+        TokenKind::Perc => Expr::Binary(lhs, BinaryOp::Mod, rhs, span),
+        TokenKind::StarStar => Expr::Binary(lhs, BinaryOp::Pow, rhs, span),
 
-        TokenKind::AmperAmper => Expr::Binary(lhs, BinaryOp::And, rhs),
-        TokenKind::PipePipe => Expr::Binary(lhs, BinaryOp::Or, rhs),
+        TokenKind::Amper => Expr::Binary(lhs, BinaryOp::BitAnd, rhs, span),
+        TokenKind::Pipe => Expr::Binary(lhs, BinaryOp::BitOr, rhs, span),
+        TokenKind::Caret => Expr::Binary(lhs, BinaryOp::BitXor, rhs, span),
+        TokenKind::LessLess => Expr::Binary(lhs, BinaryOp::Shl, rhs, span),
+        TokenKind::GreatGreat => Expr::Binary(lhs, BinaryOp::Shr, rhs, span),
+
+        TokenKind::AmperAmper => Expr::Binary(lhs, BinaryOp::And, rhs, span),
+        TokenKind::PipePipe => Expr::Binary(lhs, BinaryOp::Or, rhs, span),
 
         TokenKind::EqualEqual | TokenKind::BangEqual => {
-            let mut expr = Expr::Binary(lhs, BinaryOp::Equal, rhs);
+            let mut expr = Expr::Binary(lhs, BinaryOp::Equal, rhs, span.clone());
 
             if matches!(op, TokenKind::BangEqual) {
-                expr = Expr::Unary(UnaryOp::Not, Box::new(expr));
+                expr = Expr::Unary(UnaryOp::Not, Box::new(expr), span);
             }
 
             expr
@@ -319,16 +374,15 @@ fn make_infix_expr(lhs: Expr, op: &TokenKind, rhs: Expr) -> Expr {
                     _ => unreachable!(),
                 },
                 rhs.clone(),
+                span.clone(),
             );
 
             if matches!(op, TokenKind::GreatEqual | TokenKind::LessEqual) {
-                let lhs = lhs.clone();
-                let rhs = rhs.clone();
-
                 expr = Expr::Binary(
                     Box::new(expr),
                     BinaryOp::Or,
-                    Box::new(Expr::Binary(lhs, BinaryOp::Equal, rhs)),
+                    Box::new(Expr::Binary(lhs, BinaryOp::Equal, rhs, span.clone())),
+                    span,
                 );
             }
 
@@ -337,39 +391,55 @@ fn make_infix_expr(lhs: Expr, op: &TokenKind, rhs: Expr) -> Expr {
 
         TokenKind::Equal |
         TokenKind::PlusEqual | TokenKind::MinusEqual |
-        TokenKind::StarEqual | TokenKind::SlashEqual |
-        TokenKind::PercEqual | TokenKind::CaretEqual => {
-            let rhs = if !matches!(*op, TokenKind::Equal) {
-                let op = match *op {
+        TokenKind::StarEqual | TokenKind::SlashEqual | TokenKind::SlashSlashEqual |
+        TokenKind::PercEqual | TokenKind::StarStarEqual |
+        TokenKind::AmperEqual | TokenKind::PipeEqual | TokenKind::CaretEqual |
+        TokenKind::LessLessEqual | TokenKind::GreatGreatEqual => {
+            let rhs = if !matches!(op, TokenKind::Equal) {
+                let op = match op {
                     TokenKind::PlusEqual => BinaryOp::Sum,
                     TokenKind::MinusEqual => BinaryOp::Sub,
                     TokenKind::StarEqual => BinaryOp::Mul,
                     TokenKind::SlashEqual => BinaryOp::Div,
+                    TokenKind::SlashSlashEqual => BinaryOp::IntDiv,
                     TokenKind::PercEqual => BinaryOp::Mod,
-                    TokenKind::CaretEqual => BinaryOp::Pow,
+                    TokenKind::StarStarEqual => BinaryOp::Pow,
+                    TokenKind::AmperEqual => BinaryOp::BitAnd,
+                    TokenKind::PipeEqual => BinaryOp::BitOr,
+                    TokenKind::CaretEqual => BinaryOp::BitXor,
+                    TokenKind::LessLessEqual => BinaryOp::Shl,
+                    TokenKind::GreatGreatEqual => BinaryOp::Shr,
                     _ => unreachable!()
                 };
 
-                Box::new(Expr::Binary(lhs.clone(), op, rhs))
+                Box::new(Expr::Binary(lhs.clone(), op, rhs, span.clone()))
             } else {
                 rhs
             };
 
             match *lhs {
-                Expr::Get(name) => Expr::Set(name, rhs),
-                Expr::GetField { base, index } => Expr::SetField { base, index, expr: rhs },
-                _ => panic!("bad assignment target")
+                Expr::Get(name, span) => Expr::Set(name, rhs, span),
+                Expr::GetField { base, index, span } => Expr::SetField { base, index, expr: rhs, span },
+                _ => return Err(ParseError {
+                    message: "bad assignment target".to_string(),
+                    span,
+                }),
             }
         }
 
         _ => unreachable!(),
-    }
+    };
+
+    Ok(expr)
 }
 
+// Left binding power of `..`, right between assignments and `||`
+const RANGE_BP: u8 = 2;
+
 fn prefix_binding_power(op: &TokenKind) -> Option<((), u8)> {
     use TokenKind::*;
     let bp = match op {
-        Bang | Plus | Minus => ((), 17),
+        Bang | Plus | Minus => ((), 25),
         _ => return None,
     };
     Some(bp)
@@ -378,14 +448,19 @@ fn prefix_binding_power(op: &TokenKind) -> Option<((), u8)> {
 fn infix_binding_power(op: &TokenKind) -> Option<(u8, u8)> {
     use TokenKind::*;
     let bp = match op {
-        Caret => (16, 15),
-        Star | Slash | Perc => (13, 14),
-        Plus | Minus => (11, 12),
+        StarStar => (24, 23),
+        Star | Slash | SlashSlash | Perc => (21, 22),
+        Plus | Minus => (19, 20),
+        LessLess | GreatGreat => (17, 18),
+        Amper => (15, 16),
+        Caret => (13, 14),
+        Pipe => (11, 12),
         Great | GreatEqual | Less | LessEqual => (9, 10),
         EqualEqual | BangEqual => (7, 8),
         AmperAmper => (5, 6),
         PipePipe => (3, 4),
-        Equal | PlusEqual | MinusEqual | StarEqual | SlashEqual | PercEqual | CaretEqual => (2, 1),
+        Equal | PlusEqual | MinusEqual | StarEqual | SlashEqual | SlashSlashEqual | PercEqual | StarStarEqual |
+        AmperEqual | PipeEqual | CaretEqual | LessLessEqual | GreatGreatEqual => (2, 1),
         _ => return None,
     };
     Some(bp)
@@ -394,7 +469,7 @@ fn infix_binding_power(op: &TokenKind) -> Option<(u8, u8)> {
 fn postfix_binding_power(op: &TokenKind) -> Option<(u8, ())> {
     use TokenKind::*;
     let bp = match op {
-        LeftBracket | LeftParen | Dot => (19, ()),
+        LeftBracket | LeftParen | Dot => (27, ()),
         _ => return None,
     };
     Some(bp)
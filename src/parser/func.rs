@@ -1,14 +1,35 @@
+use crate::ast::Pattern;
 use crate::interp::Func;
 use crate::token::{Token, TokenKind};
 
-use super::Parser;
+use super::{ParseResult, Parser};
 
 impl Parser {
-    pub fn continue_parse_fn(&mut self) -> Func {
+    pub fn continue_parse_fn(&mut self) -> ParseResult<Func> {
+        let (params, rest) = self.parse_params()?;
+
+        self.lexer.consume_whitespace(self.is_multiline);
+        let body = self.parse_block()?;
+
+        Ok(Func::User {
+            name: None,
+            params,
+            rest,
+            body: Box::new(body),
+            doc: None,
+            deps: Vec::new(),
+            captured_env: None,
+        })
+    }
+
+    /// Parses the parameters of a function, which are patterns that may have a default, and the rest parameter that
+    /// gets the remaining arguments, as in `(env, replicas = 1, ...flags)`.
+    pub fn parse_params(&mut self) -> ParseResult<(Vec<Pattern>, Option<Box<Pattern>>)> {
         let mut params = Vec::new();
+        let mut rest = None;
 
         if !matches!(self.lexer.next(), Some(Token{kind: TokenKind::LeftParen, ..})) {
-            panic!("expected left parenthesis");
+            return self.error("expected left parenthesis");
         }
 
         self.lexer.consume_whitespace(self.is_multiline);
@@ -17,25 +38,39 @@ impl Parser {
         } else {
             loop {
                 self.lexer.consume_whitespace(self.is_multiline);
-                params.push(self.must_identifier());
+
+                if rest.is_some() {
+                    return self.error("the rest parameter must be the last one");
+                }
+
+                if matches!(self.lexer.peek(), Some(Token { kind: TokenKind::DotDot, .. })) {
+                    self.lexer.next();
+                    if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::Dot, .. })) {
+                        return self.error("expected ... before the rest parameter");
+                    }
+
+                    let span = self.lexer.peek().map(|token| token.span.clone()).unwrap_or_default();
+                    rest = Some(Box::new(Pattern::Bind(self.must_identifier()?, span)));
+                } else {
+                    let param = self.parse_pattern()?;
+                    let param = self.continue_parse_default(param)?;
+
+                    if !matches!(param, Pattern::Default { .. }) && matches!(params.last(), Some(Pattern::Default { .. })) {
+                        return self.error("parameters without a default can't follow ones with a default");
+                    }
+                    params.push(param);
+                }
+
                 self.lexer.consume_whitespace(self.is_multiline);
 
                 match self.lexer.next() {
                     Some(Token { kind: TokenKind::Comma, .. }) => (),
                     Some(Token { kind: TokenKind::RightParen, .. }) => break,
-                    _ => panic!("expected comma or right parenthesis"),
+                    _ => return self.error("expected comma or right parenthesis"),
                 }
             }
         }
 
-        self.lexer.consume_whitespace(self.is_multiline);
-        let body = self.parse_block();
-
-        Func::User {
-            name: None,
-            params,
-            body: Box::new(body),
-            captured_env: None,
-        }
+        Ok((params, rest))
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,16 @@
-use crate::ast::{Prog, Stmt};
+use std::fmt::{Display, Formatter};
+use std::fmt;
+
+use crate::ast::{Expr, Prog, Stmt};
 use crate::lexer::Lexer;
+use crate::source::Span;
 use crate::token::{Token, TokenKind};
 
 mod expr;
 mod stmt;
 mod cmd;
 mod func;
+mod pattern;
 
 #[cfg(test)]
 mod test;
@@ -13,18 +18,65 @@ mod test;
 pub struct Parser {
     lexer: Lexer,
     is_multiline: bool,
+    // Errors of the statements that were skipped to keep parsing
+    errors: Vec<ParseError>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
 }
 
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.span.render(&self.message))
+    }
+}
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
 impl Parser {
     pub fn new(lexer: Lexer) -> Parser {
         Parser {
             lexer,
             is_multiline: true,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Parses the whole program. Statements that don't parse are left out of the returned program and their errors
+    /// are collected, so the program is only meant to be run if there are no errors.
+    pub fn parse(&mut self) -> (Prog, Vec<ParseError>) {
+        let mut prog = Vec::new();
+
+        loop {
+            prog.append(&mut self.parse_stmts());
+
+            // `parse_stmts` stops at a right brace, at the top level that means it has no matching left brace
+            match self.lexer.peek() {
+                Some(Token { kind: TokenKind::RightBrace, span, .. }) => {
+                    let err = ParseError {
+                        message: "unexpected right brace".to_string(),
+                        span: span.clone(),
+                    };
+                    self.errors.push(err);
+                    self.lexer.next();
+                }
+                _ => break,
+            }
         }
+
+        (prog, std::mem::take(&mut self.errors))
     }
 
-    pub fn parse(&mut self) -> Prog {
-        self.parse_stmts()
+    /// Parses the whole input as a single expression, returns `None` if it isn't one.
+    pub fn parse_lone_expr(&mut self) -> Option<Expr> {
+        self.lexer.consume_whitespace(true);
+        let expr = self.parse_expr(0).ok()?;
+        self.lexer.consume_whitespace(true);
+
+        if self.is_at_end() { Some(expr) } else { None }
     }
 
     fn parse_stmts(&mut self) -> Vec<Stmt> {
@@ -38,16 +90,42 @@ impl Parser {
             }
 
             if self.lexer.peek().unwrap().kind == TokenKind::RightBrace {
-                // The lexer should already catch the error where more blocks are closed than opened
                 break;
             }
 
-            stmts.push(self.parse_stmt());
+            match self.parse_stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.synchronize();
+                }
+            }
         }
 
         stmts
     }
 
+    /// Skips the rest of a statement that failed to parse: up to the end of the line or up to the right brace closing
+    /// the enclosing block. Blocks opened along the way are skipped whole.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+
+        while let Some(token) = self.lexer.peek() {
+            match token.kind {
+                TokenKind::Newline if depth == 0 => {
+                    self.lexer.next();
+                    break;
+                }
+                TokenKind::RightBrace if depth == 0 => break,
+                TokenKind::RightBrace => depth -= 1,
+                TokenKind::LeftBrace => depth += 1,
+                _ => (),
+            }
+
+            self.lexer.next();
+        }
+    }
+
     pub fn is_at_end(&mut self) -> bool {
         match self.lexer.peek() {
             Some(Token { kind: TokenKind::Newline, .. }) if !self.is_multiline => true,
@@ -56,11 +134,32 @@ impl Parser {
         }
     }
 
-    pub fn must_identifier(&mut self) -> String {
+    pub fn must_identifier(&mut self) -> ParseResult<String> {
         if let Some(Token { kind: TokenKind::Identifier(name), .. }) = self.lexer.next() {
-            name
+            Ok(name)
         } else {
-            panic!("expected identifier");
+            self.error("expected identifier")
         }
     }
+
+    /// Fails with `message` at the last token returned by the lexer.
+    fn error<T>(&self, message: &str) -> ParseResult<T> {
+        Err(ParseError {
+            message: message.to_string(),
+            span: self.lexer.last_span.clone(),
+        })
+    }
+
+    /// Fails with `message` at the next token or, if there are none left, at the last one.
+    fn error_at_peeked<T>(&mut self, message: &str) -> ParseResult<T> {
+        let span = match self.lexer.peek() {
+            Some(token) => token.span.clone(),
+            None => self.lexer.last_span.clone(),
+        };
+
+        Err(ParseError {
+            message: message.to_string(),
+            span,
+        })
+    }
 }
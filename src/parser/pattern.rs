@@ -0,0 +1,272 @@
+use regex::Regex;
+
+use crate::ast::{Expr, MatchArm, Pattern, Stmt};
+use crate::interp::Value;
+use crate::token::{Token, TokenKind};
+
+use super::{ParseResult, Parser};
+
+impl Parser {
+    pub fn parse_match_stmt(&mut self) -> ParseResult<Stmt> {
+        self.lexer.next();
+
+        self.lexer.consume_whitespace(self.is_multiline);
+        let subject = self.parse_expr(0)?;
+
+        self.lexer.consume_whitespace(self.is_multiline);
+        if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::LeftBrace, .. })) {
+            return self.error("expected left brace");
+        }
+
+        let mut arms = Vec::new();
+
+        loop {
+            self.lexer.consume_whitespace(self.is_multiline);
+
+            match self.lexer.peek() {
+                Some(Token { kind: TokenKind::RightBrace, .. }) => {
+                    self.lexer.next();
+                    break;
+                }
+                None => return self.error("expected right brace"),
+                _ => (),
+            }
+
+            let pattern = self.parse_pattern()?;
+
+            self.lexer.consume_whitespace(self.is_multiline);
+            if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::Equal, .. })) ||
+                !matches!(self.lexer.next(), Some(Token { kind: TokenKind::Great, .. })) {
+                return self.error("expected => after pattern");
+            }
+
+            self.lexer.consume_whitespace(false);
+
+            // Unless it's a block, the body ends with its line, so that the next pattern isn't read as part of it
+            let body = if matches!(self.lexer.peek(), Some(Token { kind: TokenKind::LeftBrace, .. })) {
+                self.parse_block()
+            } else {
+                let was_multiline = self.is_multiline;
+                self.is_multiline = false;
+                let body = self.parse_stmt();
+                self.is_multiline = was_multiline;
+                body
+            }?;
+
+            arms.push(MatchArm { pattern, body });
+        }
+
+        Ok(Stmt::Match { subject, arms })
+    }
+
+    /// Parses a pattern, possibly made of alternatives separated by `|`.
+    pub fn parse_pattern(&mut self) -> ParseResult<Pattern> {
+        let mut alternatives = vec![self.parse_single_pattern()?];
+
+        loop {
+            self.lexer.consume_whitespace(self.is_multiline);
+
+            if !matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Pipe, .. })) {
+                break;
+            }
+            self.lexer.next();
+
+            self.lexer.consume_whitespace(self.is_multiline);
+            alternatives.push(self.parse_single_pattern()?);
+        }
+
+        if alternatives.len() == 1 {
+            Ok(alternatives.pop().unwrap())
+        } else {
+            Ok(Pattern::Or(alternatives))
+        }
+    }
+
+    fn parse_single_pattern(&mut self) -> ParseResult<Pattern> {
+        let pattern = match self.lexer.next() {
+            Some(Token { kind: TokenKind::Identifier(name), .. }) if name == "_" => Pattern::Any,
+            Some(Token { kind: TokenKind::Identifier(name), span, .. }) => {
+                if name == "re" || name == "glob" {
+                    self.lexer.consume_whitespace(false);
+                }
+
+                match (name.as_str(), self.lexer.peek()) {
+                    ("re" | "glob", Some(Token { kind: TokenKind::String { does_interp: false, .. }, .. })) => {
+                        self.parse_string_pattern(&name)?
+                    }
+                    _ => Pattern::Bind(name, span),
+                }
+            }
+            Some(Token { kind: TokenKind::Int(int), .. }) => self.continue_parse_num_pattern(Value::Int(int))?,
+            Some(Token { kind: TokenKind::Num(num), .. }) => self.continue_parse_num_pattern(Value::Num(num))?,
+            Some(Token { kind: TokenKind::Minus, .. }) => match self.must_num()? {
+                Value::Int(int) => self.continue_parse_num_pattern(Value::Int(-int))?,
+                Value::Num(num) => self.continue_parse_num_pattern(Value::Num(-num))?,
+                _ => unreachable!(),
+            },
+            Some(t @ Token { kind: TokenKind::String { .. }, .. }) => Pattern::Value(self.continue_parse_string_expr(t)?),
+            Some(Token { kind: TokenKind::True, .. }) => Pattern::Value(Expr::Literal(Value::Bool(true))),
+            Some(Token { kind: TokenKind::False, .. }) => Pattern::Value(Expr::Literal(Value::Bool(false))),
+            Some(Token { kind: TokenKind::Nil, .. }) => Pattern::Value(Expr::Literal(Value::Nil)),
+            Some(Token { kind: TokenKind::LeftBracket, .. }) => self.parse_vec_pattern()?,
+            Some(Token { kind: TokenKind::LeftBrace, .. }) => self.parse_dict_pattern()?,
+            _ => return self.error("expected pattern"),
+        };
+
+        Ok(pattern)
+    }
+
+    /// Parses the string of a `re` or `glob` pattern, which must be valid.
+    fn parse_string_pattern(&mut self, kind: &str) -> ParseResult<Pattern> {
+        let pattern = match self.lexer.next() {
+            Some(Token { kind: TokenKind::String { value, .. }, .. }) => value,
+            _ => unreachable!(),
+        };
+
+        if kind == "re" {
+            if let Err(err) = Regex::new(&pattern) {
+                return self.error(&format!("invalid regex: {}", err));
+            }
+            Ok(Pattern::Regex(pattern))
+        } else {
+            if let Err(err) = glob::Pattern::new(&pattern) {
+                return self.error(&format!("invalid glob: {}", err));
+            }
+            Ok(Pattern::Glob(pattern))
+        }
+    }
+
+    fn continue_parse_num_pattern(&mut self, l: Value) -> ParseResult<Pattern> {
+        self.lexer.consume_whitespace(false);
+
+        if !matches!(self.lexer.peek(), Some(Token { kind: TokenKind::DotDot, .. })) {
+            return Ok(Pattern::Value(Expr::Literal(l)));
+        }
+        self.lexer.next();
+
+        let inclusive = matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Equal, .. }));
+        if inclusive {
+            self.lexer.next();
+        }
+
+        self.lexer.consume_whitespace(false);
+        let is_negative = matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Minus, .. }));
+        if is_negative {
+            self.lexer.next();
+        }
+
+        let r = match self.must_num()? {
+            Value::Int(int) if is_negative => Value::Int(-int),
+            Value::Num(num) if is_negative => Value::Num(-num),
+            r => r,
+        };
+
+        Ok(Pattern::Range { l: Box::new(l), r: Box::new(r), inclusive })
+    }
+
+    fn parse_vec_pattern(&mut self) -> ParseResult<Pattern> {
+        let mut items = Vec::new();
+        let mut rest = None;
+
+        loop {
+            self.lexer.consume_whitespace(self.is_multiline);
+
+            match self.lexer.peek() {
+                Some(Token { kind: TokenKind::RightBracket, .. }) => {
+                    self.lexer.next();
+                    break;
+                }
+                Some(Token { kind: TokenKind::DotDot, .. }) => {
+                    self.lexer.next();
+                    if !matches!(self.lexer.next(), Some(Token { kind: TokenKind::Dot, .. })) {
+                        return self.error("expected ... before the rest pattern");
+                    }
+
+                    if rest.is_some() {
+                        return self.error("only one rest pattern is allowed");
+                    }
+
+                    let name = match self.lexer.peek() {
+                        Some(Token { kind: TokenKind::Identifier(..), .. }) => Some(self.must_identifier()?),
+                        _ => None,
+                    };
+
+                    rest = Some((items.len(), name));
+                }
+                _ => {
+                    let item = self.parse_pattern()?;
+                    items.push(self.continue_parse_default(item)?);
+                }
+            }
+
+            self.consume_comma();
+        }
+
+        Ok(Pattern::Vec { items, rest })
+    }
+
+    fn parse_dict_pattern(&mut self) -> ParseResult<Pattern> {
+        let mut entries = Vec::new();
+
+        loop {
+            self.lexer.consume_whitespace(self.is_multiline);
+
+            if matches!(self.lexer.peek(), Some(Token { kind: TokenKind::RightBrace, .. })) {
+                self.lexer.next();
+                break;
+            }
+
+            let (key, span) = match self.lexer.next() {
+                Some(Token { kind: TokenKind::String { value, does_interp: false }, span, .. }) => (value, span),
+                Some(Token { kind: TokenKind::Identifier(name), span, .. }) => (name, span),
+                _ => return self.error("bad dict key"),
+            };
+
+            self.lexer.consume_whitespace(self.is_multiline);
+
+            // `{name}` is short for `{name: name}`
+            let pattern = if matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Colon, .. })) {
+                self.lexer.next();
+                self.lexer.consume_whitespace(self.is_multiline);
+                self.parse_pattern()?
+            } else {
+                Pattern::Bind(key.clone(), span)
+            };
+
+            entries.push((key, self.continue_parse_default(pattern)?));
+
+            self.consume_comma();
+        }
+
+        Ok(Pattern::Dict(entries))
+    }
+
+    /// Parses the default of an item of a vec or dict pattern or of a parameter, as in `[a, b = 2]`, if there's one.
+    pub fn continue_parse_default(&mut self, pattern: Pattern) -> ParseResult<Pattern> {
+        self.lexer.consume_whitespace(self.is_multiline);
+
+        if !matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Equal, .. })) {
+            return Ok(pattern);
+        }
+        self.lexer.next();
+
+        self.lexer.consume_whitespace(self.is_multiline);
+        let start = self.lexer.peek().map(|token| token.span.clone());
+        let default = self.parse_expr(0)?;
+        let span = start.unwrap_or_default().to(&self.lexer.last_span);
+
+        Ok(Pattern::Default {
+            pattern: Box::new(pattern),
+            default,
+            span,
+        })
+    }
+
+    fn must_num(&mut self) -> ParseResult<Value> {
+        match self.lexer.next() {
+            Some(Token { kind: TokenKind::Int(int), .. }) => Ok(Value::Int(int)),
+            Some(Token { kind: TokenKind::Num(num), .. }) => Ok(Value::Num(num)),
+            _ => self.error("expected number"),
+        }
+    }
+}
@@ -1,33 +1,39 @@
-use crate::ast::{Expr, Stmt};
+use crate::ast::{Expr, Pattern, Stmt};
 use crate::interp::Func;
 use crate::token::{Token, TokenKind};
 
-use super::Parser;
+use super::{ParseError, ParseResult, Parser};
 
 impl Parser {
-    pub fn parse_stmt(&mut self) -> Stmt {
-        match self.lexer.peek() {
-            Some(Token { kind: TokenKind::Import, .. }) => self.parse_import(),
+    pub fn parse_stmt(&mut self) -> ParseResult<Stmt> {
+        let stmt = match self.lexer.peek() {
+            Some(Token { kind: TokenKind::Import, .. }) => self.parse_import()?,
 
-            Some(Token { kind: TokenKind::LeftBrace, .. }) => self.parse_block(),
+            Some(Token { kind: TokenKind::LeftBrace, .. }) => self.parse_block()?,
 
             Some(Token { kind: TokenKind::Let, .. }) |
-            Some(Token { kind: TokenKind::Exp, .. }) => self.parse_let_stmt(),
+            Some(Token { kind: TokenKind::Exp, .. }) => self.parse_let_stmt()?,
+
+            Some(Token { kind: TokenKind::If, .. }) => self.parse_if_stmt()?,
+            Some(Token { kind: TokenKind::For, .. }) => self.parse_for_stmt()?,
+            Some(Token { kind: TokenKind::While, .. }) => self.parse_while_stmt()?,
+            Some(Token { kind: TokenKind::Fn, .. }) => self.parse_fn_stmt()?,
 
-            Some(Token { kind: TokenKind::If, .. }) => self.parse_if_stmt(),
-            Some(Token { kind: TokenKind::For, .. }) => self.parse_for_stmt(),
-            Some(Token { kind: TokenKind::While, .. }) => self.parse_while_stmt(),
-            Some(Token { kind: TokenKind::Fn, .. }) => self.parse_fn_stmt(),
+            Some(Token { kind: TokenKind::Return, .. }) => self.parse_return()?,
 
-            Some(Token { kind: TokenKind::Return, .. }) => self.parse_return(),
+            Some(Token { kind: TokenKind::Try, .. }) => self.parse_try_stmt()?,
+            Some(Token { kind: TokenKind::Match, .. }) => self.parse_match_stmt()?,
+            Some(Token { kind: TokenKind::Throw, .. }) => {
+                let span = self.lexer.next().unwrap().span;
+                self.lexer.consume_whitespace(false);
+                Stmt::Throw(self.parse_expr(0)?, span)
+            }
 
             Some(Token { kind: TokenKind::Continue, .. }) => {
-                self.lexer.next();
-                Stmt::Continue
+                Stmt::Continue(self.lexer.next().unwrap().span)
             }
             Some(Token { kind: TokenKind::Break, .. }) => {
-                self.lexer.next();
-                Stmt::Break
+                Stmt::Break(self.lexer.next().unwrap().span)
             }
 
             _ => {
@@ -40,17 +46,17 @@ impl Parser {
 
                     let was_multiline = self.is_multiline;
                     self.is_multiline = false;
-                    let cmd = Stmt::Cmd(self.parse_cmd(0));
+                    let cmd = self.parse_cmd(0);
+                    let is_at_end = self.is_at_end();
+                    self.is_multiline = was_multiline;
 
-                    if !self.is_at_end() {
-                        panic!("expected newline or EOF");
+                    if !is_at_end {
+                        return self.error_at_peeked("expected newline or EOF");
                     }
 
-                    self.is_multiline = was_multiline;
-
-                    cmd
+                    Stmt::Cmd(cmd?)
                 } else {
-                    let expr = self.parse_expr(0);
+                    let expr = self.parse_expr(0)?;
                     match expr {
                         // If top level expression is a command, convert to a statement. Reason is we want the subprocess
                         // to inherit the standard streams so that output is printed in realtime
@@ -58,10 +64,24 @@ impl Parser {
                         // We allow comma expression because they can't be created by the user, they are generated
                         // to emulate x++
                         Expr::Set(..) | Expr::SetField { .. } | Expr::Call { .. } => Stmt::Expr(expr),
-                        _ => panic!("only assignment, call and command expressions are allowed as statements"),
+                        _ => return self.error("only assignment, call and command expressions are allowed as statements"),
                     }
                 }
             }
+        };
+
+        Ok(stmt)
+    }
+
+    /// Whether the next statement is a command rather than a keyword statement or an expression, the way `parse_stmt`
+    /// decides it.
+    pub fn is_cmd_next(&mut self) -> bool {
+        use TokenKind::*;
+
+        match self.lexer.peek() {
+            Some(Token { kind: Import | LeftBrace | Let | Exp | If | For | While | Fn | Return | Try | Throw | Match | Continue | Break, .. }) => false,
+            Some(Token { kind: Dollar, .. }) => true,
+            _ => !self.is_expr_next(),
         }
     }
 
@@ -98,26 +118,28 @@ impl Parser {
 
             use TokenKind::*;
             return matches!(line_tokens_iter.next(),
-                Some(Token {kind: LeftParen | LeftBracket | Equal | PlusEqual | MinusEqual | StarEqual | SlashEqual | CaretEqual | PercEqual , ..})
+                Some(Token {kind: LeftParen | LeftBracket | Equal | PlusEqual | MinusEqual | StarEqual | SlashEqual |
+                    SlashSlashEqual | PercEqual | StarStarEqual | AmperEqual | PipeEqual | CaretEqual | LessLessEqual |
+                    GreatGreatEqual, ..})
             );
         }
     }
 
-    fn parse_import(&mut self) -> Stmt {
+    fn parse_import(&mut self) -> ParseResult<Stmt> {
         // Consume the `import` token
         self.lexer.next();
 
         self.lexer.consume_whitespace(self.is_multiline);
 
         match self.lexer.next() {
-            Some(Token { kind: TokenKind::String { does_interp: false, value }, .. }) => {
-                Stmt::Import(value)
+            Some(Token { kind: TokenKind::String { does_interp: false, value }, span, .. }) => {
+                Ok(Stmt::Import(value, span))
             },
-            _ => panic!("expected a file to import")
+            _ => self.error("expected a file to import")
         }
     }
 
-    fn parse_let_stmt(&mut self) -> Stmt {
+    fn parse_let_stmt(&mut self) -> ParseResult<Stmt> {
         let is_exp = self.lexer.peek().unwrap().kind == TokenKind::Exp;
 
         if is_exp {
@@ -127,53 +149,62 @@ impl Parser {
 
         // Only meaningful if there was an `exp`. Otherwise this has already been checked by `parse_stmt`
         if !matches!(self.lexer.next(), Some(Token{kind: TokenKind::Let, ..})) {
-            panic!("expected let");
+            return self.error("expected let");
         }
 
         self.lexer.consume_whitespace(self.is_multiline);
 
-        let name = self.must_identifier();
+        let start = self.lexer.peek().map(|token| token.span.clone()).unwrap_or_default();
+        let pattern = self.parse_pattern()?;
+        let span = start.to(&self.lexer.last_span);
 
         self.lexer.consume_whitespace(self.is_multiline);
 
-        if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Equal, ..})) {
+        let init = if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Equal, ..})) {
             self.lexer.next();
             self.lexer.consume_whitespace(self.is_multiline);
-            let init = Some(self.parse_expr(0));
-
-            Stmt::Let {
-                is_exp,
-                name,
-                init,
-            }
+            Some(self.parse_expr(0)?)
+        } else if matches!(pattern, Pattern::Bind(..)) {
+            None
         } else {
-            Stmt::Let {
-                is_exp,
-                name,
-                init: None,
-            }
-        }
+            return self.error_at_peeked("expected = and the value to destructure");
+        };
+
+        Ok(Stmt::Let {
+            is_exp,
+            pattern,
+            init,
+            span,
+        })
     }
 
-    pub fn parse_block(&mut self) -> Stmt {
-        self.lexer.next();
+    pub fn parse_block(&mut self) -> ParseResult<Stmt> {
+        if !matches!(self.lexer.next(), Some(Token {kind: TokenKind::LeftBrace, ..})) {
+            return self.error("expected left brace");
+        }
+
         let stmts = self.parse_stmts();
 
+        // `parse_stmts` only stops at a right brace or at the end of the file, in which case the block can still be
+        // used as if it were closed there
         if !matches!(self.lexer.next(), Some(Token {kind: TokenKind::RightBrace, ..})) {
-            panic!("expected right brace");
+            self.errors.push(ParseError {
+                message: "expected right brace".to_string(),
+                span: self.lexer.last_span.clone(),
+            });
         }
 
-        Stmt::Block(stmts)
+        Ok(Stmt::Block(stmts))
     }
 
-    fn parse_if_stmt(&mut self) -> Stmt {
+    fn parse_if_stmt(&mut self) -> ParseResult<Stmt> {
         self.lexer.next();
 
         self.lexer.consume_whitespace(self.is_multiline);
-        let cond = self.parse_expr(0);
+        let cond = self.parse_expr(0)?;
 
         self.lexer.consume_whitespace(self.is_multiline);
-        let then_do = Box::new(self.parse_block());
+        let then_do = Box::new(self.parse_block()?);
 
         self.lexer.consume_whitespace(self.is_multiline);
         let else_do = if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Else, ..})) {
@@ -181,9 +212,9 @@ impl Parser {
             self.lexer.consume_whitespace(self.is_multiline);
 
             let else_do = if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::If, ..})) {
-                self.parse_if_stmt()
+                self.parse_if_stmt()?
             } else {
-                self.parse_block()
+                self.parse_block()?
             };
 
             Some(Box::new(else_do))
@@ -191,18 +222,18 @@ impl Parser {
             None
         };
 
-        Stmt::If {
+        Ok(Stmt::If {
             cond,
             then_do,
             else_do,
-        }
+        })
     }
 
-    fn parse_for_stmt(&mut self) -> Stmt {
-        self.lexer.next();
+    fn parse_for_stmt(&mut self) -> ParseResult<Stmt> {
+        let span = self.lexer.next().unwrap().span;
 
         self.lexer.consume_whitespace(self.is_multiline);
-        let lvar = self.must_identifier();
+        let lvar = self.parse_pattern()?;
 
         self.lexer.consume_whitespace(self.is_multiline);
 
@@ -211,79 +242,171 @@ impl Parser {
         if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Comma, ..})) {
             self.lexer.next();
             self.lexer.consume_whitespace(self.is_multiline);
-            rvar = Some(self.must_identifier());
+            rvar = Some(self.parse_pattern()?);
         }
 
         self.lexer.consume_whitespace(self.is_multiline);
         if !matches!(self.lexer.next(), Some(Token{kind: TokenKind::In, ..})) {
-            panic!("expected in");
+            return self.error("expected in");
         }
 
         self.lexer.consume_whitespace(self.is_multiline);
-        let iterated = self.parse_expr(0);
+        let iterated = self.parse_expr(0)?;
 
         self.lexer.consume_whitespace(self.is_multiline);
-        let each_do = self.parse_block();
+        let each_do = self.parse_block()?;
 
-        Stmt::For {
+        Ok(Stmt::For {
             lvar,
             rvar,
             iterated,
             each_do: Box::new(each_do),
-        }
+            span,
+        })
     }
 
-    fn parse_while_stmt(&mut self) -> Stmt {
+    fn parse_while_stmt(&mut self) -> ParseResult<Stmt> {
         self.lexer.next();
 
         self.lexer.consume_whitespace(self.is_multiline);
-        let cond = self.parse_expr(0);
+        let cond = self.parse_expr(0)?;
 
         self.lexer.consume_whitespace(self.is_multiline);
-        let then_do = self.parse_block();
+        let then_do = self.parse_block()?;
 
-        Stmt::While {
+        Ok(Stmt::While {
             cond,
             then_do: Box::new(then_do),
-        }
+        })
     }
 
-    fn parse_fn_stmt(&mut self) -> Stmt {
-        self.lexer.next();
+    fn parse_fn_stmt(&mut self) -> ParseResult<Stmt> {
+        let line = self.lexer.next().unwrap().span.line;
+        let doc = self.doc_comment(line);
 
         self.lexer.consume_whitespace(self.is_multiline);
-        let name = self.must_identifier();
+        let name = self.must_identifier()?;
 
         self.lexer.consume_whitespace(self.is_multiline);
 
-        let (params, body) = match self.continue_parse_fn() {
-            Func::User { params, body, .. } => (params, body),
-            _ => unreachable!(),
-        };
+        let (params, rest) = self.parse_params()?;
+        self.lexer.consume_whitespace(self.is_multiline);
+
+        let deps = self.parse_deps()?;
+        self.lexer.consume_whitespace(self.is_multiline);
+
+        let body = self.parse_block()?;
 
         let func = Func::User {
             name: Some(name),
             params,
-            body,
+            rest,
+            body: Box::new(body),
+            doc,
+            deps,
             captured_env: None,
         };
 
-        Stmt::Func(func)
+        Ok(Stmt::Func(func))
     }
 
-    fn parse_return(&mut self) -> Stmt {
+    /// Parses the functions that a function needs to run first, as in `fn deploy() needs build, test {}`.
+    fn parse_deps(&mut self) -> ParseResult<Vec<String>> {
+        let mut deps = Vec::new();
+
+        if !matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Identifier(word), .. }) if word == "needs") {
+            return Ok(deps);
+        }
         self.lexer.next();
 
+        loop {
+            self.lexer.consume_whitespace(self.is_multiline);
+            deps.push(self.must_identifier()?);
+            self.lexer.consume_whitespace(self.is_multiline);
+
+            if !matches!(self.lexer.peek(), Some(Token { kind: TokenKind::Comma, .. })) {
+                return Ok(deps);
+            }
+            self.lexer.next();
+        }
+    }
+
+    /// Joins the comments on the lines right above `line` that have nothing else on their line.
+    fn doc_comment(&self, line: usize) -> Option<Box<str>> {
+        let comments = self.lexer.comments();
+
+        let mut block = comments.iter().rev()
+            .skip_while(|comment| comment.span.line >= line)
+            .zip((1..line).rev())
+            .take_while(|(comment, line)| comment.is_own_line && comment.span.line == *line)
+            .map(|(comment, _)| comment.text.strip_prefix(' ').unwrap_or(&comment.text).trim_end())
+            .collect::<Vec<&str>>();
+
+        if block.is_empty() {
+            return None;
+        }
+
+        block.reverse();
+        Some(block.join("\n").into())
+    }
+
+    fn parse_try_stmt(&mut self) -> ParseResult<Stmt> {
+        self.lexer.next();
+
+        self.lexer.consume_whitespace(self.is_multiline);
+        let try_do = Box::new(self.parse_block()?);
+
+        self.lexer.consume_whitespace(self.is_multiline);
+
+        let mut err_var = None;
+        let mut catch_do = None;
+
+        if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Catch, ..})) {
+            self.lexer.next();
+            self.lexer.consume_whitespace(self.is_multiline);
+
+            if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Identifier(..), ..})) {
+                err_var = Some(self.must_identifier()?);
+                self.lexer.consume_whitespace(self.is_multiline);
+            }
+
+            catch_do = Some(Box::new(self.parse_block()?));
+            self.lexer.consume_whitespace(self.is_multiline);
+        }
+
+        let finally_do = if matches!(self.lexer.peek(), Some(Token{kind: TokenKind::Finally, ..})) {
+            self.lexer.next();
+            self.lexer.consume_whitespace(self.is_multiline);
+            Some(Box::new(self.parse_block()?))
+        } else {
+            None
+        };
+
+        if catch_do.is_none() && finally_do.is_none() {
+            return self.error_at_peeked("expected catch or finally");
+        }
+
+        Ok(Stmt::Try {
+            try_do,
+            err_var,
+            catch_do,
+            finally_do,
+        })
+    }
+
+    fn parse_return(&mut self) -> ParseResult<Stmt> {
+        let span = self.lexer.next().unwrap().span;
+
         self.lexer.consume_whitespace(false);
 
         // Because of this you can't write `fn f() {return let x = 2}` because it will try to parse `let x = 2` as an
         // expression. But why would you execute a statement after a return anyway? The only think you would ever put
         // after a return are: an expression to return, a right brace to close a block or a newline.
         if matches!(self.lexer.peek(), None | Some(Token {kind: TokenKind::Newline | TokenKind::RightBrace, ..})) {
-            return Stmt::Return(None);
+            return Ok(Stmt::Return(None, span));
         }
 
-        let expr = self.parse_expr(0);
-        Stmt::Return(Some(expr))
+        let expr = self.parse_expr(0)?;
+        Ok(Stmt::Return(Some(expr), span))
     }
 }
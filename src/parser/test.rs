@@ -1,23 +1,53 @@
 use std::collections::HashMap;
+use std::fmt::Debug;
 
-use crate::ast::{BinaryOp, Cmd, CmdOp, Expr};
+use regex::Regex;
+
+use crate::ast::{BinaryOp, Cmd, CmdOp, Expr, MatchArm, Pattern, UnaryOp};
 use crate::interp::Func;
 use crate::interp::Value;
 use crate::lexer::new as new_lexer;
+use crate::source::{Source, Span};
 
 use super::*;
 
+// Trees are compared by shape, leaving out their spans: the tests about spans check them directly
+macro_rules! assert_same_tree {
+    ($left:expr, $right:expr $(,)?) => {
+        assert_eq!(without_spans(&$left), without_spans(&$right))
+    };
+}
+
+fn without_spans<T: Debug>(tree: &T) -> String {
+    // Spans print as `<source name>:<line>:<col>` and the sources of the tests have no name
+    Regex::new(r":\d+:\d+").unwrap().replace_all(&format!("{:#?}", tree), ":_:_").into_owned()
+}
+
 fn make_parser(source: &str) -> Parser {
-    let lexer = new_lexer(source.to_owned());
+    let lexer = new_lexer(Source::new(String::new(), source.to_owned()));
     Parser::new(lexer)
 }
 
 fn parse_expression(source: &str) -> Expr {
-    make_parser(source).parse_expr(0)
+    make_parser(source).parse_expr(0).unwrap()
 }
 
 fn parse(source: &str) -> Vec<Stmt> {
-    make_parser(source).parse()
+    let (prog, errors) = make_parser(source).parse();
+    assert_eq!(errors, vec![]);
+    prog
+}
+
+fn bind(name: &str) -> Pattern {
+    Pattern::Bind(name.to_owned(), Span::default())
+}
+
+fn parse_errs(source: &str) -> Vec<ParseError> {
+    make_parser(source).parse().1
+}
+
+fn parse_err(source: &str) -> ParseError {
+    parse_errs(source).remove(0)
 }
 
 #[test]
@@ -29,208 +59,271 @@ fn parses_literals() {
         ("false", Expr::Literal(Value::Bool(false))),
         ("nil", Expr::Literal(Value::Nil)),
     ] {
-        assert_eq!(parse_expression(source), *want);
+        assert_same_tree!(parse_expression(source), *want);
     }
 }
 
 #[test]
 fn parses_lookups() {
     for (source, want) in &[
-        ("foo", Expr::Get("foo".to_owned())),
+        ("foo", Expr::Get("foo".to_owned(), Span::default())),
         ("foo.bar", Expr::GetField {
-            base: Box::new(Expr::Get("foo".to_owned())),
+            base: Box::new(Expr::Get("foo".to_owned(), Span::default())),
             index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
+            span: Span::default(),
         }),
         ("foo[\"bar\"]", Expr::GetField {
-            base: Box::new(Expr::Get("foo".to_owned())),
+            base: Box::new(Expr::Get("foo".to_owned(), Span::default())),
             index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
+            span: Span::default(),
         }),
         ("foo[1]", Expr::GetField {
-            base: Box::new(Expr::Get("foo".to_owned())),
-            index: Box::new(Expr::Literal(Value::Num(1.0))),
+            base: Box::new(Expr::Get("foo".to_owned(), Span::default())),
+            index: Box::new(Expr::Literal(Value::Int(1))),
+            span: Span::default(),
         }),
         ("foo.bar[\"baz\"].egg[\"beam\"]", Expr::GetField {
             base: Box::new(Expr::GetField {
                 base: Box::new(Expr::GetField {
                     base: Box::new(Expr::GetField {
-                        base: Box::new(Expr::Get("foo".to_owned())),
+                        base: Box::new(Expr::Get("foo".to_owned(), Span::default())),
                         index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
+                        span: Span::default(),
                     }),
                     index: Box::new(Expr::Literal(Value::String("baz".to_owned()))),
+                    span: Span::default(),
                 }),
                 index: Box::new(Expr::Literal(Value::String("egg".to_owned()))),
+                span: Span::default(),
             }),
             index: Box::new(Expr::Literal(Value::String("beam".to_owned()))),
+            span: Span::default(),
         }),
     ] {
-        assert_eq!(parse_expression(source), *want);
+        assert_same_tree!(parse_expression(source), *want);
     }
 }
 
 #[test]
 fn parses_assignments() {
     for (source, want) in &[
-        ("foo=5", Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Num(5.0))))),
+        ("foo=5", Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Int(5))), Span::default())),
         ("foo.bar=5", Expr::SetField {
-            base: Box::new(Expr::Get("foo".to_owned())),
+            base: Box::new(Expr::Get("foo".to_owned(), Span::default())),
             index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
-            expr: Box::new(Expr::Literal(Value::Num(5.0))),
+            expr: Box::new(Expr::Literal(Value::Int(5))),
+            span: Span::default(),
         }),
         ("foo[\"bar\"]=5", Expr::SetField {
-            base: Box::new(Expr::Get("foo".to_owned())),
+            base: Box::new(Expr::Get("foo".to_owned(), Span::default())),
             index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
-            expr: Box::new(Expr::Literal(Value::Num(5.0))),
+            expr: Box::new(Expr::Literal(Value::Int(5))),
+            span: Span::default(),
         }),
         ("foo[1]=5", Expr::SetField {
-            base: Box::new(Expr::Get("foo".to_owned())),
-            index: Box::new(Expr::Literal(Value::Num(1.0))),
-            expr: Box::new(Expr::Literal(Value::Num(5.0))),
+            base: Box::new(Expr::Get("foo".to_owned(), Span::default())),
+            index: Box::new(Expr::Literal(Value::Int(1))),
+            expr: Box::new(Expr::Literal(Value::Int(5))),
+            span: Span::default(),
         }),
         ("foo.bar[\"baz\"].egg[\"beam\"]=5", Expr::SetField {
             base: Box::new(Expr::GetField {
                 base: Box::new(Expr::GetField {
                     base: Box::new(Expr::GetField {
-                        base: Box::new(Expr::Get("foo".to_owned())),
+                        base: Box::new(Expr::Get("foo".to_owned(), Span::default())),
                         index: Box::new(Expr::Literal(Value::String("bar".to_owned()))),
+                        span: Span::default(),
                     }),
                     index: Box::new(Expr::Literal(Value::String("baz".to_owned()))),
+                    span: Span::default(),
                 }),
                 index: Box::new(Expr::Literal(Value::String("egg".to_owned()))),
+                span: Span::default(),
             }),
             index: Box::new(Expr::Literal(Value::String("beam".to_owned()))),
-            expr: Box::new(Expr::Literal(Value::Num(5.0))),
+            expr: Box::new(Expr::Literal(Value::Int(5))),
+            span: Span::default(),
         }),
     ] {
-        assert_eq!(parse_expression(source), *want);
+        assert_same_tree!(parse_expression(source), *want);
     }
 }
 
 #[test]
 fn parses_precedence() {
-    assert_eq!(parse_expression("1+2*3"), Expr::Binary(
-        Box::new(Expr::Literal(Value::Num(1.0))),
+    assert_same_tree!(parse_expression("1+2*3"), Expr::Binary(
+        Box::new(Expr::Literal(Value::Int(1))),
         BinaryOp::Sum,
         Box::new(Expr::Binary(
-            Box::new(Expr::Literal(Value::Num(2.0))),
+            Box::new(Expr::Literal(Value::Int(2))),
             BinaryOp::Mul,
-            Box::new(Expr::Literal(Value::Num(3.0))),
+            Box::new(Expr::Literal(Value::Int(3))),
+            Span::default(),
         )),
+        Span::default(),
+    ));
+}
+
+#[test]
+fn parses_bitwise_precedence() {
+    let int = |int| Box::new(Expr::Literal(Value::Int(int)));
+    let binary = |lhs, op, rhs| Box::new(Expr::Binary(lhs, op, rhs, Span::default()));
+
+    // Shifts bind tighter than `&`, then `^`, then `|`, and all of them tighter than comparisons
+    assert_same_tree!(parse_expression("1 | 2 ^ 3 & 4 << 1 == 5"), *binary(
+        binary(int(1), BinaryOp::BitOr, binary(int(2), BinaryOp::BitXor, binary(int(3), BinaryOp::BitAnd, binary(int(4), BinaryOp::Shl, int(1))))),
+        BinaryOp::Equal,
+        int(5),
     ));
+
+    assert_same_tree!(parse_expression("2 ** 3 ** 2 // 4"), *binary(
+        binary(int(2), BinaryOp::Pow, binary(int(3), BinaryOp::Pow, int(2))),
+        BinaryOp::IntDiv,
+        int(4),
+    ));
+
+    assert_same_tree!(parse("mode |= 0o111"), vec![Stmt::Expr(Expr::Set(
+        "mode".to_owned(),
+        binary(Box::new(Expr::Get("mode".to_owned(), Span::default())), BinaryOp::BitOr, int(0o111)),
+        Span::default(),
+    ))]);
 }
 
 #[test]
 fn parses_associativity() {
-    assert_eq!(parse_expression("1+2+3"), Expr::Binary(
+    assert_same_tree!(parse_expression("1+2+3"), Expr::Binary(
         Box::new(Expr::Binary(
-            Box::new(Expr::Literal(Value::Num(1.0))),
+            Box::new(Expr::Literal(Value::Int(1))),
             BinaryOp::Sum,
-            Box::new(Expr::Literal(Value::Num(2.0))),
+            Box::new(Expr::Literal(Value::Int(2))),
+            Span::default(),
         )),
         BinaryOp::Sum,
-        Box::new(Expr::Literal(Value::Num(3.0))),
+        Box::new(Expr::Literal(Value::Int(3))),
+        Span::default(),
     ));
 }
 
 #[test]
 fn parses_cmd_stmt() {
-    assert_eq!(parse("cmd1\ncmd2"), vec![
-        Stmt::Cmd(Cmd::Atom(vec![vec![Expr::Literal(Value::String("cmd1".to_owned()))]])),
-        Stmt::Cmd(Cmd::Atom(vec![vec![Expr::Literal(Value::String("cmd2".to_owned()))]])),
+    assert_same_tree!(parse("cmd1\ncmd2"), vec![
+        Stmt::Cmd(Cmd::Atom(vec![vec![Expr::Literal(Value::String("cmd1".to_owned()))]], Span::default())),
+        Stmt::Cmd(Cmd::Atom(vec![vec![Expr::Literal(Value::String("cmd2".to_owned()))]], Span::default())),
+    ]);
+}
+
+#[test]
+fn parses_large_int_as_cmd_arg() {
+    assert_same_tree!(parse("echo 123456789012345678901234"), vec![
+        Stmt::Cmd(Cmd::Atom(vec![
+            vec![Expr::Literal(Value::String("echo".to_owned()))],
+            vec![Expr::Literal(Value::String("123456789012345678901234".to_owned()))],
+        ], Span::default())),
     ]);
+
+    assert_eq!(parse_err("let x = 123456789012345678901234").message, "integer literal is too large");
 }
 
 #[test]
 fn parses_assignment_stmt() {
-    assert_eq!(parse("foo = \n    1"), vec![
-        Stmt::Expr(Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Num(1.0)))))
+    assert_same_tree!(parse("foo = \n    1"), vec![
+        Stmt::Expr(Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Int(1))), Span::default()))
     ]);
 }
 
 #[test]
 fn parses_call_stmt() {
-    assert_eq!(parse("my_fn(\n    \"foo\",\n    \"bar\"\n)"), vec![
+    assert_same_tree!(parse("my_fn(\n    \"foo\",\n    \"bar\"\n)"), vec![
         Stmt::Expr(Expr::Call {
-            func: Box::new(Expr::Get("my_fn".to_owned())),
+            func: Box::new(Expr::Get("my_fn".to_owned(), Span::default())),
             args: vec![
                 Expr::Literal(Value::String("foo".to_owned())),
                 Expr::Literal(Value::String("bar".to_owned())),
             ],
+            named: vec![],
+            span: Span::default(),
         }),
     ]);
 }
 
 #[test]
 fn parses_cmd_stmt_with_dot() {
-    assert_eq!(parse("cmd.exe"), vec![
+    assert_same_tree!(parse("cmd.exe"), vec![
         Stmt::Cmd(Cmd::Atom(vec![vec![
             Expr::Literal(Value::String("cmd".to_owned())),
             Expr::Literal(Value::String(".".to_owned())),
             Expr::Literal(Value::String("exe".to_owned())),
-        ]])),
+        ]], Span::default())),
     ]);
 }
 
 #[test]
 fn parses_assignment_stmt_with_dots() {
-    assert_eq!(parse("x.foo = 1"), vec![
+    assert_same_tree!(parse("x.foo = 1"), vec![
         Stmt::Expr(Expr::SetField {
-            base: Box::new(Expr::Get("x".to_owned())),
+            base: Box::new(Expr::Get("x".to_owned(), Span::default())),
             index: Box::new(Expr::Literal(Value::String("foo".to_owned()))),
-            expr: Box::new(Expr::Literal(Value::Num(1.0))),
+            expr: Box::new(Expr::Literal(Value::Int(1))),
+            span: Span::default(),
         }),
     ]);
 }
 
 #[test]
 fn parses_call_stmt_with_dots() {
-    assert_eq!(parse("x.foo()"), vec![
+    assert_same_tree!(parse("x.foo()"), vec![
         Stmt::Expr(Expr::Call {
             func: Box::new(Expr::GetField {
-                base: Box::new(Expr::Get("x".to_owned())),
+                base: Box::new(Expr::Get("x".to_owned(), Span::default())),
                 index: Box::new(Expr::Literal(Value::String("foo".to_owned()))),
+                span: Span::default(),
             }),
             args: vec![],
+            named: vec![],
+            span: Span::default(),
         })
     ]);
 }
 
 #[test]
 fn parses_incorrect_expr_stmt_with_dots() {
-    assert_eq!(parse("x.foo\n=2"), vec![
+    assert_same_tree!(parse("x.foo\n=2"), vec![
         Stmt::Cmd(Cmd::Atom(vec![vec![
             Expr::Literal(Value::String("x".to_owned())),
             Expr::Literal(Value::String(".".to_owned())),
             Expr::Literal(Value::String("foo".to_owned())),
-        ]])),
+        ]], Span::default())),
         Stmt::Cmd(Cmd::Atom(vec![vec![
             Expr::Literal(Value::String("=".to_owned())),
             Expr::Literal(Value::String("2".to_owned())),
-        ]])),
+        ]], Span::default())),
     ]);
 }
 
 #[test]
 fn parses_expr_stmt_continuation() {
-    assert_eq!(parse("foo() foo\n=1"), vec![
+    assert_same_tree!(parse("foo() foo\n=1"), vec![
         Stmt::Expr(Expr::Call {
-            func: Box::new(Expr::Get("foo".to_owned())),
+            func: Box::new(Expr::Get("foo".to_owned(), Span::default())),
             args: vec![],
+            named: vec![],
+            span: Span::default(),
         }),
-        Stmt::Expr(Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Num(1.0))))),
+        Stmt::Expr(Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Int(1))), Span::default())),
     ]);
 }
 
 #[test]
 fn parses_explicit_cmd_stmt() {
-    assert_eq!(parse("foo = 1"), vec![
-        Stmt::Expr(Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Num(1.0))))),
+    assert_same_tree!(parse("foo = 1"), vec![
+        Stmt::Expr(Expr::Set("foo".to_owned(), Box::new(Expr::Literal(Value::Int(1))), Span::default())),
     ]);
-    assert_eq!(parse("$ foo = 1"), vec![
+    assert_same_tree!(parse("$ foo = 1"), vec![
         Stmt::Cmd(Cmd::Atom(vec![
             vec![Expr::Literal(Value::String("foo".to_owned()))],
             vec![Expr::Literal(Value::String("=".to_owned()))],
             vec![Expr::Literal(Value::String("1".to_owned()))],
-        ])),
+        ], Span::default())),
     ]);
 }
 
@@ -242,49 +335,52 @@ fn panics_instead_of_cmd_fallback() {
 
 #[test]
 fn parses_var_decl() {
-    assert_eq!(parse("let foo"), vec![
+    assert_same_tree!(parse("let foo"), vec![
         Stmt::Let {
             init: None,
             is_exp: false,
-            name: "foo".to_owned(),
+            pattern: bind("foo"),
+            span: Span::default(),
         }
     ]);
 
-    assert_eq!(parse("let foo = 1"), vec![
+    assert_same_tree!(parse("let foo = 1"), vec![
         Stmt::Let {
-            init: Some(Expr::Literal(Value::Num(1.0))),
+            init: Some(Expr::Literal(Value::Int(1))),
             is_exp: false,
-            name: "foo".to_owned(),
+            pattern: bind("foo"),
+            span: Span::default(),
         }
     ]);
 
-    assert_eq!(parse("exp let foo"), vec![
+    assert_same_tree!(parse("exp let foo"), vec![
         Stmt::Let {
             init: None,
             is_exp: true,
-            name: "foo".to_owned(),
+            pattern: bind("foo"),
+            span: Span::default(),
         }
     ]);
 }
 
 #[test]
 fn parses_cmd_expr() {
-    assert_eq!(
+    assert_same_tree!(
         parse_expression("$(foo)"),
-        Expr::Cmd(Cmd::Atom(vec![vec![Expr::Literal(Value::String("foo".to_owned()))]])),
+        Expr::Cmd(Cmd::Atom(vec![vec![Expr::Literal(Value::String("foo".to_owned()))]], Span::default())),
     );
 }
 
 #[test]
 fn parses_parenthesized_cmd() {
-    assert_eq!(parse(" foo && ( bar || baz ) "), vec![
+    assert_same_tree!(parse(" foo && ( bar || baz ) "), vec![
         Stmt::Cmd(Cmd::Op(
-            Box::new(Cmd::Atom(vec![vec![Expr::Literal(Value::String("foo".to_owned()))]])),
+            Box::new(Cmd::Atom(vec![vec![Expr::Literal(Value::String("foo".to_owned()))]], Span::default())),
             CmdOp::And,
             Box::new(Cmd::Op(
-                Box::new(Cmd::Atom(vec![vec![Expr::Literal(Value::String("bar".to_owned()))]])),
+                Box::new(Cmd::Atom(vec![vec![Expr::Literal(Value::String("bar".to_owned()))]], Span::default())),
                 CmdOp::Or,
-                Box::new(Cmd::Atom(vec![vec![Expr::Literal(Value::String("baz".to_owned()))]])),
+                Box::new(Cmd::Atom(vec![vec![Expr::Literal(Value::String("baz".to_owned()))]], Span::default())),
             )),
         ))
     ]);
@@ -292,39 +388,39 @@ fn parses_parenthesized_cmd() {
 
 #[test]
 fn parses_cmd_expr_stmt() {
-    assert_eq!(parse("$(\n    foo\n    bar\n)"), vec![
+    assert_same_tree!(parse("$(\n    foo\n    bar\n)"), vec![
         Stmt::Cmd(Cmd::Atom(vec![
             vec![Expr::Literal(Value::String("foo".to_owned()))],
             vec![Expr::Literal(Value::String("bar".to_owned()))],
-        ])),
+        ], Span::default())),
     ]);
 }
 
 #[test]
 fn parses_cmd_with_interpolation() {
-    assert_eq!(parse("a{1}b a{1} {1}b"), vec![
+    assert_same_tree!(parse("a{1}b a{1} {1}b"), vec![
         Stmt::Cmd(Cmd::Atom(vec![
             vec![
                 Expr::Literal(Value::String("a".to_owned())),
-                Expr::Literal(Value::Num(1.0)),
+                Expr::Literal(Value::Int(1)),
                 Expr::Literal(Value::String("b".to_owned())),
             ],
             vec![
                 Expr::Literal(Value::String("a".to_owned())),
-                Expr::Literal(Value::Num(1.0)),
+                Expr::Literal(Value::Int(1)),
             ],
             vec![
-                Expr::Literal(Value::Num(1.0)),
+                Expr::Literal(Value::Int(1)),
                 Expr::Literal(Value::String("b".to_owned())),
             ],
-        ])),
+        ], Span::default())),
     ]);
 }
 
 #[test]
 fn parses_cmd_with_escaping() {
     // a"b"c{"d"}e
-    assert_eq!(parse("a\"b\"c\"{\"d\"}\"e"), vec![
+    assert_same_tree!(parse("a\"b\"c\"{\"d\"}\"e"), vec![
         Stmt::Cmd(Cmd::Atom(vec![vec![
             Expr::Literal(Value::String("a".to_owned())),
             Expr::Literal(Value::String("b".to_owned())),
@@ -334,46 +430,46 @@ fn parses_cmd_with_escaping() {
                 exprs: vec![Expr::Literal(Value::String("d".to_owned()))],
             },
             Expr::Literal(Value::String("e".to_owned())),
-        ]]))
+        ]], Span::default()))
     ]);
 }
 
 #[test]
 fn parses_return() {
-    assert_eq!(parse("return"), vec![
-        Stmt::Return(None),
+    assert_same_tree!(parse("return"), vec![
+        Stmt::Return(None, Span::default()),
     ]);
 
-    assert_eq!(parse("return 1"), vec![
-        Stmt::Return(Some(Expr::Literal(Value::Num(1.0)))),
+    assert_same_tree!(parse("return 1"), vec![
+        Stmt::Return(Some(Expr::Literal(Value::Int(1))), Span::default()),
     ]);
 
-    assert_eq!(parse("return\n1"), vec![
-        Stmt::Return(None),
+    assert_same_tree!(parse("return\n1"), vec![
+        Stmt::Return(None, Span::default()),
         Stmt::Cmd(Cmd::Atom(vec![vec![
             Expr::Literal(Value::String("1".to_owned())),
-        ]])),
+        ]], Span::default())),
     ]);
 }
 
 #[test]
 fn parses_continue() {
-    assert_eq!(parse("continue"), vec![
-        Stmt::Continue,
+    assert_same_tree!(parse("continue"), vec![
+        Stmt::Continue(Span::default()),
     ]);
 }
 
 #[test]
 fn parses_break() {
-    assert_eq!(parse("break"), vec![
-        Stmt::Break,
+    assert_same_tree!(parse("break"), vec![
+        Stmt::Break(Span::default()),
     ]);
 }
 
 #[test]
 fn parses_import() {
-    assert_eq!(parse("import \"foobar\""), vec![
-        Stmt::Import("foobar".to_owned()),
+    assert_same_tree!(parse("import \"foobar\""), vec![
+        Stmt::Import("foobar".to_owned(), Span::default()),
     ]);
 }
 
@@ -385,13 +481,13 @@ fn panics_on_import_interpolation() {
 
 #[test]
 fn parses_if() {
-    assert_eq!(parse("if true {\ncmd_if_true\n}"), vec![
+    assert_same_tree!(parse("if true {\ncmd_if_true\n}"), vec![
         Stmt::If {
             cond: Expr::Literal(Value::Bool(true)),
             then_do: Box::new(Stmt::Block(vec![
                 Stmt::Cmd(Cmd::Atom(vec![vec![
                     Expr::Literal(Value::String("cmd_if_true".to_owned())),
-                ]])),
+                ]], Span::default())),
             ])),
             else_do: None,
         }
@@ -400,18 +496,18 @@ fn parses_if() {
 
 #[test]
 fn parses_if_with_else() {
-    assert_eq!(parse("if true {\ncmd_if_true\n} else {\ncmd_if_false\n}"), vec![
+    assert_same_tree!(parse("if true {\ncmd_if_true\n} else {\ncmd_if_false\n}"), vec![
         Stmt::If {
             cond: Expr::Literal(Value::Bool(true)),
             then_do: Box::new(Stmt::Block(vec![
                 Stmt::Cmd(Cmd::Atom(vec![vec![
                     Expr::Literal(Value::String("cmd_if_true".to_owned())),
-                ]])),
+                ]], Span::default())),
             ])),
             else_do: Some(Box::new(Stmt::Block(vec![
                 Stmt::Cmd(Cmd::Atom(vec![vec![
                     Expr::Literal(Value::String("cmd_if_false".to_owned())),
-                ]])),
+                ]], Span::default())),
             ]))),
         }
     ]);
@@ -419,25 +515,25 @@ fn parses_if_with_else() {
 
 #[test]
 fn parses_if_with_else_and_else_if() {
-    assert_eq!(parse("if true {\ncmd_a\n} else if false {\ncmd_b\n} else {\ncmd_c\n}"), vec![
+    assert_same_tree!(parse("if true {\ncmd_a\n} else if false {\ncmd_b\n} else {\ncmd_c\n}"), vec![
         Stmt::If {
             cond: Expr::Literal(Value::Bool(true)),
             then_do: Box::new(Stmt::Block(vec![
                 Stmt::Cmd(Cmd::Atom(vec![vec![
                     Expr::Literal(Value::String("cmd_a".to_owned())),
-                ]])),
+                ]], Span::default())),
             ])),
             else_do: Some(Box::new(Stmt::If {
                 cond: Expr::Literal(Value::Bool(false)),
                 then_do: Box::new(Stmt::Block(vec![
                     Stmt::Cmd(Cmd::Atom(vec![vec![
                         Expr::Literal(Value::String("cmd_b".to_owned())),
-                    ]])),
+                    ]], Span::default())),
                 ])),
                 else_do: Some(Box::new(Stmt::Block(vec![
                     Stmt::Cmd(Cmd::Atom(vec![vec![
                         Expr::Literal(Value::String("cmd_c".to_owned())),
-                    ]])),
+                    ]], Span::default())),
                 ]))),
             })),
         }
@@ -446,31 +542,33 @@ fn parses_if_with_else_and_else_if() {
 
 #[test]
 fn parses_for() {
-    assert_eq!(parse("for \n i \n \n in \n foo \n {}"), vec![
+    assert_same_tree!(parse("for \n i \n \n in \n foo \n {}"), vec![
         Stmt::For {
-            lvar: "i".to_owned(),
+            lvar: bind("i"),
             rvar: None,
-            iterated: Expr::Get("foo".to_owned()),
+            iterated: Expr::Get("foo".to_owned(), Span::default()),
             each_do: Box::new(Stmt::Block(vec![])),
+            span: Span::default(),
         }
     ]);
 }
 
 #[test]
 fn parses_foreach() {
-    assert_eq!(parse("for \n x \n , \n y \n in \n foo \n {}"), vec![
+    assert_same_tree!(parse("for \n x \n , \n y \n in \n foo \n {}"), vec![
         Stmt::For {
-            lvar: "x".to_owned(),
-            rvar: Some("y".to_owned()),
-            iterated: Expr::Get("foo".to_owned()),
+            lvar: bind("x"),
+            rvar: Some(bind("y")),
+            iterated: Expr::Get("foo".to_owned(), Span::default()),
             each_do: Box::new(Stmt::Block(vec![])),
+            span: Span::default(),
         }
     ]);
 }
 
 #[test]
 fn parses_while() {
-    assert_eq!(parse("while \n true \n { \n }"), vec![
+    assert_same_tree!(parse("while \n true \n { \n }"), vec![
         Stmt::While {
             cond: Expr::Literal(Value::Bool(true)),
             then_do: Box::new(Stmt::Block(vec![])),
@@ -478,13 +576,167 @@ fn parses_while() {
     ]);
 }
 
+#[test]
+fn parses_try() {
+    assert_same_tree!(parse("try {} \n catch err \n {} finally {}"), vec![
+        Stmt::Try {
+            try_do: Box::new(Stmt::Block(vec![])),
+            err_var: Some("err".to_owned()),
+            catch_do: Some(Box::new(Stmt::Block(vec![]))),
+            finally_do: Some(Box::new(Stmt::Block(vec![]))),
+        }
+    ]);
+
+    assert_same_tree!(parse("try {} catch {}"), vec![
+        Stmt::Try {
+            try_do: Box::new(Stmt::Block(vec![])),
+            err_var: None,
+            catch_do: Some(Box::new(Stmt::Block(vec![]))),
+            finally_do: None,
+        }
+    ]);
+
+    assert_eq!(parse_err("try {}").message, "expected catch or finally");
+}
+
+#[test]
+fn parses_match() {
+    let source = "match x {
+    'a' | -1 => f()
+    0..=9 => {}
+    re 'v(?P<v>.)' => {}
+    [first, ...rest] => {}
+    {name, os: _} => {}
+}";
+
+    let call = Stmt::Expr(Expr::Call {
+        func: Box::new(Expr::Get("f".to_owned(), Span::default())),
+        args: vec![],
+        named: vec![],
+        span: Span::default(),
+    });
+
+    assert_same_tree!(parse(source), vec![
+        Stmt::Match {
+            subject: Expr::Get("x".to_owned(), Span::default()),
+            arms: vec![
+                MatchArm {
+                    pattern: Pattern::Or(vec![
+                        Pattern::Value(Expr::Literal(Value::String("a".to_owned()))),
+                        Pattern::Value(Expr::Literal(Value::Int(-1))),
+                    ]),
+                    body: call,
+                },
+                MatchArm {
+                    pattern: Pattern::Range { l: Box::new(Value::Int(0)), r: Box::new(Value::Int(9)), inclusive: true },
+                    body: Stmt::Block(vec![]),
+                },
+                MatchArm {
+                    pattern: Pattern::Regex("v(?P<v>.)".to_owned()),
+                    body: Stmt::Block(vec![]),
+                },
+                MatchArm {
+                    pattern: Pattern::Vec {
+                        items: vec![bind("first")],
+                        rest: Some((1, Some("rest".to_owned()))),
+                    },
+                    body: Stmt::Block(vec![]),
+                },
+                MatchArm {
+                    pattern: Pattern::Dict(vec![
+                        ("name".to_owned(), bind("name")),
+                        ("os".to_owned(), Pattern::Any),
+                    ]),
+                    body: Stmt::Block(vec![]),
+                },
+            ],
+        }
+    ]);
+
+    assert_eq!(parse_err("match x {\n    re '(' => {}\n}").message.lines().next(), Some("invalid regex: regex parse error:"));
+    assert_eq!(parse_err("match x {\n    1 {}\n}").message, "expected => after pattern");
+}
+
+#[test]
+fn parses_params_with_defaults_and_rest() {
+    match &parse("fn f(a, b = 1, ...rest) {}")[0] {
+        Stmt::Func(Func::User { params, rest, .. }) => {
+            assert_same_tree!(params, &vec![
+                bind("a"),
+                Pattern::Default {
+                    pattern: Box::new(bind("b")),
+                    default: Expr::Literal(Value::Int(1)),
+                    span: Span::default(),
+                },
+            ]);
+            assert_same_tree!(rest, &Some(Box::new(bind("rest"))));
+        }
+        _ => unreachable!(),
+    }
+
+    assert_eq!(parse_err("fn f(...a, b) {}").message, "the rest parameter must be the last one");
+    assert_eq!(parse_err("fn f(a = 1, b) {}").message, "parameters without a default can't follow ones with a default");
+    assert_eq!(parse_err("fn f(..a) {}").message, "expected ... before the rest parameter");
+}
+
+#[test]
+fn parses_named_args() {
+    assert_same_tree!(parse_expression("deploy('eu', env: 'prod', dry : true)"), Expr::Call {
+        func: Box::new(Expr::Get("deploy".to_owned(), Span::default())),
+        args: vec![Expr::Literal(Value::String("eu".to_owned()))],
+        named: vec![
+            ("env".to_owned(), Expr::Literal(Value::String("prod".to_owned()))),
+            ("dry".to_owned(), Expr::Literal(Value::Bool(true))),
+        ],
+        span: Span::default(),
+    });
+
+    assert_eq!(parse_err("f(a: 1, 2)").message, "positional arguments can't follow named ones");
+    assert_eq!(parse_err("f(a: 1, a: 2)").message, "argument a is given twice");
+}
+
+#[test]
+fn parses_destructuring() {
+    assert_same_tree!(parse("let [a, ...rest, b = 2] = v"), vec![
+        Stmt::Let {
+            is_exp: false,
+            pattern: Pattern::Vec {
+                items: vec![
+                    bind("a"),
+                    Pattern::Default {
+                        pattern: Box::new(bind("b")),
+                        default: Expr::Literal(Value::Int(2)),
+                        span: Span::default(),
+                    },
+                ],
+                rest: Some((1, Some("rest".to_owned()))),
+            },
+            init: Some(Expr::Get("v".to_owned(), Span::default())),
+            span: Span::default(),
+        }
+    ]);
+
+    assert_eq!(parse_err("let {a}").message, "expected = and the value to destructure");
+    assert_eq!(parse_err("let [a, ..rest] = v").message, "expected ... before the rest pattern");
+}
+
+#[test]
+fn parses_throw() {
+    assert_same_tree!(parse("throw 'foo'"), vec![
+        Stmt::Throw(Expr::Literal(Value::String("foo".to_owned())), Span::default()),
+    ]);
+}
+
 #[test]
 fn parses_fn() {
-    assert_eq!(parse("fn foo \n( x , y , z ) \n {}"), vec![
+    assert_same_tree!(parse("fn foo \n( x , y , z ) \n {}"), vec![
         Stmt::Func(Func::User {
             name: Some("foo".to_owned()),
-            params: vec!["x".to_owned(), "y".to_owned(), "z".to_owned()],
+            params: vec![bind("x"), bind("y"), bind("z")],
+            rest: None,
             body: Box::new(Stmt::Block(vec![])),
+            doc: None,
+            deps: vec![],
             captured_env: None,
         })
     ]);
@@ -492,56 +744,92 @@ fn parses_fn() {
 
 #[test]
 fn parses_fn_no_params() {
-    assert_eq!(parse("fn foo \n() \n {}"), vec![
+    assert_same_tree!(parse("fn foo \n() \n {}"), vec![
         Stmt::Func(Func::User {
             name: Some("foo".to_owned()),
             params: vec![],
+            rest: None,
             body: Box::new(Stmt::Block(vec![])),
+            doc: None,
+            deps: vec![],
             captured_env: None,
         })
     ]);
 }
 
+#[test]
+fn parses_fn_deps() {
+    match &parse("fn deploy(env) needs build ,\n test {}")[0] {
+        Stmt::Func(Func::User { params, deps, .. }) => {
+            assert_same_tree!(params, &vec![bind("env")]);
+            assert_eq!(deps, &vec!["build".to_owned(), "test".to_owned()]);
+        }
+        stmt => panic!("expected fn, got {:?}", stmt),
+    }
+
+    assert_eq!(parse_err("fn deploy() needs {}").message, "expected identifier");
+}
+
+#[test]
+fn attaches_comments_above_fn() {
+    let docs = parse("# Builds\n#  everything\nfn a() {}\n\n# unrelated\n\nfn b() {}\nlet x = 1 # trailing\nfn c() {}")
+        .into_iter()
+        .map(|stmt| match stmt {
+            Stmt::Func(Func::User { doc, .. }) => doc,
+            _ => None,
+        })
+        .collect::<Vec<Option<Box<str>>>>();
+
+    assert_eq!(docs, vec![Some("Builds\n everything".into()), None, None, None]);
+}
+
 #[test]
 fn parses_cmd_semicolon() {
-    assert_eq!(parse("cmd1 ; cmd2"), vec![
+    assert_same_tree!(parse("cmd1 ; cmd2"), vec![
         Stmt::Cmd(Cmd::Op(
             Box::new(Cmd::Atom(vec![vec![
                 Expr::Literal(Value::String("cmd1".to_owned())),
-            ]])),
+            ]], Span::default())),
             CmdOp::Seq,
             Box::new(Cmd::Atom(vec![vec![
                 Expr::Literal(Value::String("cmd2".to_owned())),
-            ]])),
+            ]], Span::default())),
         ))
     ]);
 }
 
 #[test]
 fn parses_lambda() {
-    assert_eq!(parse("print(fn(){})"), vec![
+    assert_same_tree!(parse("print(fn(){})"), vec![
         Stmt::Expr(Expr::Call {
-            func: Box::new(Expr::Get("print".to_owned())),
+            func: Box::new(Expr::Get("print".to_owned(), Span::default())),
             args: vec![
                 Expr::Lambda(Func::User {
                     name: None,
                     params: vec![],
+                    rest: None,
                     body: Box::new(Stmt::Block(vec![])),
+                    doc: None,
+                    deps: vec![],
                     captured_env: None,
                 })
             ],
+            named: vec![],
+            span: Span::default(),
         })
     ]);
 }
 
 #[test]
 fn parses_block() {
-    assert_eq!(parse("{nop()}"), vec![
+    assert_same_tree!(parse("{nop()}"), vec![
         Stmt::Block(vec![
             Stmt::Expr(
                 Expr::Call {
-                    func: Box::new(Expr::Get("nop".to_owned())),
+                    func: Box::new(Expr::Get("nop".to_owned(), Span::default())),
                     args: vec![],
+                    named: vec![],
+                    span: Span::default(),
                 }
             )
         ])
@@ -550,77 +838,203 @@ fn parses_block() {
 
 #[test]
 fn parses_range() {
-    assert_eq!(parse_expression("0..5"), Expr::Range {
-        l: Box::new(Expr::Literal(Value::Num(0.0))),
-        r: Box::new(Expr::Literal(Value::Num(5.0))),
+    assert_same_tree!(parse_expression("0..5"), Expr::Range {
+        l: Box::new(Expr::Literal(Value::Int(0))),
+        r: Box::new(Expr::Literal(Value::Int(5))),
         inclusive: false,
+        step: None,
+        span: Span::default(),
     });
 }
 
 #[test]
 fn parses_range_inclusive() {
-    assert_eq!(parse_expression("0..=5"), Expr::Range {
-        l: Box::new(Expr::Literal(Value::Num(0.0))),
-        r: Box::new(Expr::Literal(Value::Num(5.0))),
+    assert_same_tree!(parse_expression("0..=5"), Expr::Range {
+        l: Box::new(Expr::Literal(Value::Int(0))),
+        r: Box::new(Expr::Literal(Value::Int(5))),
         inclusive: true,
+        step: None,
+        span: Span::default(),
     });
 }
 
 #[test]
 fn parses_range_complex_exprs() {
-    assert_eq!(parse_expression("v.len()..=[1,2,3].len()+1"), Expr::Range {
+    assert_same_tree!(parse_expression("v.len()..=[1,2,3].len()+1"), Expr::Range {
         l: Box::new(Expr::Call {
             func: Box::new(Expr::GetField {
-                base: Box::new(Expr::Get("v".to_string())),
+                base: Box::new(Expr::Get("v".to_string(), Span::default())),
                 index: Box::new(Expr::Literal(Value::String("len".to_string()))),
+                span: Span::default(),
             }),
             args: vec![],
+            named: vec![],
+            span: Span::default(),
         }),
         r: Box::new(Expr::Binary(
             Box::new(Expr::Call {
                 func: Box::new(Expr::GetField {
                     base: Box::new(Expr::Vec(vec![
-                        Expr::Literal(Value::Num(1.0)),
-                        Expr::Literal(Value::Num(2.0)),
-                        Expr::Literal(Value::Num(3.0)),
+                        Expr::Literal(Value::Int(1)),
+                        Expr::Literal(Value::Int(2)),
+                        Expr::Literal(Value::Int(3)),
                     ])),
                     index: Box::new(Expr::Literal(Value::String("len".to_string()))),
+                    span: Span::default(),
                 }),
                 args: vec![],
+                named: vec![],
+                span: Span::default(),
             }),
             BinaryOp::Sum,
-            Box::new(Expr::Literal(Value::Num(1.0))),
+            Box::new(Expr::Literal(Value::Int(1))),
+            Span::default(),
         )),
         inclusive: true,
+        step: None,
+        span: Span::default(),
+    });
+}
+
+#[test]
+fn parses_range_with_negative_bounds_and_step() {
+    assert_same_tree!(parse_expression("-3..0 step -1"), Expr::Range {
+        l: Box::new(Expr::Unary(UnaryOp::Neg, Box::new(Expr::Literal(Value::Int(3))), Span::default())),
+        r: Box::new(Expr::Literal(Value::Int(0))),
+        inclusive: false,
+        step: Some(Box::new(Expr::Unary(UnaryOp::Neg, Box::new(Expr::Literal(Value::Int(1))), Span::default()))),
+        span: Span::default(),
     });
 }
 
 #[test]
 fn parses_vec_literal() {
-    assert_eq!(parse_expression("[\n1,\n2\n3,]"), Expr::Vec(vec![
-        Expr::Literal(Value::Num(1.0)),
-        Expr::Literal(Value::Num(2.0)),
-        Expr::Literal(Value::Num(3.0)),
+    assert_same_tree!(parse_expression("[\n1,\n2\n3,]"), Expr::Vec(vec![
+        Expr::Literal(Value::Int(1)),
+        Expr::Literal(Value::Int(2)),
+        Expr::Literal(Value::Int(3)),
     ]));
 }
 
 #[test]
 fn parses_dict_literal() {
     let mut map = HashMap::new();
-    map.insert("a".to_string(), Expr::Literal(Value::Num(1.0)));
-    map.insert("b".to_string(), Expr::Literal(Value::Num(2.0)));
-    map.insert("c".to_string(), Expr::Literal(Value::Num(3.0)));
+    map.insert("a".to_string(), Expr::Literal(Value::Int(1)));
+    map.insert("b".to_string(), Expr::Literal(Value::Int(2)));
+    map.insert("c".to_string(), Expr::Literal(Value::Int(3)));
     assert_eq!(parse_expression("{\na : 1,\nb : 2\nc : 3,}"), Expr::Dict(map));
+
+    let mut map = HashMap::new();
+    map.insert("1".to_string(), Expr::Literal(Value::Int(1)));
+    map.insert("2.5".to_string(), Expr::Literal(Value::Int(2)));
+    assert_eq!(parse_expression("{1: 1, 2.5: 2}"), Expr::Dict(map));
 }
 
 #[test]
 fn parses_call() {
-    assert_eq!(parse_expression("f(\n1,\n2\n3,)"), Expr::Call {
-        func: Box::new(Expr::Get("f".to_string())),
+    assert_same_tree!(parse_expression("f(\n1,\n2\n3,)"), Expr::Call {
+        func: Box::new(Expr::Get("f".to_string(), Span::default())),
         args: vec![
-            Expr::Literal(Value::Num(1.0)),
-            Expr::Literal(Value::Num(2.0)),
-            Expr::Literal(Value::Num(3.0)),
+            Expr::Literal(Value::Int(1)),
+            Expr::Literal(Value::Int(2)),
+            Expr::Literal(Value::Int(3)),
         ],
+        named: vec![],
+        span: Span::default(),
     });
 }
+
+#[test]
+fn reports_error_location() {
+    let err = parse_err("if true {\n    x = (1 + 2\n}");
+    assert_eq!(err.message, "expected right parenthesis");
+    assert_eq!((err.span.line, err.span.col), (3, 1));
+
+    let err = parse_err("let = 2");
+    assert_eq!(err.message, "expected pattern");
+    assert_eq!((err.span.line, err.span.col), (1, 5));
+
+    let err = parse_err("x = 1\n}");
+    assert_eq!(err.message, "unexpected right brace");
+    assert_eq!((err.span.line, err.span.col), (2, 1));
+}
+
+#[test]
+fn reports_all_errors() {
+    let errs = parse_errs("let = 1\nprint(1)\nimport 2\nfor in x {}\n}\nlet y = 3 +");
+    assert_eq!(
+        errs.iter().map(|err| (err.message.as_str(), err.span.line)).collect::<Vec<(&str, usize)>>(),
+        vec![
+            ("expected pattern", 1),
+            ("expected a file to import", 3),
+            ("expected pattern", 4),
+            ("unexpected right brace", 5),
+            ("expected expression, found end of file", 6),
+        ],
+    );
+}
+
+#[test]
+fn recovers_inside_blocks() {
+    let (prog, errs) = make_parser("fn f() {\n    let = 1\n    g()\n}\nf()").parse();
+    assert_eq!(errs.len(), 1);
+    assert_eq!((errs[0].span.line, errs[0].span.col), (2, 9));
+
+    assert_same_tree!(prog, vec![
+        Stmt::Func(Func::User {
+            name: Some("f".to_owned()),
+            params: vec![],
+            rest: None,
+            body: Box::new(Stmt::Block(vec![
+                Stmt::Expr(Expr::Call {
+                    func: Box::new(Expr::Get("g".to_owned(), Span::default())),
+                    args: vec![],
+                    named: vec![],
+                    span: Span::default(),
+                }),
+            ])),
+            doc: None,
+            deps: vec![],
+            captured_env: None,
+        }),
+        Stmt::Expr(Expr::Call {
+            func: Box::new(Expr::Get("f".to_owned(), Span::default())),
+            args: vec![],
+            named: vec![],
+            span: Span::default(),
+        }),
+    ]);
+}
+
+#[test]
+fn skips_blocks_of_broken_stmts() {
+    let errs = parse_errs("fn f(a b) {\n    let = 1\n}\nf()");
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].message, "expected comma or right parenthesis");
+}
+
+#[test]
+fn reports_unclosed_block() {
+    let errs = parse_errs("if true {\n    print(1)\n");
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].message, "expected right brace");
+}
+
+#[test]
+fn reports_lexer_errors() {
+    let err = parse_err("echo 'foo");
+    assert_eq!(err.message, "unterminated string");
+    assert_eq!((err.span.line, err.span.col), (1, 6));
+}
+
+#[test]
+fn tracks_node_spans() {
+    match parse_expression("foo +\n  bar.baz") {
+        Expr::Binary(lhs, BinaryOp::Sum, rhs, span) => {
+            assert_eq!((span.line, span.col), (1, 5));
+            assert!(matches!(*lhs, Expr::Get(_, Span { line: 1, col: 1, .. })));
+            assert!(matches!(*rhs, Expr::GetField { span: Span { line: 2, col: 6, .. }, .. }));
+        }
+        expr => panic!("unexpected expression {:?}", expr),
+    }
+}
@@ -0,0 +1,107 @@
+use std::io;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use itertools::Itertools;
+
+use crate::ast::{Expr, Prog, Stmt};
+use crate::interp::{ErrorKind, Interpreter, Value};
+use crate::lexer::new as new_lexer;
+use crate::parser::{ParseError, Parser};
+use crate::source::Source;
+use crate::token::{Token, TokenKind};
+
+#[cfg(test)]
+mod test;
+
+pub enum Input {
+    Complete(Prog),
+    // Needs more lines, e.g. a brace or a string is still open
+    Incomplete,
+    Invalid(Vec<ParseError>),
+}
+
+/// Parses what the user typed so far. Input that is a whole expression is evaluated as one, unless it starts with a
+/// name that isn't defined in `interpreter`, as in `ls -la`, which is left to be run as a command.
+pub fn parse_input(text: &str, interpreter: &Interpreter) -> Input {
+    if let Some(expr) = parse_expr_input(text, interpreter) {
+        return Input::Complete(vec![Stmt::Expr(expr)]);
+    }
+
+    let (prog, errors) = Parser::new(new_lexer(make_source(text))).parse();
+
+    // Errors found at the very end mean that the parser ran out of input, not that the input is wrong
+    let end = text.trim_end().len();
+    if errors.iter().any(|err| err.message == "unterminated string" || err.span.start >= end) {
+        Input::Incomplete
+    } else if !errors.is_empty() {
+        Input::Invalid(errors)
+    } else {
+        Input::Complete(prog)
+    }
+}
+
+fn parse_expr_input(text: &str, interpreter: &Interpreter) -> Option<Expr> {
+    let mut lexer = new_lexer(make_source(text));
+    lexer.consume_whitespace(true);
+    if let Some(Token { kind: TokenKind::Identifier(name), .. }) = lexer.peek() {
+        if !interpreter.is_defined(name) {
+            return None;
+        }
+    }
+
+    Parser::new(new_lexer(make_source(text))).parse_lone_expr()
+}
+
+fn make_source(text: &str) -> Rc<Source> {
+    Source::new("<repl>".to_string(), text.to_string())
+}
+
+/// Reads inputs from stdin and runs them in `interpreter` until EOF, printing the value of expressions.
+pub fn run(interpreter: &mut Interpreter) {
+    let stdin = io::stdin();
+    let mut text = String::new();
+
+    loop {
+        print!("{}", if text.is_empty() { "> " } else { ". " });
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => {
+                println!();
+                return;
+            }
+            Ok(_) => (),
+            Err(err) => {
+                eprintln!("error: couldn't read input: {}", err);
+                return;
+            }
+        }
+
+        text.push_str(&line);
+        if text.trim().is_empty() {
+            text.clear();
+            continue;
+        }
+
+        let prog = match parse_input(&text, interpreter) {
+            Input::Complete(prog) => prog,
+            Input::Incomplete => continue,
+            Input::Invalid(errors) => {
+                eprint!("{}", errors.iter().join("\n"));
+                text.clear();
+                continue;
+            }
+        };
+
+        text.clear();
+
+        match interpreter.run_to_value(prog) {
+            Ok(Value::Nil) => (),
+            Ok(val) => println!("{}", val.to_string_quoted()),
+            Err(err) if err.kind == ErrorKind::Help => print!("{}", err),
+            Err(err) => eprint!("{}", err),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use super::*;
+
+fn is_incomplete(text: &str) -> bool {
+    matches!(parse_input(text, &Interpreter::new()), Input::Incomplete)
+}
+
+fn eval(interpreter: &mut Interpreter, text: &str) -> String {
+    match parse_input(text, interpreter) {
+        Input::Complete(prog) => interpreter.run_to_value(prog).unwrap().to_string_quoted(),
+        _ => panic!("{} doesn't parse", text),
+    }
+}
+
+#[test]
+fn detects_incomplete_input() {
+    assert!(is_incomplete("if true {\n"));
+    assert!(is_incomplete("fn f() {\n  print(1)\n"));
+    assert!(is_incomplete("let x = $(ls\n"));
+    assert!(is_incomplete("let s = 'abc\n"));
+    assert!(is_incomplete("let v = [1,\n"));
+    assert!(is_incomplete("ls |\n"));
+
+    assert!(!is_incomplete("if true {\n}\n"));
+    assert!(!is_incomplete("let s = 'abc\ndef'\n"));
+}
+
+#[test]
+fn rejects_invalid_input() {
+    assert!(matches!(parse_input("let x = )\n", &Interpreter::new()), Input::Invalid(_)));
+}
+
+#[test]
+fn keeps_state_between_inputs() {
+    let mut interpreter = Interpreter::new();
+    interpreter.do_collect();
+
+    assert_eq!(eval(&mut interpreter, "let x = 'a'\n"), "nil");
+    assert_eq!(eval(&mut interpreter, "fn f(s) { return s + x }\n"), "nil");
+    assert_eq!(eval(&mut interpreter, "f('b')\n"), "'ba'");
+    assert_eq!(eval(&mut interpreter, "x.split('')\n"), "['', 'a', '']");
+}
+
+#[test]
+fn evaluates_expressions() {
+    let mut interpreter = Interpreter::new();
+    interpreter.do_collect();
+
+    assert_eq!(eval(&mut interpreter, "let x = 2\n"), "nil");
+    assert_eq!(eval(&mut interpreter, "x\n"), "2");
+    assert_eq!(eval(&mut interpreter, "x + 1\n"), "3");
+    assert_eq!(eval(&mut interpreter, "1 + 2\n"), "3");
+    assert_eq!(eval(&mut interpreter, "[1, x]\n"), "[1, 2]");
+}
+
+#[test]
+fn parses_undefined_names_as_commands() {
+    let interpreter = Interpreter::new();
+
+    assert!(matches!(
+        parse_input("ls -la\n", &interpreter),
+        Input::Complete(prog) if matches!(prog.as_slice(), [Stmt::Cmd(_)])
+    ));
+}
@@ -0,0 +1,443 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{Cmd, Expr, Pattern, Prog, Stmt};
+use crate::interp::{Func, import_path};
+use crate::lexer::new as new_lexer;
+use crate::parser::Parser;
+use crate::source::{Source, Span};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+
+        write!(f, "{}", self.span.render_as(level, &self.message))
+    }
+}
+
+/// Finds mistakes that can be spotted without running the program: undefined variables, assignments to undeclared
+/// variables, unused `let`s and `break`, `continue` and `return` outside of loops and functions. `globals` are the
+/// names defined before the program runs (natives and OS environment variables).
+pub fn resolve(prog: &Prog, globals: Vec<String>, import_root: &Path) -> Vec<Diagnostic> {
+    let mut global_scope = Scope::default();
+    for name in globals {
+        global_scope.vars.insert(name, Decl::implicit());
+    }
+
+    let mut resolver = Resolver {
+        scopes: vec![global_scope],
+        loop_depth: 0,
+        func_depth: 0,
+        import_roots: vec![import_root.to_path_buf()],
+        imported: HashSet::new(),
+        diagnostics: Vec::new(),
+    };
+
+    resolver.resolve_stmts(prog, false);
+
+    let mut diagnostics = resolver.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
+
+#[derive(Default)]
+struct Scope {
+    vars: HashMap<String, Decl>,
+    // Functions declared anywhere in the block. A function can call those declared after it in the same block because
+    // they are defined by the time it runs.
+    funcs: HashSet<String>,
+    // Unused `let`s at the top level of a file are not reported, they may be meant for files that import it
+    is_top_level: bool,
+}
+
+struct Decl {
+    // None for names that come from outside of the program: globals and imported files
+    span: Option<Span>,
+    is_used: bool,
+    is_reported_if_unused: bool,
+}
+
+impl Decl {
+    fn implicit() -> Decl {
+        Decl {
+            span: None,
+            is_used: true,
+            is_reported_if_unused: false,
+        }
+    }
+}
+
+struct Resolver {
+    scopes: Vec<Scope>,
+    // Loops enclosing the current statement within the innermost function
+    loop_depth: usize,
+    func_depth: usize,
+    // Directory that imports are relative to, the last one is the one of the file being resolved
+    import_roots: Vec<PathBuf>,
+    // Canonical paths of the imported files whose names were already declared
+    imported: HashSet<PathBuf>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    fn resolve_stmts(&mut self, stmts: &[Stmt], is_block: bool) {
+        let funcs = stmts.iter().filter_map(|stmt| match stmt {
+            Stmt::Func(Func::User { name, .. }) => name.clone(),
+            _ => None,
+        }).collect();
+
+        self.scopes.push(Scope {
+            vars: HashMap::new(),
+            funcs,
+            is_top_level: !is_block && self.func_depth == 0,
+        });
+
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+
+        self.end_scope();
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        for (name, decl) in scope.vars {
+            self.check_unused(&name, decl);
+        }
+    }
+
+    fn check_unused(&mut self, name: &str, decl: Decl) {
+        if decl.is_used || !decl.is_reported_if_unused || name.starts_with('_') {
+            return;
+        }
+
+        if let Some(span) = decl.span {
+            self.warning(format!("unused variable {}", name), span);
+        }
+    }
+
+    fn declare(&mut self, name: &str, span: Option<Span>, is_reported_if_unused: bool) {
+        let scope = self.scopes.last_mut().unwrap();
+        let is_reported_if_unused = is_reported_if_unused && !scope.is_top_level;
+
+        let old = scope.vars.insert(name.to_string(), Decl {
+            span,
+            is_used: !is_reported_if_unused,
+            is_reported_if_unused,
+        });
+
+        if let Some(old) = old {
+            self.check_unused(name, old);
+        }
+    }
+
+    fn resolve_get(&mut self, name: &str, span: &Span) {
+        let in_func = self.func_depth > 0;
+
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(decl) = scope.vars.get_mut(name) {
+                decl.is_used = true;
+                return;
+            }
+
+            if in_func && scope.funcs.contains(name) {
+                return;
+            }
+        }
+
+        // Only a warning, since it could be an environment variable that's unset now but not when the program runs
+        self.warning(format!("undefined variable {}", name), span.clone());
+    }
+
+    fn resolve_set(&mut self, name: &str, span: &Span) {
+        if !self.scopes.iter().any(|scope| scope.vars.contains_key(name)) {
+            self.error(format!("assignment to undeclared variable {}", name), span.clone());
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(expr) => self.resolve_expr(expr),
+            Stmt::Cmd(cmd) => self.resolve_cmd(cmd),
+            Stmt::Import(base_path, _) => self.resolve_import(base_path),
+            Stmt::Let { is_exp, pattern, init, .. } => {
+                if let Some(init) = init {
+                    self.resolve_expr(init);
+                }
+
+                self.resolve_pattern_exprs(pattern);
+
+                // Exported variables are used by the commands that the script runs
+                for (name, span) in pattern.names() {
+                    self.declare(&name, span, !is_exp);
+                }
+            }
+            Stmt::Block(stmts) => self.resolve_stmts(stmts, true),
+            Stmt::If { cond, then_do, else_do } => {
+                self.resolve_expr(cond);
+                self.resolve_stmt(then_do);
+                if let Some(else_do) = else_do {
+                    self.resolve_stmt(else_do);
+                }
+            }
+            Stmt::For { lvar, rvar, iterated, each_do, .. } => {
+                self.resolve_expr(iterated);
+
+                self.scopes.push(Scope::default());
+                for pattern in iter::once(lvar).chain(rvar) {
+                    self.resolve_pattern(pattern);
+                }
+
+                self.loop_depth += 1;
+                self.resolve_stmt(each_do);
+                self.loop_depth -= 1;
+
+                self.end_scope();
+            }
+            Stmt::While { cond, then_do } => {
+                self.resolve_expr(cond);
+
+                self.loop_depth += 1;
+                self.resolve_stmt(then_do);
+                self.loop_depth -= 1;
+            }
+            Stmt::Func(func) => {
+                if let Func::User { name: Some(name), .. } = func {
+                    self.declare(name, None, false);
+                }
+
+                self.resolve_func(func);
+            }
+            Stmt::Continue(span) if self.loop_depth == 0 => self.error("continue outside loop".to_string(), span.clone()),
+            Stmt::Break(span) if self.loop_depth == 0 => self.error("break outside loop".to_string(), span.clone()),
+            Stmt::Continue(_) | Stmt::Break(_) => (),
+            Stmt::Return(expr, span) => {
+                if let Some(expr) = expr {
+                    self.resolve_expr(expr);
+                }
+
+                if self.func_depth == 0 {
+                    self.error("return outside function".to_string(), span.clone());
+                }
+            }
+            Stmt::Try { try_do, err_var, catch_do, finally_do } => {
+                self.resolve_stmt(try_do);
+
+                if let Some(catch_do) = catch_do {
+                    self.scopes.push(Scope::default());
+                    if let Some(err_var) = err_var {
+                        self.declare(err_var, None, false);
+                    }
+                    self.resolve_stmt(catch_do);
+                    self.end_scope();
+                }
+
+                if let Some(finally_do) = finally_do {
+                    self.resolve_stmt(finally_do);
+                }
+            }
+            Stmt::Throw(expr, _) => self.resolve_expr(expr),
+            Stmt::Match { subject, arms } => {
+                self.resolve_expr(subject);
+
+                for arm in arms {
+                    self.scopes.push(Scope::default());
+                    self.resolve_pattern(&arm.pattern);
+                    self.resolve_stmt(&arm.body);
+                    self.end_scope();
+                }
+            }
+        }
+    }
+
+    /// Declares the variables bound by `pattern`, once the expressions in it are resolved since they can't see them.
+    fn resolve_pattern(&mut self, pattern: &Pattern) {
+        self.resolve_pattern_exprs(pattern);
+
+        for (name, _) in pattern.names() {
+            self.declare(&name, None, false);
+        }
+    }
+
+    fn resolve_pattern_exprs(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Any | Pattern::Bind(..) | Pattern::Range { .. } | Pattern::Regex(_) | Pattern::Glob(_) => (),
+            Pattern::Value(expr) => self.resolve_expr(expr),
+            Pattern::Vec { items: patterns, .. } | Pattern::Or(patterns) => {
+                for pattern in patterns {
+                    self.resolve_pattern_exprs(pattern);
+                }
+            }
+            Pattern::Dict(entries) => {
+                for (_, pattern) in entries {
+                    self.resolve_pattern_exprs(pattern);
+                }
+            }
+            Pattern::Default { pattern, default, .. } => {
+                self.resolve_expr(default);
+                self.resolve_pattern_exprs(pattern);
+            }
+        }
+    }
+
+    fn resolve_func(&mut self, func: &Func) {
+        let (params, rest, body) = match func {
+            Func::User { params, rest, body, .. } => (params, rest, body),
+            Func::Native { .. } => return,
+        };
+
+        self.scopes.push(Scope::default());
+        for param in params.iter().chain(rest.as_deref()) {
+            self.resolve_pattern(param);
+        }
+
+        // Loops outside of the function can't be broken from inside of it
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.func_depth += 1;
+
+        self.resolve_stmt(body);
+
+        self.func_depth -= 1;
+        self.loop_depth = loop_depth;
+
+        self.end_scope();
+    }
+
+    /// Imported files run in the environment of the importer, so everything they define at the top level becomes
+    /// visible to it.
+    fn resolve_import(&mut self, base_path: &str) {
+        let path = import_path(self.import_roots.last().unwrap(), base_path);
+
+        // The interpreter reports files that can't be read or parsed when it gets to the import
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => return,
+        };
+
+        if let Ok(canonical_path) = fs::canonicalize(&path) {
+            if !self.imported.insert(canonical_path) {
+                return;
+            }
+        }
+
+        let (prog, _) = Parser::new(new_lexer(Source::new(path.display().to_string(), text))).parse();
+
+        self.import_roots.push(path.parent().map(Path::to_path_buf).unwrap_or_default());
+
+        for stmt in &prog {
+            match stmt {
+                Stmt::Let { pattern, .. } => {
+                    for (name, _) in pattern.names() {
+                        self.scopes.last_mut().unwrap().vars.insert(name, Decl::implicit());
+                    }
+                }
+                Stmt::Func(Func::User { name: Some(name), .. }) => {
+                    self.scopes.last_mut().unwrap().vars.insert(name.clone(), Decl::implicit());
+                }
+                Stmt::Import(base_path, _) => self.resolve_import(base_path),
+                _ => (),
+            }
+        }
+
+        self.import_roots.pop();
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_) => (),
+            Expr::Vec(exprs) | Expr::Interp { exprs, .. } => {
+                for expr in exprs {
+                    self.resolve_expr(expr);
+                }
+            }
+            Expr::Dict(dict) => {
+                for expr in dict.values() {
+                    self.resolve_expr(expr);
+                }
+            }
+            Expr::Range { l, r, step, .. } => {
+                self.resolve_expr(l);
+                self.resolve_expr(r);
+                if let Some(step) = step {
+                    self.resolve_expr(step);
+                }
+            }
+            Expr::Binary(l, _, r, _) | Expr::GetField { base: l, index: r, .. } => {
+                self.resolve_expr(l);
+                self.resolve_expr(r);
+            }
+            Expr::Unary(_, expr, _) => self.resolve_expr(expr),
+            Expr::Get(name, span) => self.resolve_get(name, span),
+            Expr::Set(name, expr, span) => {
+                self.resolve_expr(expr);
+                self.resolve_set(name, span);
+            }
+            Expr::SetField { base, index, expr, .. } => {
+                self.resolve_expr(base);
+                self.resolve_expr(index);
+                self.resolve_expr(expr);
+            }
+            Expr::Call { func, args, named, .. } => {
+                self.resolve_expr(func);
+                for arg in args.iter().chain(named.iter().map(|(_, arg)| arg)) {
+                    self.resolve_expr(arg);
+                }
+            }
+            Expr::Cmd(cmd) => self.resolve_cmd(cmd),
+            Expr::Lambda(func) => self.resolve_func(func),
+        }
+    }
+
+    fn resolve_cmd(&mut self, cmd: &Cmd) {
+        match cmd {
+            Cmd::Atom(segments, _) => {
+                for expr in segments.iter().flatten() {
+                    self.resolve_expr(expr);
+                }
+            }
+            Cmd::Op(lhs, _, rhs) => {
+                self.resolve_cmd(lhs);
+                self.resolve_cmd(rhs);
+            }
+        }
+    }
+
+    fn error(&mut self, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic {
+            level: Level::Error,
+            message,
+            span,
+        });
+    }
+
+    fn warning(&mut self, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic {
+            level: Level::Warning,
+            message,
+            span,
+        });
+    }
+}
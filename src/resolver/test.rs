@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use crate::lexer::new as new_lexer;
+use crate::parser::Parser;
+use crate::source::Source;
+
+use super::*;
+
+fn diagnostics(source: &str) -> Vec<(Level, String, usize, usize)> {
+    let lexer = new_lexer(Source::new(String::new(), source.to_owned()));
+    let (prog, errors) = Parser::new(lexer).parse();
+    assert!(errors.is_empty());
+
+    resolve(&prog, vec!["print".to_string(), "HOME".to_string(), "rc".to_string()], &PathBuf::from("src/interp/golden"))
+        .into_iter()
+        .map(|diagnostic| (diagnostic.level, diagnostic.message, diagnostic.span.line, diagnostic.span.col))
+        .collect()
+}
+
+fn error(message: &str, line: usize, col: usize) -> (Level, String, usize, usize) {
+    (Level::Error, message.to_string(), line, col)
+}
+
+fn warning(message: &str, line: usize, col: usize) -> (Level, String, usize, usize) {
+    (Level::Warning, message.to_string(), line, col)
+}
+
+#[test]
+fn accepts_defined_names() {
+    assert_eq!(diagnostics("let x = 1\nprint(x, HOME, rc)\nfn f(a) { return a }\nprint(f(x))"), vec![]);
+}
+
+#[test]
+fn reports_undefined_variables() {
+    assert_eq!(diagnostics("print(x)\nlet y = y"), vec![
+        warning("undefined variable x", 1, 7),
+        warning("undefined variable y", 2, 9),
+    ]);
+}
+
+#[test]
+fn reports_assignment_to_undeclared() {
+    assert_eq!(diagnostics("x = 1\nlet y = 1\ny = 2"), vec![
+        error("assignment to undeclared variable x", 1, 1),
+    ]);
+}
+
+#[test]
+fn reports_unused_lets() {
+    assert_eq!(diagnostics("let top = 1\nif true {\n    let x = 1\n    let _y = 2\n    exp let Z = 3\n}"), vec![
+        warning("unused variable x", 3, 9),
+    ]);
+}
+
+#[test]
+fn reports_misplaced_control_flow() {
+    assert_eq!(diagnostics("break\nwhile true { continue }\nreturn\nfor i in 0..1 {\n    let f = fn() { break }\n    f()\n}"), vec![
+        error("break outside loop", 1, 1),
+        error("return outside function", 3, 1),
+        error("break outside loop", 5, 20),
+    ]);
+}
+
+#[test]
+fn resolves_scopes() {
+    assert_eq!(diagnostics("{\n    let x = 1\n    print(x)\n}\nprint(x)"), vec![
+        warning("undefined variable x", 5, 7),
+    ]);
+
+    assert_eq!(diagnostics("for k, v in {} { print(k, v) }\ntry {} catch err { print(err) }"), vec![]);
+}
+
+#[test]
+fn resolves_match_bindings() {
+    let source = "match 1 {
+    re '(?P<major>\\d+)' => print(major)
+    [a, ...rest] => print(a, rest)
+    {name} | [name] => print(name)
+    _ => print(a)
+}";
+
+    assert_eq!(diagnostics(source), vec![
+        warning("undefined variable a", 5, 16),
+    ]);
+}
+
+#[test]
+fn resolves_destructuring() {
+    let source = "fn f([a, b = a]) {}
+if true {
+    let {x, y: [z, ...rest]} = {}
+    print(x, rest)
+}
+for _, {k} in [] { print(k) }";
+
+    assert_eq!(diagnostics(source), vec![
+        warning("undefined variable a", 1, 14),
+        warning("unused variable z", 3, 17),
+    ]);
+}
+
+#[test]
+fn sees_functions_declared_later() {
+    assert_eq!(diagnostics("fn f() { g() }\nfn g() { f() }\nf()"), vec![]);
+    assert_eq!(diagnostics("fn f() { print(later) }\nlet later = 1"), vec![
+        warning("undefined variable later", 1, 16),
+    ]);
+}
+
+#[test]
+fn sees_imported_names() {
+    assert_eq!(diagnostics("import 'math'\nprint(double(2))"), vec![]);
+}
@@ -0,0 +1,105 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::fmt;
+use std::rc::Rc;
+
+#[cfg(test)]
+mod test;
+
+/// A piece of Koi code together with the name it should be reported under (usually a path).
+#[derive(PartialEq)]
+pub struct Source {
+    pub name: String,
+    pub text: String,
+}
+
+impl Source {
+    pub fn new(name: String, text: String) -> Rc<Source> {
+        Rc::new(Source {
+            name,
+            text,
+        })
+    }
+
+    pub fn line(&self, line: usize) -> &str {
+        self.text.lines().nth(line - 1).unwrap_or("")
+    }
+}
+
+/// Location of a token or AST node. `start` and `end` are byte offsets into the source text while `line` and `col`
+/// are 1-based and refer to the first character.
+#[derive(Clone, PartialEq)]
+pub struct Span {
+    pub source: Rc<Source>,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    /// Returns a span that starts where `self` starts and ends where `other` ends.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            end: other.end.max(self.end),
+            ..self.clone()
+        }
+    }
+
+    /// The source code the span covers.
+    pub fn text(&self) -> &str {
+        self.source.text.get(self.start..self.end).unwrap_or("")
+    }
+
+    /// Formats `message` as an error with the location and the offending source line underlined.
+    pub fn render(&self, message: &str) -> String {
+        self.render_as("error", message)
+    }
+
+    /// Like `render` but with a different severity, e.g. "warning".
+    pub fn render_as(&self, level: &str, message: &str) -> String {
+        let line = self.source.line(self.line);
+
+        let gutter = " ".repeat(self.line.to_string().len());
+
+        let prefix_width: usize = line.chars().take(self.col - 1).map(char_width).sum();
+
+        let underline_width = self.text().lines().next().unwrap_or("").chars().map(char_width).sum::<usize>().max(1);
+
+        format!(
+            "{}: {}\n{}--> {}\n{} |\n{} | {}\n{} | {}{}\n",
+            level, message,
+            gutter, self,
+            gutter,
+            self.line, line.replace('\t', "    "),
+            gutter, " ".repeat(prefix_width), "^".repeat(underline_width),
+        )
+    }
+}
+
+fn char_width(c: char) -> usize {
+    if c == '\t' { 4 } else { 1 }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span {
+            source: Source::new(String::new(), String::new()),
+            start: 0,
+            end: 0,
+            line: 1,
+            col: 1,
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.source.name, self.line, self.col)
+    }
+}
+
+impl Debug for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
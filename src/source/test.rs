@@ -0,0 +1,51 @@
+use super::*;
+
+fn span(text: &str, start: usize, end: usize, line: usize, col: usize) -> Span {
+    Span {
+        source: Source::new("Koifile".to_string(), text.to_string()),
+        start,
+        end,
+        line,
+        col,
+    }
+}
+
+#[test]
+fn displays_location() {
+    assert_eq!(span("foo\nbar baz", 8, 11, 2, 5).to_string(), "Koifile:2:5");
+}
+
+#[test]
+fn renders_underlined_line() {
+    assert_eq!(span("foo\nbar baz\nqux", 8, 11, 2, 5).render("undefined variable baz"), [
+        "error: undefined variable baz",
+        " --> Koifile:2:5",
+        "  |",
+        "2 | bar baz",
+        "  |     ^^^",
+        "",
+    ].join("\n"));
+}
+
+#[test]
+fn renders_first_line_of_multiline_span() {
+    assert_eq!(span("f(\n1\n)", 1, 6, 1, 2).render("bad call"), [
+        "error: bad call",
+        " --> Koifile:1:2",
+        "  |",
+        "1 | f(",
+        "  |  ^",
+        "",
+    ].join("\n"));
+}
+
+#[test]
+fn joins_spans() {
+    let source = Source::new(String::new(), "foo bar".to_string());
+
+    let l = Span { source: Rc::clone(&source), start: 0, end: 3, line: 1, col: 1 };
+    let r = Span { source, start: 4, end: 7, line: 1, col: 5 };
+
+    let joined = l.to(&r);
+    assert_eq!((joined.start, joined.end, joined.line, joined.col), (0, 7, 1, 1));
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use itertools::Itertools;
+
+use crate::ast::{Pattern, Prog, Stmt};
+use crate::interp::{arity_error, ErrorKind, Func, Interpreter, RuntimeError, RuntimeResult, Value};
+
+#[cfg(test)]
+mod test;
+
+/// Finds the `Koifile` in `dir` or in the closest of its ancestors.
+pub fn find_koifile(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|dir| dir.join("Koifile")).find(|path| path.is_file())
+}
+
+/// Works out what to run when `target` is called from the command line: the functions it `needs`, recursively, and
+/// then `target` itself. Every function shows up once and after everything it needs. Functions are grouped in stages
+/// where none needs another, so the functions of a stage could run at the same time. The last stage is `[target]`.
+pub fn plan(interpreter: &Interpreter, target: &str) -> RuntimeResult<Vec<Vec<String>>> {
+    let mut planner = Planner {
+        interpreter,
+        stages: HashMap::new(),
+        order: Vec::new(),
+        path: Vec::new(),
+    };
+
+    let last_stage = planner.visit(target)?;
+
+    let mut stages = vec![Vec::new(); last_stage + 1];
+    for name in planner.order {
+        stages[planner.stages[&name]].push(name);
+    }
+
+    Ok(stages)
+}
+
+/// Arguments to call a function with, by position and by name.
+pub type BoundArgs = (Vec<Value>, Vec<(String, Value)>);
+
+/// Turns the command line arguments given to the function `name` into the arguments it's called with, by position and
+/// by name. Arguments are bound by position, as `param=value` or as `--param value`, and become a number or a bool when
+/// they read as one. Parameters with a default may be left out, and the arguments left over go to the rest parameter.
+pub fn bind_args(
+    name: &str,
+    params: &[Pattern],
+    rest: Option<&Pattern>,
+    args: &[String],
+) -> RuntimeResult<BoundArgs> {
+    let names = params.iter().filter_map(Pattern::param_name).collect::<Vec<&str>>();
+
+    let mut named = HashMap::new();
+    let mut positional = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(param) = arg.strip_prefix("--").filter(|param| names.contains(param)) {
+            let val = args.next().ok_or_else(|| RuntimeError::new(
+                ErrorKind::Value,
+                format!("missing value for --{}", param),
+            ))?;
+            named.insert(param, val.as_str());
+            continue;
+        }
+
+        match arg.split_once('=') {
+            Some((param, val)) if names.contains(&param) => {
+                named.insert(param, val);
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    let is_named = !named.is_empty();
+    let given = named.len() + positional.len();
+    let mut positional = positional.into_iter();
+
+    let mut bound = (Vec::new(), Vec::new());
+    // Once a parameter is left to its default, the ones after it can only be given by name
+    let mut is_skipped = false;
+    let mut missing = None;
+
+    for param in params {
+        let param_name = param.param_name();
+
+        match param_name.and_then(|name| named.remove(name)).or_else(|| positional.next()).map(parse_arg) {
+            Some(val) if is_skipped => bound.1.push((param_name.unwrap().to_string(), val)),
+            Some(val) => bound.0.push(val),
+            None if matches!(param, Pattern::Default { .. }) => is_skipped = true,
+            None => missing = missing.or(Some(param)),
+        }
+    }
+
+    if rest.is_some() {
+        bound.0.extend(positional.by_ref().map(parse_arg));
+    }
+
+    let mut signature = params.iter().map(ToString::to_string).collect::<Vec<String>>();
+    signature.extend(rest.map(|rest| format!("...{}", rest)));
+    let callee = format!("{}({})", name, signature.join(", "));
+
+    if let Some(param) = missing.filter(|_| is_named) {
+        return Err(RuntimeError::new(ErrorKind::Arity, format!("{} is missing argument {}", callee, param)));
+    }
+
+    if missing.is_some() || positional.next().is_some() {
+        let min = params.iter().filter(|param| !matches!(param, Pattern::Default { .. })).count();
+        let max = if rest.is_some() { None } else { Some(params.len()) };
+
+        return Err(arity_error(&callee, min, max, given));
+    }
+
+    Ok(bound)
+}
+
+fn parse_arg(arg: &str) -> Value {
+    match arg {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        // Not words like `inf` or `nan`, which parse as floats
+        _ => Value::parse_num(arg).unwrap_or_else(|| Value::String(arg.to_string())),
+    }
+}
+
+struct Planner<'a> {
+    interpreter: &'a Interpreter,
+    // Stage of every function visited so far
+    stages: HashMap<String, usize>,
+    // Functions in the order they were visited, dependencies first
+    order: Vec<String>,
+    // Chain of functions that led to the one being visited, to detect cycles
+    path: Vec<String>,
+}
+
+impl Planner<'_> {
+    fn visit(&mut self, name: &str) -> RuntimeResult<usize> {
+        if let Some(stage) = self.stages.get(name) {
+            return Ok(*stage);
+        }
+
+        if let Some(i) = self.path.iter().position(|visiting| visiting == name) {
+            return Err(RuntimeError::new(ErrorKind::Value, format!(
+                "dependency cycle: {}",
+                self.path[i..].iter().map(String::as_str).chain(iter::once(name)).join(" -> "),
+            )));
+        }
+
+        let deps = match self.interpreter.get(name) {
+            Value::Func(Func::User { deps, .. }) => deps,
+            // Calling the target reports it if it's not a function
+            _ if self.path.is_empty() => Vec::new(),
+            _ => return Err(RuntimeError::new(ErrorKind::Name, format!(
+                "{} needs {}, which isn't a function",
+                self.path.last().unwrap(),
+                name,
+            ))),
+        };
+
+        self.path.push(name.to_string());
+
+        let mut stage = 0;
+        for dep in &deps {
+            stage = stage.max(self.visit(dep)? + 1);
+        }
+
+        self.path.pop();
+
+        self.stages.insert(name.to_string(), stage);
+        self.order.push(name.to_string());
+
+        Ok(stage)
+    }
+}
+
+/// The statements of `prog` to run before calling a function in a process of its own: the functions and the imports,
+/// without the rest of the top level.
+pub fn definitions(prog: Prog) -> Prog {
+    prog.into_iter().filter(|stmt| matches!(stmt, Stmt::Func(_) | Stmt::Import(..))).collect()
+}
+
+/// The command that runs the script at `path` with the koi executable `exe` and calls `name` alone, as a function
+/// needed by another one: only its functions and imports are defined, and `script_args` are only given to `args`.
+/// `flags` are the names of koi's flags to pass on.
+pub fn call_cmd(exe: &Path, path: &Path, name: &str, flags: &[&str], script_args: &[String]) -> Command {
+    let mut cmd = Command::new(exe);
+    cmd.arg(path).arg("--fn").arg(name).arg("--no-deps").arg("--no-fn-args").arg("--no-top-level");
+    for flag in flags {
+        cmd.arg(format!("--{}", flag));
+    }
+    cmd.arg("--").args(script_args);
+
+    cmd
+}
@@ -0,0 +1,139 @@
+use crate::lexer::new as new_lexer;
+use crate::parser::Parser;
+use crate::source::Source;
+
+use super::*;
+
+use std::fs;
+
+fn interpreter(source: &str) -> Interpreter {
+    let (prog, errors) = Parser::new(new_lexer(Source::new(String::new(), source.to_owned()))).parse();
+    assert!(errors.is_empty());
+
+    let mut interpreter = Interpreter::new();
+    interpreter.do_collect();
+    interpreter.run(prog).unwrap();
+    interpreter
+}
+
+// Every stage as its functions separated by spaces
+fn stages(source: &str, target: &str) -> Vec<String> {
+    plan(&interpreter(source), target).unwrap().iter().map(|stage| stage.join(" ")).collect()
+}
+
+#[test]
+fn plans_dependencies_once_in_order() {
+    let source = "
+fn clean() {}
+fn build() needs clean {}
+fn lint() {}
+fn test() needs build, lint {}
+fn deploy() needs build, test {}
+";
+
+    assert_eq!(stages(source, "deploy"), vec!["clean lint", "build", "test", "deploy"]);
+    assert_eq!(stages(source, "lint"), vec!["lint"]);
+}
+
+#[test]
+fn leaves_unknown_target_to_the_call() {
+    assert_eq!(stages("", "nope"), vec!["nope"]);
+}
+
+#[test]
+fn reports_cycles() {
+    let err = plan(&interpreter("fn a() needs b {}\nfn b() needs c {}\nfn c() needs a {}"), "a").unwrap_err();
+    assert_eq!(err.message, "dependency cycle: a -> b -> c -> a");
+
+    let err = plan(&interpreter("fn a() needs a {}"), "a").unwrap_err();
+    assert_eq!(err.message, "dependency cycle: a -> a");
+}
+
+#[test]
+fn reports_missing_dependencies() {
+    let err = plan(&interpreter("fn a() needs b {}"), "a").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Name);
+    assert_eq!(err.message, "a needs b, which isn't a function");
+}
+
+fn bind(params: &str, args: &[&str]) -> RuntimeResult<BoundArgs> {
+    let (params, rest) = match interpreter(&format!("fn deploy({}) {{}}", params)).get("deploy") {
+        Value::Func(Func::User { params, rest, .. }) => (params, rest),
+        _ => unreachable!(),
+    };
+    let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+    bind_args("deploy", &params, rest.as_deref(), &args)
+}
+
+#[test]
+fn binds_args_to_params() {
+    let staging = Value::String("staging".to_owned());
+
+    assert_eq!(bind("env, count", &["staging", "3"]).unwrap(), (vec![staging.clone(), Value::Num(3.0)], vec![]));
+    assert_eq!(bind("env, count", &["count=3", "staging"]).unwrap(), (vec![staging.clone(), Value::Num(3.0)], vec![]));
+    assert_eq!(bind("env, dry", &["--dry", "true", "--env", "staging"]).unwrap(), (vec![staging.clone(), Value::Bool(true)], vec![]));
+    assert_eq!(bind("env", &["a=b"]).unwrap(), (vec![Value::String("a=b".to_owned())], vec![]));
+    assert_eq!(bind("env", &["nan"]).unwrap(), (vec![Value::String("nan".to_owned())], vec![]));
+}
+
+#[test]
+fn binds_args_to_defaults_and_rest() {
+    let staging = Value::String("staging".to_owned());
+
+    assert_eq!(bind("env, count = 1", &["staging"]).unwrap(), (vec![staging.clone()], vec![]));
+    assert_eq!(
+        bind("env, count = 1, dry = false", &["staging", "--dry", "true"]).unwrap(),
+        (vec![staging.clone()], vec![("dry".to_owned(), Value::Bool(true))]),
+    );
+    assert_eq!(
+        bind("env, ...hosts", &["staging", "a", "b"]).unwrap(),
+        (vec![staging, Value::String("a".to_owned()), Value::String("b".to_owned())], vec![]),
+    );
+}
+
+#[test]
+fn reports_wrong_number_of_args() {
+    let err = bind("env, count", &["staging"]).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Arity);
+    assert_eq!(err.message, "deploy(env, count) expects 2 arguments, got 1");
+
+    assert_eq!(bind("env", &["a", "b"]).unwrap_err().message, "deploy(env) expects 1 argument, got 2");
+    assert_eq!(bind("env", &["--env"]).unwrap_err().message, "missing value for --env");
+    assert_eq!(bind("env, count = 1", &[]).unwrap_err().message, "deploy(env, count = 1) expects 1 to 2 arguments, got 0");
+    assert_eq!(bind("env, ...hosts", &[]).unwrap_err().message, "deploy(env, ...hosts) expects at least 1 argument, got 0");
+    assert_eq!(bind("env, count = 1", &["count=2"]).unwrap_err().message, "deploy(env, count = 1) is missing argument env");
+}
+
+#[test]
+fn finds_koifile_in_ancestors() {
+    let root = std::env::temp_dir().join(format!("koi-find-koifile-{}", std::process::id()));
+    let nested = root.join("a").join("b");
+    fs::create_dir_all(&nested).unwrap();
+    fs::create_dir_all(root.join("a").join("Koifile")).unwrap();
+
+    // Directories named Koifile don't count
+    assert_eq!(find_koifile(&nested).filter(|path| path.starts_with(&root)), None);
+
+    fs::write(root.join("Koifile"), "").unwrap();
+    assert_eq!(find_koifile(&nested), Some(root.join("Koifile")));
+    assert_eq!(find_koifile(&root), Some(root.join("Koifile")));
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn passes_script_args_to_parallel_calls() {
+    let script_args = vec!["staging".to_string(), "--force".to_string()];
+    let cmd = call_cmd(Path::new("koi"), Path::new("Koifile"), "build", &["errexit"], &script_args);
+
+    assert_eq!(cmd.get_args().collect::<Vec<_>>(), vec![
+        "Koifile", "--fn", "build", "--no-deps", "--no-fn-args", "--no-top-level", "--errexit", "--", "staging", "--force",
+    ]);
+}
+
+#[test]
+fn keeps_only_definitions_for_parallel_calls() {
+    let (prog, _) = Parser::new(new_lexer(Source::new(String::new(), "import 'lib'\nprint(1)\nlet x = 1\nfn a() {}".to_owned()))).parse();
+
+    assert_eq!(definitions(prog).len(), 2);
+}
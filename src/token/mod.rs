@@ -1,12 +1,28 @@
+use crate::source::Span;
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub lexeme: String,
+    pub span: Span,
+}
+
+/// A `#` comment. Comments aren't tokens, the lexer sets them aside for tools that document code.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Comment {
+    // Without the leading `#`
+    pub text: String,
+    pub span: Span,
+    // Whether nothing but whitespace precedes the comment on its line
+    pub is_own_line: bool,
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum TokenKind {
     Identifier(String),
+    Int(i64),
+    // An integer literal too large for an int, which is only valid as a command argument
+    LargeInt,
     Num(f64),
     String {
         value: String,
@@ -25,6 +41,11 @@ pub enum TokenKind {
     Continue,
     Let,
     Exp,
+    Try,
+    Catch,
+    Finally,
+    Throw,
+    Match,
 
     LeftParen,
     RightParen,
@@ -42,8 +63,14 @@ pub enum TokenKind {
     MinusEqual,
     StarEqual,
     SlashEqual,
+    SlashSlashEqual,
     CaretEqual,
     PercEqual,
+    StarStarEqual,
+    AmperEqual,
+    PipeEqual,
+    LessLessEqual,
+    GreatGreatEqual,
 
     Great,
     GreatEqual,
@@ -54,8 +81,13 @@ pub enum TokenKind {
     Minus,
     Star,
     Slash,
+    SlashSlash,
     Caret,
     Perc,
+    StarStar,
+
+    Amper,
+    LessLess,
 
     True,
     False,
@@ -93,4 +125,6 @@ pub enum TokenKind {
     Newline,
 
     UnknownChar(char),
+    // Produced when the lexer can't make sense of the source, e.g. for an unterminated string
+    Error(String),
 }
@@ -0,0 +1,112 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use itertools::Itertools;
+
+#[cfg(test)]
+mod test;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to do with a run that's still going when files change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnChange {
+    // Kill it and start again
+    Restart,
+    // Let it finish and then start again
+    Wait,
+}
+
+/// Modification time of every file matched by the patterns.
+pub type Snapshot = HashMap<PathBuf, Option<SystemTime>>;
+
+pub fn snapshot(patterns: &[String]) -> Snapshot {
+    patterns.iter()
+        .filter_map(|pattern| glob::glob(pattern).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .map(|path| {
+            let modified = path.metadata().and_then(|metadata| metadata.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+/// Files added, removed or modified from `old` to `new`, sorted.
+pub fn changes(old: &Snapshot, new: &Snapshot) -> Vec<PathBuf> {
+    let added_or_modified = new.iter()
+        .filter(|(path, modified)| old.get(*path) != Some(modified))
+        .map(|(path, _)| path.clone());
+    let removed = old.keys().filter(|path| !new.contains_key(*path)).cloned();
+
+    added_or_modified.chain(removed).collect::<BTreeSet<PathBuf>>().into_iter().collect()
+}
+
+/// Runs `cmd` and runs it again whenever the files matched by `patterns` change, once they've stopped changing for
+/// `debounce`. Files are polled, so this works the same everywhere. Only returns if `cmd` can't be started.
+pub fn watch(patterns: &[String], debounce: Duration, on_change: OnChange, cmd: &mut Command) -> io::Result<()> {
+    // In its own process group, so that killing it also kills the commands it's running
+    cmd.process_group(0);
+
+    let mut files = snapshot(patterns);
+    let mut run = Some(cmd.spawn()?);
+    let mut changed = Vec::new();
+    let mut last_change = None;
+    let mut is_pending = false;
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let new_files = snapshot(patterns);
+        let new_changes = changes(&files, &new_files);
+        if !new_changes.is_empty() {
+            changed.extend(new_changes);
+            last_change = Some(Instant::now());
+            files = new_files;
+        }
+
+        if last_change.is_some_and(|last_change: Instant| last_change.elapsed() >= debounce) {
+            last_change = None;
+            is_pending = true;
+        }
+
+        if let Some(child) = &mut run {
+            if child.try_wait()?.is_some() {
+                run = None;
+            }
+        }
+
+        if !is_pending {
+            continue;
+        }
+
+        match &mut run {
+            Some(child) if on_change == OnChange::Restart => kill(child)?,
+            Some(_) => continue,
+            None => (),
+        }
+
+        changed.sort();
+        changed.dedup();
+        eprintln!("[watch] {} changed, running again", changed.iter().map(|path| path.display()).join(", "));
+
+        changed.clear();
+        is_pending = false;
+        run = Some(cmd.spawn()?);
+    }
+}
+
+fn kill(child: &mut Child) -> io::Result<()> {
+    let status = Command::new("kill").arg("-TERM").arg("--").arg(format!("-{}", child.id())).status();
+    if !status.is_ok_and(|status| status.success()) {
+        child.kill()?;
+    }
+    child.wait()?;
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use std::fs;
+use std::time::UNIX_EPOCH;
+
+use super::*;
+
+fn snapshot_of(files: &[(&str, u64)]) -> Snapshot {
+    files.iter()
+        .map(|(path, secs)| (PathBuf::from(path), Some(UNIX_EPOCH + Duration::from_secs(*secs))))
+        .collect()
+}
+
+#[test]
+fn finds_changes() {
+    let old = snapshot_of(&[("a", 1), ("b", 1), ("c", 1)]);
+    let new = snapshot_of(&[("a", 1), ("b", 2), ("d", 1)]);
+
+    assert_eq!(changes(&old, &new), vec![PathBuf::from("b"), PathBuf::from("c"), PathBuf::from("d")]);
+    assert!(changes(&new, &new).is_empty());
+}
+
+#[test]
+fn snapshots_matched_files() {
+    let root = std::env::temp_dir().join(format!("koi-watch-{}", std::process::id()));
+    fs::create_dir_all(root.join("src").join("nested")).unwrap();
+    fs::write(root.join("src").join("a.rs"), "").unwrap();
+    fs::write(root.join("src").join("nested").join("b.rs"), "").unwrap();
+    fs::write(root.join("src").join("c.txt"), "").unwrap();
+
+    let pattern = format!("{}/src/**/*.rs", root.display());
+    let mut files = snapshot(&[pattern]).into_keys().collect::<Vec<PathBuf>>();
+    files.sort();
+
+    assert_eq!(files, vec![root.join("src").join("a.rs"), root.join("src").join("nested").join("b.rs")]);
+
+    fs::remove_dir_all(&root).unwrap();
+}